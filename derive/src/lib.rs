@@ -0,0 +1,196 @@
+// #[derive(Parse)]: implement parser::parseable::Parseable from the definition of a type
+// (re-exported by the parser crate with its derive feature)
+//
+// a struct parses its fields in order, an enum is a oneof() over its variants (in declaration order)
+// each field is parsed by the Parseable implementation of its type, unless it has #[parse(with = "path")]
+// attributes, all in #[parse(...)]:
+// - tag = "x=": a literal before the struct, the variant or the field
+// - sep = ",": a literal between the fields (on the struct, the enum or a variant)
+// - skip_ws: ascii whitespace is skipped before each literal and each field (on any of them)
+// - with = "path::to::parser": a function returning the parser of the field (only on fields)
+// nothing is skipped after the last field: use all_consuming() or lexeme() around the parser if needed
+//
+// #[derive(Parse)]
+// #[parse(sep = ";")]
+// struct Point {
+//     #[parse(tag = "x=")]
+//     x: i64,
+//     #[parse(tag = "y=")]
+//     y: i64
+// }
+// parses "x=1;y=2"
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as Tokens;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, LitStr, Path};
+
+/// Implements `parser::parseable::Parseable` for a struct or an enum.
+///
+/// Every field needs a parser: a field type without a `Parseable` implementation
+/// (and without `#[parse(with = "...")]`) doesn't compile.
+///
+/// ```compile_fail
+/// use parser_derive::Parse;
+///
+/// struct Color(u8, u8, u8);
+///
+/// #[derive(Parse)]
+/// struct Pixel {
+///     x: u32,
+///     color: Color
+/// }
+/// ```
+#[proc_macro_derive(Parse, attributes(parse))]
+pub fn derive_parse(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match parseable(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into()
+    }
+}
+
+// the #[parse(...)] attributes of an item
+#[derive(Default)]
+struct Options {
+    tag: Option<LitStr>,
+    sep: Option<LitStr>,
+    skip_ws: bool,
+    with: Option<Path>
+}
+
+fn options(attrs: &[Attribute]) -> syn::Result<Options> {
+    let mut options = Options::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("parse")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") {
+                options.tag = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("sep") {
+                options.sep = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("skip_ws") {
+                options.skip_ws = true;
+            } else if meta.path.is_ident("with") {
+                let path: LitStr = meta.value()?.parse()?;
+                options.with = Some(path.parse()?);
+            } else {
+                return Err(meta.error("unknown parse attribute (expected tag, sep, skip_ws or with)"))
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+fn parseable(input: &DeriveInput) -> syn::Result<Tokens> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(input.generics.span(), "derive(Parse) doesn't support generic types"))
+    }
+    let container = options(&input.attrs)?;
+    if let Some(with) = &container.with {
+        return Err(syn::Error::new(with.span(), "with is only allowed on fields"))
+    }
+    let parser = match &input.data {
+        Data::Struct(data) => fields_parser(&data.fields, &container, quote!(Self))?,
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Err(syn::Error::new(input.ident.span(), "derive(Parse) needs at least one variant"))
+            }
+            let mut variants = Vec::new();
+            for variant in &data.variants {
+                let mut options = options(&variant.attrs)?;
+                if let Some(with) = &options.with {
+                    return Err(syn::Error::new(with.span(), "with is only allowed on fields"))
+                }
+                if options.tag.is_none() && variant.fields.is_empty() {
+                    let message = "a variant without fields needs a #[parse(tag = \"...\")]";
+                    return Err(syn::Error::new(variant.span(), message))
+                }
+                // the settings of the enum apply to its variants
+                options.skip_ws |= container.skip_ws;
+                options.sep = options.sep.or(container.sep.clone());
+                let ident = &variant.ident;
+                let parser = fields_parser(&variant.fields, &options, quote!(Self::#ident))?;
+                variants.push(tagged(parser, &options.tag, options.skip_ws));
+            }
+            quote!(::parser::combinator::oneof(::std::vec![#(#variants),*]))
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(data.union_token.span(), "derive(Parse) doesn't support unions"))
+        }
+    };
+    // (the tag of an enum is before any of its variants)
+    let parser = tagged(parser, &container.tag, container.skip_ws);
+    let name = &input.ident;
+    Ok(quote! {
+        impl ::parser::parseable::Parseable for #name {
+            fn parser() -> ::parser::Parser<Self> {
+                #parser
+            }
+        }
+    })
+}
+
+// parse the fields in order, then build the value with the constructor (Self or Self::Variant)
+// the options are those of the struct or the variant (their tag is added by the caller)
+fn fields_parser(fields: &Fields, container: &Options, constructor: Tokens) -> syn::Result<Tokens> {
+    let mut parsers = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let options = options(&field.attrs)?;
+        if let Some(sep) = &options.sep {
+            return Err(syn::Error::new(sep.span(), "sep is only allowed on structs, enums and variants"))
+        }
+        let skip_ws = options.skip_ws || container.skip_ws;
+        let ty = &field.ty;
+        let mut parser = match &options.with {
+            Some(path) => quote_spanned!(path.span()=> #path()),
+            None => quote_spanned!(ty.span()=> <#ty as ::parser::parseable::Parseable>::parser())
+        };
+        if skip_ws {
+            parser = quote!(::parser::combinator::preceded(::parser::primitive::multispace0(), #parser));
+        }
+        parser = tagged(parser, &options.tag, skip_ws);
+        if i > 0 {
+            parser = tagged(parser, &container.sep, skip_ws);
+        }
+        parsers.push(parser);
+    }
+
+    // f0 + (f1 + f2) into (v0, (v1, v2))
+    let values: Vec<_> = (0..parsers.len()).map(|i| format_ident!("v{}", i)).collect();
+    let (chain, pattern) = match parsers.pop() {
+        None => (quote!(::parser::primitive::pure(())), quote!(())),
+        Some(last) => {
+            let mut chain = last;
+            let mut pattern = {
+                let value = &values[parsers.len()];
+                quote!(#value)
+            };
+            while let Some(parser) = parsers.pop() {
+                let value = &values[parsers.len()];
+                chain = quote!(::parser::combinator::pair(#parser, #chain));
+                pattern = quote!((#value, #pattern));
+            }
+            (chain, pattern)
+        }
+    };
+    let value = match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote!(#constructor { #(#names: #values),* })
+        }
+        Fields::Unnamed(_) => quote!(#constructor(#(#values),*)),
+        Fields::Unit => quote!(#constructor)
+    };
+    Ok(quote!(::parser::combinator::process(|#pattern| #value, #chain)))
+}
+
+// the literal (if any) before the parser
+fn tagged(parser: Tokens, tag: &Option<LitStr>, skip_ws: bool) -> Tokens {
+    let Some(tag) = tag else { return parser };
+    let mut literal = quote!(::parser::primitive::tag_str(#tag));
+    if skip_ws {
+        literal = quote!(::parser::combinator::preceded(::parser::primitive::multispace0(), #literal));
+    }
+    quote!(::parser::combinator::preceded(#literal, #parser))
+}
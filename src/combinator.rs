@@ -0,0 +1,3916 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use crate::{describe_list, describe_node, Parse, Parser, Result};
+use crate::Result::*;
+use crate::error::{ErrorKind, Expected, ParseError, ParseFailure};
+use crate::primitive::{self, PureParser};
+
+// the junk is skipped between the parsers (see tokens())
+struct AndParser<T, E, I> {
+    parsers: Vec<Parser<T, E, I>>,
+    junk: Option<Parser<(), E, I>>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<Vec<T>, E, I> for AndParser<T, E, I> {
+    fn create(&self) -> Parser<Vec<T>, E, I> {
+        //let parsers = self.parsers.clone();
+        Box::new(AndParser { parsers: self.parsers.clone(), junk: self.junk.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_list(if self.junk.is_some() { "Tokens" } else { "And" }, depth, &self.parsers)
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<Vec<T>, E> {
+        let mut cursor = position;
+        let mut parsed = Vec::new();
+        for p in &self.parsers {
+            if let (Some(junk), false) = (&self.junk, parsed.is_empty()) {
+                match junk.parse(cursor, source) {
+                    Success(pos, ()) => cursor = pos,
+                    Fail(error) => return Fail(error),
+                    Error(error) => return Error(error)
+                }
+            }
+            let r = p.parse(cursor, source);
+            match r {
+                Fail(error) => {
+                    return Fail(error)
+                }
+                Error(error) => return Error(error),
+                Success(pos, data) => {
+                    parsed.push(data);
+                    cursor = pos;
+                }
+            }
+        }
+        Success(cursor, parsed)
+    }
+}
+
+pub fn concat<T: 'static, E: ParseFailure, I: 'static>(parsers: Vec<Parser<T, E, I>>) -> Parser<Vec<T>, E, I> {
+    AndParser { parsers, junk: None }.create()
+}
+
+// where tokens() and sep_by_tokens() skip junk, besides between their elements
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SkipPolicy {
+    // only between elements
+    Between,
+    // also before the first element
+    Leading,
+    // also after the last element
+    Trailing,
+    // also before the first and after the last element
+    Around
+}
+
+// the junk as the lists skip it: its results are dropped, and its failure means there is nothing to skip
+// (a fatal error is still fatal)
+fn junk_skipper<J: 'static, E: ParseFailure, I: 'static>(junk: Parser<J, E, I>) -> Parser<(), E, I> {
+    skip(optional(junk))
+}
+
+// the junk before the first and after the last element
+fn skip_edges<T: 'static, E: ParseFailure, I: 'static>(
+    junk: Parser<(), E, I>,
+    parser: Parser<T, E, I>,
+    policy: SkipPolicy
+) -> Parser<T, E, I> {
+    match policy {
+        SkipPolicy::Between => parser,
+        SkipPolicy::Leading => preceded(junk, parser),
+        SkipPolicy::Trailing => terminated(parser, junk),
+        SkipPolicy::Around => delimited(junk.clone(), parser, junk)
+    }
+}
+
+// concat() at the token level: the junk (whitespace, comments...) is skipped between the parsers
+// the junk is not passed down: the parsers themselves don't skip anything
+pub fn tokens<T: 'static, J: 'static, E: ParseFailure, I: 'static>(
+    junk: Parser<J, E, I>,
+    parsers: Vec<Parser<T, E, I>>
+) -> Parser<Vec<T>, E, I> {
+    tokens_with(junk, parsers, SkipPolicy::Between)
+}
+
+pub fn tokens_with<T: 'static, J: 'static, E: ParseFailure, I: 'static>(
+    junk: Parser<J, E, I>,
+    parsers: Vec<Parser<T, E, I>>,
+    policy: SkipPolicy
+) -> Parser<Vec<T>, E, I> {
+    let junk = junk_skipper(junk);
+    skip_edges(junk.clone(), AndParser { parsers, junk: Some(junk) }.create(), policy)
+}
+
+struct OrParser<T, E, I> {
+    parsers: Vec<Parser<T, E, I>>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for OrParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(OrParser { parsers: self.parsers.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_list("Or", depth, &self.parsers)
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        let mut error = None;
+        for p in &self.parsers {
+            match p.parse(position, source) {
+                // past a cut(): the other alternatives are not tried
+                Error(e) => return Error(e),
+                Fail(e) => error = Some(furthest(error, e)),
+                Success(pos, data) => return Success(pos, data)
+            }
+        }
+        Fail(error.unwrap_or_else(|| E::new(position, "no alternative to choose from")))
+    }
+
+    fn alternatives(&self) -> Option<&[Parser<T, E, I>]> {
+        Some(&self.parsers)
+    }
+}
+
+pub fn oneof<T: 'static, E: ParseFailure, I: 'static>(parsers: Vec<Parser<T, E, I>>) -> Parser<T, E, I> {
+    OrParser {parsers}.create()
+}
+
+
+// keep the deepest failure when several parsers were tried
+// (the expectations of the failures at the same position are combined)
+fn furthest<E: ParseFailure>(error: Option<E>, other: E) -> E {
+    match error {
+        None => other,
+        Some(error) => error.merge(other)
+    }
+}
+
+// unlike oneof(), every alternative is tried: the one that consumed the most input wins
+// (the first one declared, in case of a tie)
+struct LongestParser<T, E, I> {
+    parsers: Vec<Parser<T, E, I>>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for LongestParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(LongestParser { parsers: self.parsers.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_list("Longest", depth, &self.parsers)
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        let mut best = Fail(E::new(position, "no alternative to choose from"));
+        let mut error = None;
+        for p in &self.parsers {
+            match p.parse(position, source) {
+                Error(e) => return Error(e),
+                Fail(e) => error = Some(furthest(error, e)),
+                Success(pos, data) => match best {
+                    Success(best_pos, _) if best_pos >= pos => (),
+                    _ => best = Success(pos, data)
+                }
+            }
+        }
+        match (best, error) {
+            (Fail(_), Some(error)) => Fail(error),
+            (best, _) => best
+        }
+    }
+}
+
+pub fn longest_of<T: 'static, E: ParseFailure, I: 'static>(parsers: Vec<Parser<T, E, I>>) -> Parser<T, E, I> {
+    LongestParser { parsers }.create()
+}
+
+// every parser matches once, in any order
+// the results are in the declaration order, not in the input order
+struct PermutationParser<T, E, I> {
+    parsers: Vec<Parser<T, E, I>>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<Vec<T>, E, I> for PermutationParser<T, E, I> {
+    fn create(&self) -> Parser<Vec<T>, E, I> {
+        Box::new(PermutationParser { parsers: self.parsers.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_list("Permutation", depth, &self.parsers)
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<Vec<T>, E> {
+        let mut cursor = position;
+        let mut results: Vec<Option<T>> = self.parsers.iter().map(|_| None).collect();
+        for _ in 0..self.parsers.len() {
+            // first parser not matched yet that matches here
+            let mut error = None;
+            let mut matched = None;
+            for (i, p) in self.parsers.iter().enumerate() {
+                if results[i].is_some() {
+                    continue
+                }
+                match p.parse(cursor, source) {
+                    Error(e) => return Error(e),
+                    Fail(e) => error = Some(furthest(error, e)),
+                    Success(pos, data) => {
+                        matched = Some((i, pos, data));
+                        break
+                    }
+                }
+            }
+            match matched {
+                // there is at least one parser left, so there is an error
+                None => return Fail(error.unwrap()),
+                Some((i, pos, data)) => {
+                    results[i] = Some(data);
+                    cursor = pos;
+                }
+            }
+        }
+        Success(cursor, results.into_iter().map(Option::unwrap).collect())
+    }
+}
+
+pub fn permutation<T: 'static, E: ParseFailure, I: 'static>(parsers: Vec<Parser<T, E, I>>) -> Parser<Vec<T>, E, I> {
+    PermutationParser { parsers }.create()
+}
+
+// same as process(), but the parser fails when the function returns an error
+// (the message of the failure is the error of the function: "out of range integral type conversion attempted")
+struct MapResParser<T, U, R, E, I> {
+    parser: Parser<T, E, I>,
+    f: fn(T) -> std::result::Result<U, R>
+}
+
+impl<T: 'static, U: 'static, R: fmt::Display + 'static, E: ParseFailure, I: 'static> Parse<U, E, I>
+    for MapResParser<T, U, R, E, I> {
+    fn create(&self) -> Parser<U, E, I> {
+        Box::new(MapResParser { parser: self.parser.clone(), f: self.f })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("MapRes", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<U, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(end, data) => match (self.f)(data) {
+                Ok(data) => Success(end, data),
+                Err(error) => Fail(E::custom(position, error.to_string()))
+            }
+        }
+    }
+}
+
+pub fn map_res<T: 'static, U: 'static, R: fmt::Display + 'static, E: ParseFailure, I: 'static>(
+    f: fn(T) -> std::result::Result<U, R>,
+    parser: Parser<T, E, I>
+) -> Parser<U, E, I> {
+    MapResParser { parser, f }.create()
+}
+
+// only accept results that are matched by the filter function
+// the label is what the failure says was expected
+// (the filter can be a closure, shared like the function of process())
+struct FilterParser<T, E, I> {
+    parser: Parser<T, E, I>,
+    filter: Arc<dyn Fn(&T) -> bool + Send + Sync>,
+    label: Option<&'static str>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for FilterParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(FilterParser{parser: self.parser.clone(), filter: self.filter.clone(), label: self.label })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        match self.label {
+            Some(label) => describe_node("Filter", depth, |depth| {
+                vec![format!("{:?}", label), self.parser.describe_with(depth)]
+            }),
+            None => describe_node("Filter", depth, |depth| vec![self.parser.describe_with(depth)])
+        }
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => {
+                Fail(error)
+            }
+            Error(error) => Error(error),
+            Success(end, data) => {
+                if (self.filter)(&data) {
+                    Success(end, data)
+                } else {
+                    let error = match self.label {
+                        Some(label) => E::expected(position, Expected::Class(label)),
+                        None => E::new(position, "value rejected by require()")
+                    };
+                    Fail(error.with_kind(ErrorKind::PredicateFailed))
+                }
+            }
+        }
+    }
+}
+
+pub fn require<T: 'static, E: ParseFailure, I: 'static>(
+    f: impl Fn(&T) -> bool + Send + Sync + 'static,
+    p: Parser<T, E, I>
+) -> Parser<T, E, I> {
+    FilterParser { parser: p, filter: Arc::new(f), label: None }.create()
+}
+
+// require(), with a name for what is accepted: require_labeled("even number", |n| n % 2 == 0, ...)
+pub fn require_labeled<T: 'static, E: ParseFailure, I: 'static>(
+    label: &'static str,
+    f: impl Fn(&T) -> bool + Send + Sync + 'static,
+    p: Parser<T, E, I>
+) -> Parser<T, E, I> {
+    FilterParser { parser: p, filter: Arc::new(f), label: Some(label) }.create()
+}
+
+// same as require(), but the filter also sees the part of the source that was consumed
+struct VerifyParser<T, E, I> {
+    parser: Parser<T, E, I>,
+    filter: fn(&T, &[I]) -> bool
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for VerifyParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(VerifyParser { parser: self.parser.clone(), filter: self.filter })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Verify", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(end, data) if (self.filter)(&data, &source[position..end]) => Success(end, data),
+            Success(_, _) => Fail(E::new(position, "value rejected by verify()").with_kind(ErrorKind::PredicateFailed))
+        }
+    }
+}
+
+pub fn verify<T: 'static, E: ParseFailure, I: 'static>(
+    parser: Parser<T, E, I>,
+    filter: fn(&T, &[I]) -> bool
+) -> Parser<T, E, I> {
+    VerifyParser { parser, filter }.create()
+}
+
+
+// apply a function to the result of a successful parsing
+// (the function can be a closure: it is shared between the copies of the parser)
+struct MapParser<T, U, E, I> {
+    parser: Parser<T, E, I>,
+    f: Arc<dyn Fn(T) -> U + Send + Sync>
+}
+
+impl<T: 'static, U: 'static, E: ParseFailure, I: 'static> Parse<U, E, I> for MapParser<T, U, E, I> {
+    fn create(&self) -> Parser<U, E, I> {
+        Box::new(MapParser { parser: self.parser.clone(), f: self.f.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Map", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<U, E> {
+        let result = self.parser.parse(position, source);
+        match result {
+            Fail(error) => {
+                Fail(error)
+            }
+            Error(error) => Error(error),
+            Success(position, data) => {
+                Success(position, (self.f)(data))
+            }
+        }
+    }
+}
+
+pub fn process<T: 'static, U: 'static, E: ParseFailure, I: 'static>(
+    f: impl Fn(T) -> U + Send + Sync + 'static,
+    parser: Parser<T, E, I>
+) -> Parser<U, E, I> {
+    MapParser { parser, f: Arc::new(f) }.create()
+}
+
+// same as process(), but the parser fails when the function returns None
+struct MapOptParser<T, U, E, I> {
+    parser: Parser<T, E, I>,
+    f: fn(T) -> Option<U>
+}
+
+impl<T: 'static, U: 'static, E: ParseFailure, I: 'static> Parse<U, E, I> for MapOptParser<T, U, E, I> {
+    fn create(&self) -> Parser<U, E, I> {
+        Box::new(MapOptParser { parser: self.parser.clone(), f: self.f })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("MapOpt", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<U, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(end, data) => match (self.f)(data) {
+                Some(data) => Success(end, data),
+                None => Fail(E::new(position, "invalid value"))
+            }
+        }
+    }
+}
+
+pub fn map_opt<T: 'static, U: 'static, E: ParseFailure, I: 'static>(
+    f: fn(T) -> Option<U>,
+    parser: Parser<T, E, I>
+) -> Parser<U, E, I> {
+    MapOptParser { parser, f }.create()
+}
+
+// choose the next parser from the result of the previous one
+// (the next parser starts where the first one stopped)
+struct AndThenParser<T, U, E, I> {
+    parser: Parser<T, E, I>,
+    f: fn(T) -> Parser<U, E, I>
+}
+
+impl<T: 'static, U: 'static, E: ParseFailure, I: 'static> Parse<U, E, I> for AndThenParser<T, U, E, I> {
+    fn create(&self) -> Parser<U, E, I> {
+        Box::new(AndThenParser { parser: self.parser.clone(), f: self.f })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("AndThen", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<U, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(position, data) => (self.f)(data).parse(position, source)
+        }
+    }
+}
+
+pub fn and_then<T: 'static, U: 'static, E: ParseFailure, I: 'static>(
+    parser: Parser<T, E, I>,
+    f: fn(T) -> Parser<U, E, I>
+) -> Parser<U, E, I> {
+    AndThenParser { parser, f }.create()
+}
+
+// recursive grammars
+// the parser is defined after being used: the definition is shared (not copied) between clones
+// recursive() only keeps a weak reference to the grammar inside of its own definition,
+// while declare() is for mutually recursive rules, which keep each other alive forever
+enum Link<T, E, I> {
+    Strong(Arc<OnceLock<Parser<T, E, I>>>),
+    Weak(Weak<OnceLock<Parser<T, E, I>>>)
+}
+
+struct LazyParser<T, E, I> {
+    link: Link<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for LazyParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        let link = match &self.link {
+            Link::Strong(cell) => Link::Strong(cell.clone()),
+            Link::Weak(cell) => Link::Weak(cell.clone())
+        };
+        Box::new(LazyParser { link })
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        let cell = match &self.link {
+            Link::Strong(cell) => cell.clone(),
+            Link::Weak(cell) => cell.upgrade().expect("recursive parser used after the grammar was dropped")
+        };
+        match cell.get() {
+            Some(parser) => parser.parse(position, source),
+            None => panic!("parser used before define()")
+        }
+    }
+}
+
+// forward declaration of a parser
+pub struct Declaration<T, E = ParseError, I = u8> {
+    cell: Arc<OnceLock<Parser<T, E, I>>>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Declaration<T, E, I> {
+    // declare() for an other error type
+    pub fn new() -> Declaration<T, E, I> {
+        Declaration { cell: Arc::new(OnceLock::new()) }
+    }
+
+    // the declared parser, usable before it is defined
+    pub fn parser(&self) -> Parser<T, E, I> {
+        LazyParser { link: Link::Strong(self.cell.clone()) }.create()
+    }
+
+    pub fn define(&self, parser: Parser<T, E, I>) {
+        if self.cell.set(parser).is_err() {
+            panic!("parser defined twice")
+        }
+    }
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Default for Declaration<T, E, I> {
+    fn default() -> Self {
+        Declaration::new()
+    }
+}
+
+pub fn declare<T: 'static, E: ParseFailure, I: 'static>() -> Declaration<T, E, I> {
+    Declaration::new()
+}
+
+// build a parser that can refer to itself: recursive(|this| ...)
+// (f can capture the parsers it is built from: an atom, operators...)
+pub fn recursive<T: 'static, E: ParseFailure, I: 'static>(
+    f: impl FnOnce(Parser<T, E, I>) -> Parser<T, E, I>
+) -> Parser<T, E, I> {
+    let cell = Arc::new(OnceLock::new());
+    let this = LazyParser { link: Link::Weak(Arc::downgrade(&cell)) }.create();
+    // the cell is new, so this can't fail
+    let _ = cell.set(f(this));
+    LazyParser { link: Link::Strong(cell) }.create()
+}
+
+// a grammar in a static: built on the first use (by whichever thread gets there first), then shared
+// static NUMBERS: GrammarCell<Vec<u64>> = GrammarCell::new(|| sep_by(number::uint(), byte(b',')));
+// NUMBERS.parse(0, b"1,2,3") (through Deref to the parser)
+// parser() is a reference to the static grammar to use in other grammars (nothing is copied),
+// and it can be used in the definition itself: a static grammar can be recursive
+pub struct GrammarCell<T, E = ParseError, I = u8> {
+    cell: OnceLock<Parser<T, E, I>>,
+    init: fn() -> Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> GrammarCell<T, E, I> {
+    pub const fn new(init: fn() -> Parser<T, E, I>) -> GrammarCell<T, E, I> {
+        GrammarCell { cell: OnceLock::new(), init }
+    }
+
+    // the grammar, built if needed
+    pub fn get(&self) -> &Parser<T, E, I> {
+        self.cell.get_or_init(self.init)
+    }
+
+    pub fn parser(&'static self) -> Parser<T, E, I> {
+        StaticParser { grammar: self }.create()
+    }
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Deref for GrammarCell<T, E, I> {
+    type Target = Parser<T, E, I>;
+
+    fn deref(&self) -> &Parser<T, E, I> {
+        self.get()
+    }
+}
+
+struct StaticParser<T: 'static, E: 'static, I: 'static> {
+    grammar: &'static GrammarCell<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for StaticParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(StaticParser { grammar: self.grammar })
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        self.grammar.get().parse(position, source)
+    }
+}
+
+// replace the result of a parser with a constant
+struct ValueParser<T, U, E, I> {
+    value: T,
+    parser: Parser<U, E, I>
+}
+
+impl<T: Clone + Send + Sync + 'static, U: 'static, E: ParseFailure, I: 'static> Parse<T, E, I>
+    for ValueParser<T, U, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(ValueParser { value: self.value.clone(), parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Value", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(position, _) => Success(position, self.value.clone())
+        }
+    }
+}
+
+pub fn value<T: Clone + Send + Sync + 'static, U: 'static, E: ParseFailure, I: 'static>(
+    value: T,
+    parser: Parser<U, E, I>
+) -> Parser<T, E, I> {
+    ValueParser { value, parser }.create()
+}
+
+// the items (bytes) matched by a parser, instead of its result
+struct RecognizeParser<T, E, I> {
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: Clone + 'static> Parse<Vec<I>, E, I> for RecognizeParser<T, E, I> {
+    fn create(&self) -> Parser<Vec<I>, E, I> {
+        Box::new(RecognizeParser { parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Recognize", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<Vec<I>, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(end, _) => Success(end, source[position..end].to_vec())
+        }
+    }
+}
+
+pub fn recognize<T: 'static, E: ParseFailure, I: Clone + 'static>(parser: Parser<T, E, I>) -> Parser<Vec<I>, E, I> {
+    RecognizeParser { parser }.create()
+}
+
+// the items (bytes) matched by a parser, along with its result
+struct ConsumedParser<T, E, I> {
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: Clone + 'static> Parse<(Vec<I>, T), E, I> for ConsumedParser<T, E, I> {
+    fn create(&self) -> Parser<(Vec<I>, T), E, I> {
+        Box::new(ConsumedParser { parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Consumed", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<(Vec<I>, T), E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(end, data) => Success(end, (source[position..end].to_vec(), data))
+        }
+    }
+}
+
+pub fn consumed<T: 'static, E: ParseFailure, I: Clone + 'static>(
+    parser: Parser<T, E, I>
+) -> Parser<(Vec<I>, T), E, I> {
+    ConsumedParser { parser }.create()
+}
+
+// make a parser able to repeat as much as possible
+// fails if it matched less than min times, and stops after max matches
+// (star, many1, count and repeat_range all use this loop)
+struct StarParser<T, E, I> {
+    parser: Parser<T, E, I>,
+    min: usize,
+    max: Option<usize>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<Vec<T>, E, I> for StarParser<T, E, I> {
+    fn create(&self) -> Parser<Vec<T>, E, I> {
+        Box::new(StarParser {parser: self.parser.clone(), min: self.min, max: self.max})
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        match (self.min, self.max) {
+            (0, None) => describe_node("Star", depth, |depth| vec![self.parser.describe_with(depth)]),
+            (min, None) => describe_node("Repeat", depth, |depth| {
+                vec![format!("{}..", min), self.parser.describe_with(depth)]
+            }),
+            (min, Some(max)) => describe_node("Repeat", depth, |depth| {
+                vec![format!("{}..={}", min, max), self.parser.describe_with(depth)]
+            })
+        }
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<Vec<T>, E> {
+        let mut results = Vec::with_capacity(self.min);
+        let looped = repeat(&self.parser, position, source, self.max, |data| results.push(data));
+        let (cursor, (matches, error)) = match looped {
+            Fail(error) => return Fail(error),
+            Error(error) => return Error(error),
+            Success(cursor, result) => (cursor, result)
+        };
+        if matches < self.min {
+            return Fail(too_few(cursor, error))
+        }
+        // with min = 0 (star()), this always succeeds, even if nothing is parsed
+        Success(cursor, results)
+    }
+}
+
+// the repetition loop shared by star(), fold_many() and skip_many()
+// each result is given to f, and the end position is returned along with the number of matches
+// (and the failure that ended the loop, if any)
+// a match that doesn't consume anything is counted, but ends the loop (it would repeat forever)
+// a fatal error doesn't end the loop: it is the failure of the whole repetition
+fn repeat<T, E: ParseFailure, I: 'static>(
+    parser: &Parser<T, E, I>,
+    position: usize,
+    source: &[I],
+    max: Option<usize>,
+    mut f: impl FnMut(T)
+) -> Result<(usize, Option<E>), E> {
+    let mut cursor = position;
+    let mut matches = 0;
+    while max.is_none_or(|max| matches < max) {
+        match parser.parse(cursor, source) {
+            Error(error) => return Error(error),
+            Fail(error) => {
+                return Success(cursor, (matches, Some(error)))
+            }
+            Success(position, data) => {
+                f(data);
+                matches += 1;
+                if position == cursor {
+                    break
+                }
+                cursor = position;
+            }
+        }
+    }
+    Success(cursor, (matches, None))
+}
+
+// failure of a loop that matched less than its minimum
+fn too_few<E: ParseFailure>(cursor: usize, error: Option<E>) -> E {
+    error.unwrap_or_else(|| E::new(cursor, "not enough repetitions"))
+}
+
+pub fn star<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<Vec<T>, E, I> {
+    repeat_range(0, None, parser)
+}
+
+// same as star(), but the parser has to match at least once
+pub fn many1<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<Vec<T>, E, I> {
+    repeat_range(1, None, parser)
+}
+
+// repeat a parser exactly n times
+pub fn count<T: 'static, E: ParseFailure, I: 'static>(n: usize, parser: Parser<T, E, I>) -> Parser<Vec<T>, E, I> {
+    repeat_range(n, Some(n), parser)
+}
+
+// repeat a parser between min and max times (max = None for no upper bound)
+pub fn repeat_range<T: 'static, E: ParseFailure, I: 'static>(
+    min: usize,
+    max: Option<usize>,
+    parser: Parser<T, E, I>
+) -> Parser<Vec<T>, E, I> {
+    StarParser {parser, min, max}.create()
+}
+
+// what to do with a separator after the last item of a list
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum TrailingPolicy {
+    // never consumed ("a,b," stops before the last ',')
+    Forbidden,
+    // consumed if present
+    Optional,
+    // each item must be followed by a separator (the empty list is still accepted)
+    Required
+}
+
+// same loop as star(), but the results are folded into an accumulator instead of a Vec
+struct FoldParser<T, A, E, I> {
+    parser: Parser<T, E, I>,
+    init: fn() -> A,
+    step: fn(A, T) -> A,
+    min: usize
+}
+
+impl<T: 'static, A: 'static, E: ParseFailure, I: 'static> Parse<A, E, I> for FoldParser<T, A, E, I> {
+    fn create(&self) -> Parser<A, E, I> {
+        Box::new(FoldParser { parser: self.parser.clone(), init: self.init, step: self.step, min: self.min })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Fold", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<A, E> {
+        let mut accumulator = Some((self.init)());
+        let looped = repeat(&self.parser, position, source, None, |data| {
+            accumulator = Some((self.step)(accumulator.take().unwrap(), data));
+        });
+        let (cursor, (matches, error)) = match looped {
+            Fail(error) => return Fail(error),
+            Error(error) => return Error(error),
+            Success(cursor, result) => (cursor, result)
+        };
+        if matches < self.min {
+            return Fail(too_few(cursor, error))
+        }
+        Success(cursor, accumulator.unwrap())
+    }
+}
+
+pub fn fold_many<T: 'static, A: 'static, E: ParseFailure, I: 'static>(
+    parser: Parser<T, E, I>,
+    init: fn() -> A,
+    step: fn(A, T) -> A
+) -> Parser<A, E, I> {
+    FoldParser { parser, init, step, min: 0 }.create()
+}
+
+pub fn fold_many1<T: 'static, A: 'static, E: ParseFailure, I: 'static>(
+    parser: Parser<T, E, I>,
+    init: fn() -> A,
+    step: fn(A, T) -> A
+) -> Parser<A, E, I> {
+    FoldParser { parser, init, step, min: 1 }.create()
+}
+
+// operand (operator operand)*, folded with the functions returned by the operator parser
+// left associative: ((a op b) op c), right associative: (a op (b op c))
+// an operator that isn't followed by an operand is not consumed
+struct ChainParser<T, E, I> {
+    operand: Parser<T, E, I>,
+    operator: Parser<fn(T, T) -> T, E, I>,
+    right: bool
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for ChainParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(ChainParser { operand: self.operand.clone(), operator: self.operator.clone(), right: self.right })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node(if self.right { "ChainRight" } else { "ChainLeft" }, depth, |depth| {
+            vec![self.operand.describe_with(depth), self.operator.describe_with(depth)]
+        })
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        let (mut cursor, first) = match self.operand.parse(position, source) {
+            Fail(error) => return Fail(error),
+            Error(error) => return Error(error),
+            Success(position, data) => (position, data)
+        };
+        let mut rest = Vec::new();
+        loop {
+            let (after_operator, f) = match self.operator.parse(cursor, source) {
+                Error(error) => return Error(error),
+                Fail(_) => break,
+                Success(position, f) => (position, f)
+            };
+            match self.operand.parse(after_operator, source) {
+                Error(error) => return Error(error),
+                Fail(_) => break,
+                Success(position, data) => {
+                    rest.push((f, data));
+                    cursor = position;
+                }
+            }
+        }
+
+        let result = if self.right {
+            // a op1 b op2 c: fold from the right, each operator goes with the operand on its left
+            let mut operands = vec![first];
+            let mut operators = Vec::new();
+            for (f, data) in rest {
+                operators.push(f);
+                operands.push(data);
+            }
+            let mut result = operands.pop().unwrap();
+            while let Some(f) = operators.pop() {
+                result = f(operands.pop().unwrap(), result);
+            }
+            result
+        } else {
+            rest.into_iter().fold(first, |acc, (f, data)| f(acc, data))
+        };
+        Success(cursor, result)
+    }
+}
+
+pub fn chainl1<T: 'static, E: ParseFailure, I: 'static>(
+    operand: Parser<T, E, I>,
+    operator: Parser<fn(T, T) -> T, E, I>
+) -> Parser<T, E, I> {
+    ChainParser { operand, operator, right: false }.create()
+}
+
+pub fn chainr1<T: 'static, E: ParseFailure, I: 'static>(
+    operand: Parser<T, E, I>,
+    operator: Parser<fn(T, T) -> T, E, I>
+) -> Parser<T, E, I> {
+    ChainParser { operand, operator, right: true }.create()
+}
+
+// associativity of an infix operator of PrattParser
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Assoc {
+    // a - b - c is (a - b) - c
+    Left,
+    // a ^ b ^ c is a ^ (b ^ c)
+    Right
+}
+
+// expressions with operators of several precedence levels (precedence climbing, with binding powers)
+// PrattParser::new(atom)
+//     .infix(byte(b'+'), 1, Assoc::Left, |a, b| a + b)
+//     .infix(byte(b'*'), 2, Assoc::Left, |a, b| a * b)
+//     .prefix(byte(b'-'), 3, |a| -a)
+//     .build()
+// the higher the power, the tighter the operator binds
+// (a prefix binds tighter than the infix and postfix operators of the same power, whatever their associativity:
+// with the same power for all of them, -a*b is (-a)*b, -a^b is (-a)^b and -a! is (-a)!)
+// a parenthesized expression is an atom: see recursive()
+// the operators are tried in the order they were added (the first one that matches is used),
+// and an expression stops before anything that is not an operator where an operator could be
+// the pending operators are kept on a heap stack, so long chains and deep prefixes don't overflow the call stack
+// (only the atoms can recurse, through parentheses)
+pub struct PrattParser<T, E = ParseError, I = u8> {
+    atom: Parser<T, E, I>,
+    prefix: Vec<UnaryOperator<T, E, I>>,
+    infix: Vec<BinaryOperator<T, E, I>>,
+    postfix: Vec<UnaryOperator<T, E, I>>
+}
+
+// the operator, its binding power (left and right for an infix) and its function
+type UnaryOperator<T, E, I> = (Parser<(), E, I>, u16, fn(T) -> T);
+type BinaryOperator<T, E, I> = (Parser<(), E, I>, u16, u16, fn(T, T) -> T);
+
+// an operator waiting for its right operand, with the minimum binding power to restore after it
+enum Pending<T> {
+    Prefix(fn(T) -> T, u16),
+    Infix(T, fn(T, T) -> T, u16)
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> PrattParser<T, E, I> {
+    pub fn new(atom: Parser<T, E, I>) -> PrattParser<T, E, I> {
+        PrattParser { atom, prefix: Vec::new(), infix: Vec::new(), postfix: Vec::new() }
+    }
+
+    pub fn prefix<O: 'static>(mut self, operator: Parser<O, E, I>, power: u8, f: fn(T) -> T) -> PrattParser<T, E, I> {
+        // (above the left power of an infix of the same power, 2 * power + 1 for a right one)
+        self.prefix.push((skip(operator), 2 * power as u16 + 2, f));
+        self
+    }
+
+    pub fn infix<O: 'static>(
+        mut self,
+        operator: Parser<O, E, I>,
+        power: u8,
+        assoc: Assoc,
+        f: fn(T, T) -> T
+    ) -> PrattParser<T, E, I> {
+        let power = 2 * power as u16;
+        let (left, right) = match assoc {
+            Assoc::Left => (power, power + 1),
+            Assoc::Right => (power + 1, power)
+        };
+        self.infix.push((skip(operator), left, right, f));
+        self
+    }
+
+    pub fn postfix<O: 'static>(mut self, operator: Parser<O, E, I>, power: u8, f: fn(T) -> T) -> PrattParser<T, E, I> {
+        self.postfix.push((skip(operator), 2 * power as u16, f));
+        self
+    }
+
+    pub fn build(self) -> Parser<T, E, I> {
+        self.create()
+    }
+}
+
+// the data of the first operator that matches at position
+fn match_operator<'a, O, E: ParseFailure + 'a, I: 'static>(
+    operators: impl Iterator<Item = (&'a Parser<(), E, I>, O)>,
+    position: usize,
+    source: &[I]
+) -> Result<O, E> {
+    for (operator, data) in operators {
+        match operator.parse(position, source) {
+            Success(end, ()) => return Success(end, data),
+            Error(error) => return Error(error),
+            Fail(_) => {}
+        }
+    }
+    Fail(E::new(position, "no operator"))
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for PrattParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(PrattParser {
+            atom: self.atom.clone(),
+            prefix: self.prefix.clone(),
+            infix: self.infix.clone(),
+            postfix: self.postfix.clone()
+        })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Pratt", depth, |depth| {
+            let operators = |operators: Vec<&Parser<(), E, I>>| {
+                format!("[{}]", operators.iter().map(|p| p.describe_with(depth)).collect::<Vec<_>>().join(", "))
+            };
+            vec![
+                self.atom.describe_with(depth),
+                operators(self.prefix.iter().map(|(p, _, _)| p).collect()),
+                operators(self.infix.iter().map(|(p, _, _, _)| p).collect()),
+                operators(self.postfix.iter().map(|(p, _, _)| p).collect())
+            ]
+        })
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        let prefix = || self.prefix.iter().map(|(p, power, f)| (p, (*power, *f)));
+        let infix = || self.infix.iter().map(|(p, left, right, f)| (p, (*left, *right, *f)));
+        let postfix = || self.postfix.iter().map(|(p, power, f)| (p, (*power, *f)));
+
+        let mut cursor = position;
+        let mut pending = Vec::new();
+        let mut min_power = 0;
+        loop {
+            // prefix operators, then an atom
+            loop {
+                match match_operator(prefix(), cursor, source) {
+                    Error(error) => return Error(error),
+                    Fail(_) => break,
+                    Success(end, (power, f)) => {
+                        pending.push(Pending::Prefix(f, min_power));
+                        min_power = power;
+                        cursor = end;
+                    }
+                }
+            }
+            let mut value = match self.atom.parse(cursor, source) {
+                Fail(error) => return Fail(error),
+                Error(error) => return Error(error),
+                Success(end, data) => {
+                    cursor = end;
+                    data
+                }
+            };
+
+            // postfix and infix operators, as long as they bind tighter than the pending operator
+            loop {
+                match match_operator(postfix(), cursor, source) {
+                    Error(error) => return Error(error),
+                    Success(end, (power, f)) if power >= min_power => {
+                        value = f(value);
+                        cursor = end;
+                        continue
+                    }
+                    _ => {}
+                }
+                match match_operator(infix(), cursor, source) {
+                    Error(error) => return Error(error),
+                    Success(end, (left, right, f)) if left >= min_power => {
+                        pending.push(Pending::Infix(value, f, min_power));
+                        min_power = right;
+                        cursor = end;
+                        break
+                    }
+                    _ => {}
+                }
+                // nothing binds here: the pending operator gets its right operand
+                match pending.pop() {
+                    None => return Success(cursor, value),
+                    Some(Pending::Prefix(f, previous)) => {
+                        value = f(value);
+                        min_power = previous;
+                    }
+                    Some(Pending::Infix(left, f, previous)) => {
+                        value = f(left, value);
+                        min_power = previous;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// same loop as star(), but the results are dropped: nothing is allocated
+struct SkipManyParser<T, E, I> {
+    parser: Parser<T, E, I>,
+    min: usize
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<(), E, I> for SkipManyParser<T, E, I> {
+    fn create(&self) -> Parser<(), E, I> {
+        Box::new(SkipManyParser { parser: self.parser.clone(), min: self.min })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("SkipMany", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<(), E> {
+        let (cursor, (matches, error)) = match repeat(&self.parser, position, source, None, drop) {
+            Fail(error) => return Fail(error),
+            Error(error) => return Error(error),
+            Success(cursor, result) => (cursor, result)
+        };
+        if matches < self.min {
+            return Fail(too_few(cursor, error))
+        }
+        Success(cursor, ())
+    }
+}
+
+pub fn skip_many<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<(), E, I> {
+    SkipManyParser { parser, min: 0 }.create()
+}
+
+pub fn skip_many1<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<(), E, I> {
+    SkipManyParser { parser, min: 1 }.create()
+}
+
+// same loop as star(), but only the number of matches is kept
+struct CountManyParser<T, E, I> {
+    parser: Parser<T, E, I>,
+    min: usize
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<usize, E, I> for CountManyParser<T, E, I> {
+    fn create(&self) -> Parser<usize, E, I> {
+        Box::new(CountManyParser { parser: self.parser.clone(), min: self.min })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("CountMany", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<usize, E> {
+        let (cursor, (matches, error)) = match repeat(&self.parser, position, source, None, drop) {
+            Fail(error) => return Fail(error),
+            Error(error) => return Error(error),
+            Success(cursor, result) => (cursor, result)
+        };
+        if matches < self.min {
+            return Fail(too_few(cursor, error))
+        }
+        Success(cursor, matches)
+    }
+}
+
+pub fn many0_count<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<usize, E, I> {
+    CountManyParser { parser, min: 0 }.create()
+}
+
+pub fn many1_count<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<usize, E, I> {
+    CountManyParser { parser, min: 1 }.create()
+}
+
+// repeat item until end matches
+// end is tried first at each step, so an item that would also match the terminator doesn't hide it
+struct ManyTillParser<T, N, E, I> {
+    item: Parser<T, E, I>,
+    end: Parser<N, E, I>
+}
+
+impl<T: 'static, N: 'static, E: ParseFailure, I: 'static> Parse<(Vec<T>, N), E, I> for ManyTillParser<T, N, E, I> {
+    fn create(&self) -> Parser<(Vec<T>, N), E, I> {
+        Box::new(ManyTillParser { item: self.item.clone(), end: self.end.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("ManyTill", depth, |depth| vec![self.item.describe_with(depth), self.end.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<(Vec<T>, N), E> {
+        let mut cursor = position;
+        let mut results = Vec::new();
+        loop {
+            let end_error = match self.end.parse(cursor, source) {
+                Success(position, end) => return Success(position, (results, end)),
+                Error(error) => return Error(error),
+                Fail(error) => error
+            };
+            match self.item.parse(cursor, source) {
+                Fail(error) => return Fail(end_error.merge(error)),
+                Error(error) => return Error(error),
+                Success(position, data) => {
+                    results.push(data);
+                    cursor = position;
+                }
+            }
+        }
+    }
+}
+
+pub fn many_till<T: 'static, N: 'static, E: ParseFailure, I: 'static>(
+    item: Parser<T, E, I>,
+    end: Parser<N, E, I>
+) -> Parser<(Vec<T>, N), E, I> {
+    ManyTillParser { item, end }.create()
+}
+
+// items separated by a separator (the separator results are dropped)
+// a separator is only consumed if it is followed by an item, or if the trailing policy allows it
+struct SepByParser<T, S, E, I> {
+    item: Parser<T, E, I>,
+    separator: Parser<S, E, I>,
+    min: usize,
+    trailing: TrailingPolicy,
+    // skipped around the separators (see sep_by_tokens())
+    junk: Option<Parser<(), E, I>>
+}
+
+impl<T: 'static, S: 'static, E: ParseFailure, I: 'static> Parse<Vec<T>, E, I> for SepByParser<T, S, E, I> {
+    fn create(&self) -> Parser<Vec<T>, E, I> {
+        Box::new(SepByParser {
+            item: self.item.clone(),
+            separator: self.separator.clone(),
+            min: self.min,
+            trailing: self.trailing,
+            junk: self.junk.clone()
+        })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("SepBy", depth, |depth| vec![self.item.describe_with(depth), self.separator.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<Vec<T>, E> {
+        let mut results = Vec::new();
+        // the failure that ended the list
+        let mut error = None;
+        let mut cursor = match self.item.parse(position, source) {
+            Error(e) => return Error(e),
+            Fail(e) => {
+                error = Some(e);
+                position
+            }
+            Success(position, data) => {
+                results.push(data);
+                position
+            }
+        };
+        // end of a separator that was not followed by an item
+        let mut trailing = None;
+        if !results.is_empty() {
+            loop {
+                let before_separator = match self.skip_junk(cursor, source) {
+                    Error(e) => return Error(e),
+                    Fail(e) => return Fail(e),
+                    Success(position, ()) => position
+                };
+                let after_separator = match self.separator.parse(before_separator, source) {
+                    Error(e) => return Error(e),
+                    Fail(e) => {
+                        error = Some(e);
+                        break
+                    }
+                    Success(position, _) => position
+                };
+                let before_item = match self.skip_junk(after_separator, source) {
+                    Error(e) => return Error(e),
+                    Fail(e) => return Fail(e),
+                    Success(position, ()) => position
+                };
+                match self.item.parse(before_item, source) {
+                    Error(e) => return Error(e),
+                    Fail(_) => {
+                        trailing = Some(after_separator);
+                        break
+                    }
+                    // a separator and an item that consume nothing would repeat forever:
+                    // the list ends there, as if the item was missing
+                    Success(position, _) if position == cursor => {
+                        trailing = Some(after_separator);
+                        break
+                    }
+                    Success(position, data) => {
+                        results.push(data);
+                        cursor = position;
+                    }
+                }
+            }
+        }
+        // (either the first item or the last separator failed)
+        if results.len() < self.min {
+            return Fail(error.unwrap())
+        }
+        match (self.trailing, trailing) {
+            (TrailingPolicy::Optional, Some(end)) | (TrailingPolicy::Required, Some(end)) => {
+                Success(end, results)
+            }
+            // the separator is missing after the last item
+            (TrailingPolicy::Required, None) if !results.is_empty() => Fail(error.unwrap()),
+            _ => Success(cursor, results)
+        }
+    }
+}
+
+impl<T, S, E: ParseFailure, I: 'static> SepByParser<T, S, E, I> {
+    fn skip_junk(&self, position: usize, source: &[I]) -> Result<(), E> {
+        match &self.junk {
+            Some(junk) => junk.parse(position, source),
+            None => Success(position, ())
+        }
+    }
+}
+
+pub fn sep_by<T: 'static, S: 'static, E: ParseFailure, I: 'static>(
+    item: Parser<T, E, I>,
+    separator: Parser<S, E, I>
+) -> Parser<Vec<T>, E, I> {
+    SepByParser { item, separator, min: 0, trailing: TrailingPolicy::Forbidden, junk: None }.create()
+}
+
+pub fn sep_by1<T: 'static, S: 'static, E: ParseFailure, I: 'static>(
+    item: Parser<T, E, I>,
+    separator: Parser<S, E, I>
+) -> Parser<Vec<T>, E, I> {
+    SepByParser { item, separator, min: 1, trailing: TrailingPolicy::Forbidden, junk: None }.create()
+}
+
+pub fn sep_by_trailing<T: 'static, S: 'static, E: ParseFailure, I: 'static>(
+    item: Parser<T, E, I>,
+    separator: Parser<S, E, I>,
+    trailing: TrailingPolicy
+) -> Parser<Vec<T>, E, I> {
+    SepByParser { item, separator, min: 0, trailing, junk: None }.create()
+}
+
+// sep_by_trailing() at the token level: the junk is skipped around the separators (see tokens())
+// the trailing separator, if any, is preceded by junk too
+pub fn sep_by_tokens<T: 'static, S: 'static, J: 'static, E: ParseFailure, I: 'static>(
+    junk: Parser<J, E, I>,
+    item: Parser<T, E, I>,
+    separator: Parser<S, E, I>,
+    trailing: TrailingPolicy,
+    policy: SkipPolicy
+) -> Parser<Vec<T>, E, I> {
+    let junk = junk_skipper(junk);
+    let list = SepByParser { item, separator, min: 0, trailing, junk: Some(junk.clone()) }.create();
+    skip_edges(junk, list, policy)
+}
+
+pub fn sep_by1_tokens<T: 'static, S: 'static, J: 'static, E: ParseFailure, I: 'static>(
+    junk: Parser<J, E, I>,
+    item: Parser<T, E, I>,
+    separator: Parser<S, E, I>,
+    policy: SkipPolicy
+) -> Parser<Vec<T>, E, I> {
+    let junk = junk_skipper(junk);
+    let list = SepByParser { item, separator, min: 1, trailing: TrailingPolicy::Forbidden, junk: Some(junk.clone()) };
+    skip_edges(junk, list.create(), policy)
+}
+
+// two parsers of different types in sequence
+struct PairParser<A, B, E, I> {
+    first: Parser<A, E, I>,
+    second: Parser<B, E, I>
+}
+
+impl<A: 'static, B: 'static, E: ParseFailure, I: 'static> Parse<(A, B), E, I> for PairParser<A, B, E, I> {
+    fn create(&self) -> Parser<(A, B), E, I> {
+        Box::new(PairParser { first: self.first.clone(), second: self.second.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Pair", depth, |depth| vec![self.first.describe_with(depth), self.second.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<(A, B), E> {
+        match self.first.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(position, a) => match self.second.parse(position, source) {
+                Fail(error) => Fail(error),
+                Error(error) => Error(error),
+                Success(position, b) => Success(position, (a, b))
+            }
+        }
+    }
+}
+
+pub fn pair<A: 'static, B: 'static, E: ParseFailure, I: 'static>(
+    first: Parser<A, E, I>,
+    second: Parser<B, E, I>
+) -> Parser<(A, B), E, I> {
+    PairParser { first, second }.create()
+}
+
+struct TripleParser<A, B, C, E, I> {
+    first: Parser<A, E, I>,
+    second: Parser<B, E, I>,
+    third: Parser<C, E, I>
+}
+
+impl<A: 'static, B: 'static, C: 'static, E: ParseFailure, I: 'static> Parse<(A, B, C), E, I>
+    for TripleParser<A, B, C, E, I> {
+    fn create(&self) -> Parser<(A, B, C), E, I> {
+        Box::new(TripleParser {
+            first: self.first.clone(),
+            second: self.second.clone(),
+            third: self.third.clone()
+        })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Triple", depth, |depth| {
+            vec![self.first.describe_with(depth), self.second.describe_with(depth), self.third.describe_with(depth)]
+        })
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<(A, B, C), E> {
+        let (position, a) = match self.first.parse(position, source) {
+            Fail(error) => return Fail(error),
+            Error(error) => return Error(error),
+            Success(position, a) => (position, a)
+        };
+        let (position, b) = match self.second.parse(position, source) {
+            Fail(error) => return Fail(error),
+            Error(error) => return Error(error),
+            Success(position, b) => (position, b)
+        };
+        match self.third.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(position, c) => Success(position, (a, b, c))
+        }
+    }
+}
+
+pub fn triple<A: 'static, B: 'static, C: 'static, E: ParseFailure, I: 'static>(
+    first: Parser<A, E, I>,
+    second: Parser<B, E, I>,
+    third: Parser<C, E, I>
+) -> Parser<(A, B, C), E, I> {
+    TripleParser { first, second, third }.create()
+}
+
+// sequence of parsers of different types, stored as a tuple of parsers
+// this is what seq! expands to: the tuple is flat, there are no intermediate pair parsers
+#[doc(hidden)]
+pub struct SeqParser<P> {
+    pub parsers: P
+}
+
+macro_rules! impl_seq {
+    ($($T:ident $i:tt),+) => {
+        impl<$($T: 'static,)+ E: ParseFailure, I: 'static> Parse<($($T,)+), E, I>
+            for SeqParser<($(Parser<$T, E, I>,)+)> {
+            fn create(&self) -> Parser<($($T,)+), E, I> {
+                Box::new(SeqParser { parsers: ($(self.parsers.$i.clone(),)+) })
+            }
+
+            fn describe_with(&self, depth: usize) -> String {
+                describe_node("Seq", depth, |depth| vec![$(self.parsers.$i.describe_with(depth)),+])
+            }
+
+            #[allow(non_snake_case)]
+            fn parse(&self, position: usize, source: &[I]) -> Result<($($T,)+), E> {
+                let mut cursor = position;
+                $(
+                    let $T = match self.parsers.$i.parse(cursor, source) {
+                        Fail(error) => return Fail(error),
+                        Error(error) => return Error(error),
+                        Success(position, data) => {
+                            cursor = position;
+                            data
+                        }
+                    };
+                )+
+                Success(cursor, ($($T,)+))
+            }
+        }
+    }
+}
+
+impl_seq!(T1 0, T2 1);
+impl_seq!(T1 0, T2 1, T3 2);
+impl_seq!(T1 0, T2 1, T3 2, T4 3);
+impl_seq!(T1 0, T2 1, T3 2, T4 3, T5 4);
+impl_seq!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5);
+impl_seq!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6);
+impl_seq!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7);
+impl_seq!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8);
+impl_seq!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9);
+impl_seq!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10);
+impl_seq!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11);
+
+// seq!(p1, p2, ...): run 2 to 12 parsers in sequence, into a flat tuple (T1, T2, ...)
+#[macro_export]
+macro_rules! seq {
+    ($($parser:expr),+ $(,)?) => {
+        $crate::Parse::create(&$crate::combinator::SeqParser { parsers: ($($parser,)+) })
+    }
+}
+
+// alt![p1, p2, ...]: oneof() over parsers or literals converted by Into ("let", b';', b'a'..=b'z', see primitive)
+// the literals take the type of the other alternatives
+#[macro_export]
+macro_rules! alt {
+    ($($parser:expr),+ $(,)?) => {
+        $crate::combinator::oneof(::std::vec![$(::std::convert::Into::into($parser)),+])
+    }
+}
+
+// cat![p1, p2, ...]: concat() over parsers or literals converted by Into (see alt!())
+#[macro_export]
+macro_rules! cat {
+    ($($parser:expr),+ $(,)?) => {
+        $crate::combinator::concat(::std::vec![$(::std::convert::Into::into($parser)),+])
+    }
+}
+
+// grammar! { name: Type = expression => mapping; ... }
+// every rule becomes a function returning Parser<Type>, a reference to the GrammarCell of the rule:
+// the rules can refer to each other, and each one is built once, on its first use by any thread
+// in expressions, from the loosest to the tightest:
+// - a | b: alternatives (oneof())
+// - a ~ b ~ c: sequence into a flat tuple (seq!())
+// - a*, a+, a?: star(), many1(), optional()
+// - "text" or b"text" is a tag (Vec<u8>), name is a call to name() (a rule, or any function returning a parser),
+//   (...) is a group, and {...} is any rust expression returning a parser
+// the mapping is optional: a function applied to the value of the whole expression (process())
+// grammar! {
+//     sum: u64 = number ~ ("+" ~ number)* => |(first, rest)| rest.iter().fold(first, |a, (_, n)| a + n);
+//     number: u64 = {number::uint()};
+// }
+// the rules parse bytes, unless the grammar starts with the type of its items (type Item = Token;):
+// then they return Parser<Type, ParseError, Token> (the literals are byte tags, use {...} for the item parsers)
+#[macro_export]
+macro_rules! grammar {
+    () => {};
+
+    // the rules, over a source of items
+    (@rules [$item:ty]) => {};
+    (@rules [$item:ty] $name:ident: $ty:ty = $($rest:tt)*) => {
+        $crate::grammar!(@rule [$item] $name $ty [] $($rest)*);
+    };
+
+    // rule: collect the expression until the mapping or the end of the rule
+    (@rule [$item:ty] $name:ident $ty:ty [$($expression:tt)*] => $mapping:expr; $($rest:tt)*) => {
+        fn $name() -> $crate::Parser<$ty, $crate::error::ParseError, $item> {
+            static RULE: $crate::combinator::GrammarCell<$ty, $crate::error::ParseError, $item> =
+                $crate::combinator::GrammarCell::new(|| {
+                    $crate::combinator::process($mapping, $crate::grammar!(@alt [] [] $($expression)*))
+                });
+            RULE.parser()
+        }
+        $crate::grammar!(@rules [$item] $($rest)*);
+    };
+    (@rule [$item:ty] $name:ident $ty:ty [$($expression:tt)*]; $($rest:tt)*) => {
+        fn $name() -> $crate::Parser<$ty, $crate::error::ParseError, $item> {
+            static RULE: $crate::combinator::GrammarCell<$ty, $crate::error::ParseError, $item> =
+                $crate::combinator::GrammarCell::new(|| $crate::grammar!(@alt [] [] $($expression)*));
+            RULE.parser()
+        }
+        $crate::grammar!(@rules [$item] $($rest)*);
+    };
+    (@rule [$item:ty] $name:ident $ty:ty [$($expression:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::grammar!(@rule [$item] $name $ty [$($expression)* $next] $($rest)*);
+    };
+
+    // alternatives: split at the top-level |
+    (@alt [$($done:tt)*] [$($current:tt)*] | $($rest:tt)*) => {
+        $crate::grammar!(@alt [$($done)* [$($current)*]] [] $($rest)*)
+    };
+    (@alt [$($done:tt)*] [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::grammar!(@alt [$($done)*] [$($current)* $next] $($rest)*)
+    };
+    (@alt [] [$($current:tt)*]) => {
+        $crate::grammar!(@seq [] [] $($current)*)
+    };
+    (@alt [$([$($done:tt)*])+] [$($current:tt)*]) => {
+        $crate::combinator::oneof(::std::vec![
+            $($crate::grammar!(@seq [] [] $($done)*),)+
+            $crate::grammar!(@seq [] [] $($current)*)
+        ])
+    };
+
+    // sequence: split at the top-level ~
+    (@seq [$($done:tt)*] [$($current:tt)*] ~ $($rest:tt)*) => {
+        $crate::grammar!(@seq [$($done)* [$($current)*]] [] $($rest)*)
+    };
+    (@seq [$($done:tt)*] [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::grammar!(@seq [$($done)*] [$($current)* $next] $($rest)*)
+    };
+    (@seq [] [$($current:tt)*]) => {
+        $crate::grammar!(@item $($current)*)
+    };
+    (@seq [$([$($done:tt)*])+] [$($current:tt)*]) => {
+        $crate::seq!($($crate::grammar!(@item $($done)*),)+ $crate::grammar!(@item $($current)*))
+    };
+
+    // one atom, then its repetition operators
+    (@item $atom:tt $($operators:tt)*) => {
+        $crate::grammar!(@repeat $crate::grammar!(@atom $atom); $($operators)*)
+    };
+    (@repeat $parser:expr;) => {
+        $parser
+    };
+    (@repeat $parser:expr; * $($operators:tt)*) => {
+        $crate::grammar!(@repeat $crate::combinator::star($parser); $($operators)*)
+    };
+    (@repeat $parser:expr; + $($operators:tt)*) => {
+        $crate::grammar!(@repeat $crate::combinator::many1($parser); $($operators)*)
+    };
+    (@repeat $parser:expr; ? $($operators:tt)*) => {
+        $crate::grammar!(@repeat $crate::combinator::optional($parser); $($operators)*)
+    };
+
+    (@atom $text:literal) => {
+        $crate::primitive::tag(::std::convert::AsRef::<[u8]>::as_ref($text))
+    };
+    (@atom $rule:ident) => {
+        $rule()
+    };
+    (@atom ($($expression:tt)*)) => {
+        $crate::grammar!(@alt [] [] $($expression)*)
+    };
+    (@atom {$parser:expr}) => {
+        $parser
+    };
+
+    // start of the grammar
+    (type Item = $item:ty; $($rest:tt)*) => {
+        $crate::grammar!(@rules [$item] $($rest)*);
+    };
+    ($name:ident: $ty:ty = $($rest:tt)*) => {
+        $crate::grammar!(@rules [u8] $name: $ty = $($rest)*);
+    };
+}
+
+// first, separator, second: only keep first and second
+pub fn separated_pair<A: 'static, S: 'static, B: 'static, E: ParseFailure, I: 'static>(
+    first: Parser<A, E, I>,
+    separator: Parser<S, E, I>,
+    second: Parser<B, E, I>
+) -> Parser<(A, B), E, I> {
+    process(|(a, _, b)| (a, b), triple(first, separator, second))
+}
+
+// open, content, close: only keep the content
+struct DelimitedParser<A, B, C, E, I> {
+    open: Parser<A, E, I>,
+    content: Parser<B, E, I>,
+    close: Parser<C, E, I>
+}
+
+impl<A: 'static, B: 'static, C: 'static, E: ParseFailure, I: 'static> Parse<B, E, I>
+    for DelimitedParser<A, B, C, E, I> {
+    fn create(&self) -> Parser<B, E, I> {
+        Box::new(DelimitedParser {
+            open: self.open.clone(),
+            content: self.content.clone(),
+            close: self.close.clone()
+        })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Delimited", depth, |depth| {
+            vec![self.open.describe_with(depth), self.content.describe_with(depth), self.close.describe_with(depth)]
+        })
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<B, E> {
+        let cursor = match self.open.parse(position, source) {
+            Fail(error) => return Fail(error),
+            Error(error) => return Error(error),
+            Success(position, _) => position
+        };
+        let (cursor, data) = match self.content.parse(cursor, source) {
+            Fail(error) => return Fail(error),
+            Error(error) => return Error(error),
+            Success(position, data) => (position, data)
+        };
+        match self.close.parse(cursor, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(position, _) => Success(position, data)
+        }
+    }
+}
+
+pub fn delimited<A: 'static, B: 'static, C: 'static, E: ParseFailure, I: 'static>(
+    open: Parser<A, E, I>,
+    content: Parser<B, E, I>,
+    close: Parser<C, E, I>
+) -> Parser<B, E, I> {
+    DelimitedParser { open, content, close }.create()
+}
+
+// prefix, then value: only keep the value
+struct PrecededParser<A, B, E, I> {
+    prefix: Parser<A, E, I>,
+    value: Parser<B, E, I>
+}
+
+impl<A: 'static, B: 'static, E: ParseFailure, I: 'static> Parse<B, E, I> for PrecededParser<A, B, E, I> {
+    fn create(&self) -> Parser<B, E, I> {
+        Box::new(PrecededParser { prefix: self.prefix.clone(), value: self.value.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Preceded", depth, |depth| {
+            vec![self.prefix.describe_with(depth), self.value.describe_with(depth)]
+        })
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<B, E> {
+        match self.prefix.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(position, _) => self.value.parse(position, source)
+        }
+    }
+}
+
+pub fn preceded<A: 'static, B: 'static, E: ParseFailure, I: 'static>(
+    prefix: Parser<A, E, I>,
+    value: Parser<B, E, I>
+) -> Parser<B, E, I> {
+    PrecededParser { prefix, value }.create()
+}
+
+// value, then suffix: only keep the value
+struct TerminatedParser<B, A, E, I> {
+    value: Parser<B, E, I>,
+    suffix: Parser<A, E, I>
+}
+
+impl<B: 'static, A: 'static, E: ParseFailure, I: 'static> Parse<B, E, I> for TerminatedParser<B, A, E, I> {
+    fn create(&self) -> Parser<B, E, I> {
+        Box::new(TerminatedParser { value: self.value.clone(), suffix: self.suffix.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Terminated", depth, |depth| {
+            vec![self.value.describe_with(depth), self.suffix.describe_with(depth)]
+        })
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<B, E> {
+        match self.value.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(position, data) => match self.suffix.parse(position, source) {
+                Fail(error) => Fail(error),
+                Error(error) => Error(error),
+                Success(position, _) => Success(position, data)
+            }
+        }
+    }
+}
+
+pub fn terminated<B: 'static, A: 'static, E: ParseFailure, I: 'static>(
+    value: Parser<B, E, I>,
+    suffix: Parser<A, E, I>
+) -> Parser<B, E, I> {
+    TerminatedParser { value, suffix }.create()
+}
+
+// run a parser and drop its result
+struct SkipParser<T, E, I> {
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<(), E, I> for SkipParser<T, E, I> {
+    fn create(&self) -> Parser<(), E, I> {
+        Box::new(SkipParser { parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Skip", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<(), E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(position, _) => Success(position, ())
+        }
+    }
+}
+
+pub fn skip<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<(), E, I> {
+    SkipParser { parser }.create()
+}
+
+// negative lookahead: succeed only if the parser fails
+// nothing is ever consumed
+struct NotParser<T, E, I> {
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<(), E, I> for NotParser<T, E, I> {
+    fn create(&self) -> Parser<(), E, I> {
+        Box::new(NotParser { parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Not", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<(), E> {
+        match self.parser.parse(position, source) {
+            Error(error) => Error(error),
+            Fail(_) => Success(position, ()),
+            Success(_, _) => Fail(E::new(position, "unexpected match"))
+        }
+    }
+}
+
+pub fn not<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<(), E, I> {
+    NotParser { parser }.create()
+}
+
+// positive lookahead: return the result of the parser, without consuming anything
+struct PeekParser<T, E, I> {
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for PeekParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(PeekParser { parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Peek", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(_, data) => Success(position, data)
+        }
+    }
+}
+
+pub fn peek<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<T, E, I> {
+    PeekParser { parser }.create()
+}
+
+// token followed by junk (whitespace, comments...) that is consumed and dropped
+// if the junk parser fails, there is just nothing to skip
+pub fn lexeme_with<T: 'static, J: 'static, E: ParseFailure, I: 'static>(
+    parser: Parser<T, E, I>,
+    junk: Parser<J, E, I>
+) -> Parser<T, E, I> {
+    terminated(parser, optional(junk))
+}
+
+// token followed by ascii whitespace
+pub fn lexeme<T: 'static, E: ParseFailure>(parser: Parser<T, E>) -> Parser<T, E> {
+    lexeme_with(parser, primitive::generic::multispace0())
+}
+
+// succeed only if the parser reaches the end of the source
+struct AllConsumingParser<T, E, I> {
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for AllConsumingParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(AllConsumingParser { parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("AllConsuming", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(position, data) if position == source.len() => Success(position, data),
+            Success(position, _) => Fail(E::expected(position, Expected::EndOfInput))
+        }
+    }
+}
+
+pub fn all_consuming<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<T, E, I> {
+    AllConsumingParser { parser }.create()
+}
+
+// name a grammar rule in the failures that go through it
+// ("expected digit at offset 3, in number, in field value")
+struct ContextParser<T, E, I> {
+    name: &'static str,
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for ContextParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(ContextParser { name: self.name, parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Context", depth, |depth| vec![format!("{:?}", self.name), self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(mut error) => {
+                error.add_context(self.name);
+                Fail(error)
+            }
+            Error(mut error) => {
+                error.add_context(self.name);
+                Error(error)
+            }
+            success => success
+        }
+    }
+}
+
+pub fn context<T: 'static, E: ParseFailure, I: 'static>(
+    name: &'static str,
+    parser: Parser<T, E, I>
+) -> Parser<T, E, I> {
+    ContextParser { name, parser }.create()
+}
+
+thread_local! {
+    // the named() rules being parsed on this thread, outermost first
+    static RULES: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    static TRACING: Cell<bool> = const { Cell::new(false) };
+}
+
+// the named() rules being parsed on this thread, outermost first (empty between parses)
+pub fn rule_stack() -> Vec<&'static str> {
+    RULES.with(|rules| rules.borrow().clone())
+}
+
+// log every named() rule to stderr on this thread, labeled with the rule stack:
+// [expr > term] at 3
+// [expr > term] success at 3..5
+// [expr > term] failure at 4
+pub fn set_tracing(enabled: bool) {
+    TRACING.with(|tracing| tracing.set(enabled));
+}
+
+fn trace(message: std::fmt::Arguments) {
+    if TRACING.with(Cell::get) {
+        RULES.with(|rules| eprintln!("[{}] {}", rules.borrow().join(" > "), message));
+    }
+}
+
+// pops the rule on every exit path (including panics)
+struct RuleGuard;
+
+impl RuleGuard {
+    fn push(name: &'static str) -> RuleGuard {
+        RULES.with(|rules| rules.borrow_mut().push(name));
+        RuleGuard
+    }
+}
+
+impl Drop for RuleGuard {
+    fn drop(&mut self) {
+        RULES.with(|rules| rules.borrow_mut().pop());
+    }
+}
+
+// a lightweight grammar rule: its name is kept on a stack while it is parsed
+// the innermost rule records the stack in its failure ("... (in expr > term > factor)"),
+// and labels the trace when tracing is enabled (see set_tracing())
+struct NamedParser<T, E, I> {
+    name: &'static str,
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for NamedParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(NamedParser { name: self.name, parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Named", depth, |depth| vec![format!("{:?}", self.name), self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        let _guard = RuleGuard::push(self.name);
+        trace(format_args!("at {}", position));
+        // the stack is recorded by the innermost rule (see ParseFailure::with_rules())
+        let record = |error: E| RULES.with(|rules| error.with_rules(&rules.borrow()));
+        match self.parser.parse(position, source) {
+            Success(end, value) => {
+                trace(format_args!("success at {}..{}", position, end));
+                Success(end, value)
+            }
+            Fail(error) => {
+                trace(format_args!("failure at {}", error.position()));
+                Fail(record(error))
+            }
+            Error(error) => {
+                trace(format_args!("error at {}", error.position()));
+                Error(record(error))
+            }
+        }
+    }
+}
+
+pub fn named<T: 'static, E: ParseFailure, I: 'static>(name: &'static str, parser: Parser<T, E, I>) -> Parser<T, E, I> {
+    NamedParser { name, parser }.create()
+}
+
+// rewrite the failures of a parser (a fatal error stays fatal)
+// the contexts of the rules around it are added after f is applied
+struct MapErrParser<T, E, I> {
+    parser: Parser<T, E, I>,
+    f: fn(E) -> E
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for MapErrParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(MapErrParser { parser: self.parser.clone(), f: self.f })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("MapErr", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail((self.f)(error)),
+            Error(error) => Error((self.f)(error)),
+            success => success
+        }
+    }
+}
+
+pub fn map_err<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>, f: fn(E) -> E) -> Parser<T, E, I> {
+    MapErrParser { parser, f }.create()
+}
+
+// use a parser that fails with a ParseError (the primitives, a Parseable type...) in a grammar with an other error type
+// (the primitives also have versions for any error type: see primitive::generic)
+struct ErrIntoParser<T, I> {
+    parser: Parser<T, ParseError, I>
+}
+
+impl<T: 'static, E: ParseFailure + From<ParseError>, I: 'static> Parse<T, E, I> for ErrIntoParser<T, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(ErrIntoParser { parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("ErrInto", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error.into()),
+            Error(error) => Error(error.into()),
+            Success(position, data) => Success(position, data)
+        }
+    }
+}
+
+pub fn err_into<T: 'static, E: ParseFailure + From<ParseError>, I: 'static>(
+    parser: Parser<T, ParseError, I>
+) -> Parser<T, E, I> {
+    ErrIntoParser { parser }.create()
+}
+
+// replace the message and the expected set of the failures ("invalid IPv4 address")
+// the position of the failure is kept
+struct WithMessageParser<T, E, I> {
+    message: &'static str,
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for WithMessageParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(WithMessageParser { message: self.message, parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("WithMessage", depth, |depth| {
+            vec![format!("{:?}", self.message), self.parser.describe_with(depth)]
+        })
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Fail(error.with_message(self.message)),
+            Error(error) => Error(error.with_message(self.message)),
+            success => success
+        }
+    }
+}
+
+pub fn with_message<T: 'static, E: ParseFailure, I: 'static>(
+    parser: Parser<T, E, I>,
+    message: &'static str
+) -> Parser<T, E, I> {
+    WithMessageParser { message, parser }.create()
+}
+
+// commit to the current alternative: a failure of the parser becomes a fatal error
+// oneof(), star(), optional()... propagate it instead of trying something else
+// (preceded(byte(b'{'), cut(body)): once the brace matched, the body has to match)
+struct CutParser<T, E, I> {
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for CutParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(CutParser { parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Cut", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => Error(error),
+            result => result
+        }
+    }
+}
+
+pub fn cut<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<T, E, I> {
+    CutParser { parser }.create()
+}
+
+// on failure, record the error, skip the input up to the synchronization point and use a fallback value
+// the sync parser is not consumed, so the grammar still parses its separator (';', newline...)
+// fatal errors are recovered from too (a recovery point is usually right above a cut())
+// if sync never matches, the original failure is returned
+// once the log is full, the failure is returned as a fatal error (the parse is aborted)
+struct RecoverParser<T, S, E, I> {
+    parser: Parser<T, E, I>,
+    sync: Parser<S, E, I>,
+    fallback: fn() -> T,
+    log: ErrorLog<E>
+}
+
+impl<T: 'static, S: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for RecoverParser<T, S, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(RecoverParser {
+            parser: self.parser.clone(),
+            sync: self.sync.clone(),
+            fallback: self.fallback,
+            log: self.log.clone()
+        })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Recover", depth, |depth| vec![self.parser.describe_with(depth), self.sync.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        let (error, fatal) = match self.parser.parse(position, source) {
+            Fail(error) => (error, false),
+            Error(error) => (error, true),
+            success => return success
+        };
+        if self.log.is_full() {
+            return Error(error)
+        }
+        // (the end of input is tried too, for a sync parser like eof())
+        for cursor in position..=source.len() {
+            if let Success(_, _) = self.sync.parse(cursor, source) {
+                self.log.errors.lock().unwrap().push(error);
+                return Success(cursor, (self.fallback)())
+            }
+        }
+        if fatal { Error(error) } else { Fail(error) }
+    }
+}
+
+// the errors recovered from while parsing, shared by the parsers built with recover()
+// (an error is kept even if the parser that recovered was part of an alternative that failed later)
+// limit is the maximum number of errors recovered from in one parse, to avoid error avalanches
+pub struct ErrorLog<E = ParseError> {
+    errors: Arc<Mutex<Vec<E>>>,
+    limit: Option<usize>
+}
+
+// (derive(Clone) would require E: Clone)
+impl<E> Clone for ErrorLog<E> {
+    fn clone(&self) -> Self {
+        ErrorLog { errors: self.errors.clone(), limit: self.limit }
+    }
+}
+
+impl<E: ParseFailure> ErrorLog<E> {
+    // no limit by default
+    pub fn new(limit: Option<usize>) -> ErrorLog<E> {
+        ErrorLog { errors: Arc::new(Mutex::new(Vec::new())), limit }
+    }
+
+    pub fn recover<T: 'static, S: 'static, I: 'static>(
+        &self,
+        parser: Parser<T, E, I>,
+        sync: Parser<S, E, I>,
+        fallback: fn() -> T
+    ) -> Parser<T, E, I> {
+        RecoverParser { parser, sync, fallback, log: self.clone() }.create()
+    }
+
+    // the recorded errors, in the order they were found (the log is emptied for the next parse)
+    pub fn take(&self) -> Vec<E> {
+        std::mem::take(&mut self.errors.lock().unwrap())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.limit.is_some_and(|limit| self.errors.lock().unwrap().len() >= limit)
+    }
+
+    // batch mode: parse the whole source and report every error found along the way
+    // the result is None if the parse failed anyway (its failure is then the last error, unless the log is full)
+    pub fn run_all<T, I>(&self, parser: &Parser<T, E, I>, source: &[I]) -> (Option<T>, Vec<E>) {
+        self.take();
+        let result = parser.parse_all(source);
+        let full = self.is_full();
+        let mut errors = self.take();
+        match result {
+            Success(_, data) => (Some(data), errors),
+            Fail(error) | Error(error) => {
+                if !full {
+                    errors.push(error);
+                }
+                (None, errors)
+            }
+        }
+    }
+}
+
+pub fn error_log() -> ErrorLog {
+    ErrorLog::new(None)
+}
+
+// same as error_log(), but the parse is aborted after limit errors
+pub fn error_log_with_limit(limit: usize) -> ErrorLog {
+    ErrorLog::new(Some(limit))
+}
+
+// parser surrounded by junk on both sides (whitespace, comments...)
+// on failure, nothing is consumed (not even the leading junk)
+pub fn padded_with<T: 'static, J: 'static, E: ParseFailure, I: 'static>(
+    parser: Parser<T, E, I>,
+    junk: Parser<J, E, I>
+) -> Parser<T, E, I> {
+    delimited(optional(junk.clone()), parser, optional(junk))
+}
+
+// parser surrounded by ascii whitespace
+pub fn padded<T: 'static, E: ParseFailure>(parser: Parser<T, E>) -> Parser<T, E> {
+    padded_with(parser, primitive::generic::multispace0())
+}
+
+// the result of the parser, or a default value (without consuming anything) if it fails
+pub fn opt_or<T: Clone + Send + Sync + 'static, E: ParseFailure, I: 'static>(
+    default: T,
+    parser: Parser<T, E, I>
+) -> Parser<T, E, I> {
+    oneof(vec![parser, PureParser { value: default }.create()])
+}
+
+// same as opt_or(), with the default value built by a function (only called when needed)
+struct OptOrElseParser<T, E, I> {
+    parser: Parser<T, E, I>,
+    default: fn() -> T
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<T, E, I> for OptOrElseParser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(OptOrElseParser { parser: self.parser.clone(), default: self.default })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("OptOrElse", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Error(error) => Error(error),
+            Fail(_) => Success(position, (self.default)()),
+            Success(position, data) => Success(position, data)
+        }
+    }
+}
+
+pub fn opt_or_else<T: 'static, E: ParseFailure, I: 'static>(
+    default: fn() -> T,
+    parser: Parser<T, E, I>
+) -> Parser<T, E, I> {
+    OptOrElseParser { parser, default }.create()
+}
+
+// run the parser only if present is true, otherwise succeed with None
+// unlike optional(), a failure of the parser is still a failure
+pub fn cond<T: 'static, E: ParseFailure, I: 'static>(
+    present: bool,
+    parser: Parser<T, E, I>
+) -> Parser<Option<T>, E, I> {
+    if present {
+        process(Some, parser)
+    } else {
+        process(|_| None, PureParser { value: () }.create())
+    }
+}
+
+// make a parser optional: None (and no input consumed) if it fails
+struct OptionalParser<T, E, I> {
+    parser: Parser<T, E, I>
+}
+
+impl<T: 'static, E: ParseFailure, I: 'static> Parse<Option<T>, E, I> for OptionalParser<T, E, I> {
+    fn create(&self) -> Parser<Option<T>, E, I> {
+        Box::new(OptionalParser { parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Optional", depth, |depth| vec![self.parser.describe_with(depth)])
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<Option<T>, E> {
+        match self.parser.parse(position, source) {
+            Error(error) => Error(error),
+            Fail(_) => Success(position, None),
+            Success(position, data) => Success(position, Some(data))
+        }
+    }
+}
+
+pub fn optional<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<T, E, I>) -> Parser<Option<T>, E, I> {
+    OptionalParser { parser }.create()
+}
+
+// concatenate the inner vectors in order (star(concat(...)) gives a Vec<Vec<T>>)
+pub fn flatten<T: 'static, E: ParseFailure, I: 'static>(parser: Parser<Vec<Vec<T>>, E, I>) -> Parser<Vec<T>, E, I> {
+    process(|parts| {
+        let mut result = Vec::with_capacity(parts.iter().map(Vec::len).sum());
+        for part in parts {
+            result.extend(part);
+        }
+        result
+    }, parser)
+}
+
+// same as flatten(), for strings
+pub fn join<E: ParseFailure, I: 'static>(parser: Parser<Vec<String>, E, I>) -> Parser<String, E, I> {
+    process(|parts| {
+        let mut result = String::with_capacity(parts.iter().map(String::len).sum());
+        for part in parts {
+            result.push_str(&part);
+        }
+        result
+    }, parser)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{number, primitive, text, ParserExt};
+    use crate::primitive::{fail, pure, readchar};
+
+    #[test]
+    fn bound() {
+        // length-prefixed payload
+        let p = and_then(terminated(number::uint(), require(|c| *c == b':', readchar())), |n| {
+            primitive::take(n as usize)
+        });
+        assert_eq!(p.parse(0, b"3:abcd"), Success(5, b"abc".to_vec()));
+        assert_eq!(p.parse(0, b"0:abcd"), Success(2, vec![]));
+        // failure in the continuation
+        assert!(p.parse(0, b"5:abcd").is_fail());
+        assert!(p.parse(0, b"x:abcd").is_fail());
+
+        // a tag name (length-prefixed too), then a body that depends on the tag
+        let name = and_then(readchar(), |n| primitive::take(n as usize));
+        let p = and_then(name, |name| match name.as_slice() {
+            b"num" => primitive::digit1(),
+            _ => primitive::alpha1()
+        });
+        assert_eq!(p.parse(0, b"\x03num42x"), Success(6, b"42".to_vec()));
+        assert_eq!(p.parse(0, b"\x03strab1"), Success(6, b"ab".to_vec()));
+        assert!(p.parse(0, b"\x03numab").is_fail());
+    }
+
+    #[test]
+    fn recursion() {
+        // balanced parentheses, counting the pairs
+        let p = recursive(|this| {
+            process(|groups: Vec<usize>| groups.iter().sum(), star(delimited(
+                require(|c| *c == b'(', readchar()),
+                process(|n| n + 1, this),
+                require(|c| *c == b')', readchar())
+            )))
+        });
+        assert_eq!(p.parse(0, b"(()())()"), Success(8, 4));
+        assert_eq!(p.parse(0, b"(()"), Success(0, 0));
+        // clones share the same definition
+        let q = p.clone();
+        drop(p);
+        assert_eq!(q.parse(0, b"((()))"), Success(6, 3));
+
+        // expr = term ('+' term)*, term = digit | '(' expr ')'
+        let expr = recursive(|expr| {
+            let digit = process(|c| (c - b'0') as i64, require(|c| c.is_ascii_digit(), readchar()));
+            let group = delimited(require(|c| *c == b'(', readchar()), expr, require(|c| *c == b')', readchar()));
+            let plus = process(|_| (|a, b| a + b) as fn(i64, i64) -> i64, require(|c| *c == b'+', readchar()));
+            chainl1(oneof(vec![digit, group]), plus)
+        });
+        assert_eq!(expr.parse(0, b"1+(2+(3+4))+5"), Success(13, 15));
+
+        // the body captures the parsers given by the caller
+        let nested = |open: u8, close: u8, leaf: Parser<usize>| recursive(move |this| {
+            let group = delimited(primitive::byte(open), this, primitive::byte(close));
+            oneof(vec![leaf, process(|depth| depth + 1, group)])
+        });
+        let p = nested(b'<', b'>', process(|_| 0, primitive::alpha()));
+        assert_eq!(p.parse(0, b"<<a>>"), Success(5, 2));
+        assert!(p.parse(0, b"((a))").is_fail());
+
+        // mutual recursion: a list is '[' items ']', an item is a digit or a list
+        let list: Declaration<usize> = declare();
+        let item: Declaration<usize> = declare();
+        list.define(delimited(
+            require(|c| *c == b'[', readchar()),
+            process(|items: Vec<usize>| items.iter().sum(), star(item.parser())),
+            require(|c| *c == b']', readchar())
+        ));
+        item.define(oneof(vec![process(|_| 1, require(|c| c.is_ascii_digit(), readchar())), list.parser()]));
+        assert_eq!(list.parser().parse(0, b"[1[2[3]]4]"), Success(10, 4));
+        assert!(list.parser().parse(0, b"[1[2]").is_fail());
+    }
+
+    // expr = digit ('+' expr)?, built once for the whole program
+    static BUILT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    static SUM: GrammarCell<u64> = GrammarCell::new(|| {
+        BUILT.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let digit = process(|c| (c - b'0') as u64, primitive::digit());
+        let rest = optional(preceded(primitive::byte(b'+'), SUM.parser()));
+        process(|(first, rest)| first + rest.unwrap_or(0), pair(digit, rest))
+    });
+
+    #[test]
+    fn static_grammar() {
+        fn shared<T: Send + Sync>() {}
+        shared::<GrammarCell<u64>>();
+        shared::<Parser<u64>>();
+
+        let sources: Vec<Vec<u8>> = (1..=8).map(|n| vec!["1"; n * 10].join("+").into_bytes()).collect();
+        std::thread::scope(|scope| {
+            let threads: Vec<_> = sources.iter().map(|source| scope.spawn(|| SUM.parse(0, source))).collect();
+            for (n, thread) in (1..=8).zip(threads) {
+                assert_eq!(thread.join().unwrap(), Success(n * 20 - 1, n as u64 * 10));
+            }
+        });
+        assert_eq!(SUM.parser().parse(0, b"1+2+3x"), Success(5, 6));
+        // in an other grammar
+        let sums = sep_by(SUM.parser(), primitive::byte(b','));
+        assert_eq!(sums.parse(0, b"1+1,2"), Success(5, vec![2, 2]));
+        assert_eq!(sums.describe(), "SepBy(Static, Byte(','))");
+        assert_eq!(BUILT.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "parser used before define()")]
+    fn undefined() {
+        let p: Declaration<u8> = declare();
+        p.parser().parse(0, b"x");
+    }
+
+    #[test]
+    fn starred() {
+        let p = readchar();
+        let p = star(p);
+        crate::assert_parses!(p, "test", b"test".to_vec());
+
+        // star combined with mapped
+        let p = process(|chars| String::from_utf8(chars).unwrap(), p);
+        crate::assert_parses!(p, "test", "test".to_string());
+    }
+
+    #[test]
+    fn at_least_once() {
+        let digit = require(|c| c.is_ascii_digit(), readchar());
+        let p = many1(digit.clone());
+        assert!(p.parse(0, b"x").is_fail());
+        assert!(p.parse(0, b"").is_fail());
+        assert_eq!(p.parse(0, b"1x"), Success(1, vec![b'1']));
+        assert_eq!(p.parse(0, b"123x"), Success(3, b"123".to_vec()));
+
+        // inside concat
+        let p = concat(vec![many1(digit), star(require(|c| *c == b'.', readchar()))]);
+        assert_eq!(p.parse(0, b"12.."), Success(4, vec![b"12".to_vec(), b"..".to_vec()]));
+        assert!(p.parse(0, b"..").is_fail());
+    }
+
+    #[test]
+    fn counted() {
+        let hex = require(|c| c.is_ascii_hexdigit(), readchar());
+        let p = count(4, hex.clone());
+        assert_eq!(p.parse(0, b"00e9x"), Success(4, b"00e9".to_vec()));
+        assert!(p.parse(0, b"00e").is_fail());
+        assert!(p.parse(0, b"00ex").is_fail());
+
+        // count(0) never runs the parser
+        let p = count(0, process(|_| panic!("called"), readchar()));
+        assert_eq!(p.parse(0, b"abc"), Success(0, Vec::<()>::new()));
+
+        // groups of 2
+        let p = star(count(2, hex));
+        assert_eq!(p.parse(0, b"c0ffe"), Success(4, vec![b"c0".to_vec(), b"ff".to_vec()]));
+    }
+
+    #[test]
+    fn ranged() {
+        let digit = require(|c| c.is_ascii_digit(), readchar());
+        let octet = repeat_range(1, Some(3), digit.clone());
+        assert_eq!(octet.parse(0, b"1."), Success(1, b"1".to_vec()));
+        assert_eq!(octet.parse(0, b"12."), Success(2, b"12".to_vec()));
+        // stops at max, even if more input could match
+        assert_eq!(octet.parse(0, b"1234"), Success(3, b"123".to_vec()));
+        // not enough matches
+        assert!(octet.parse(0, b".").is_fail());
+        assert!(repeat_range(2, Some(3), digit.clone()).parse(0, b"1.").is_fail());
+
+        // unbounded
+        let p = repeat_range(2, None, digit);
+        assert_eq!(p.parse(0, b"12345"), Success(5, b"12345".to_vec()));
+        assert!(p.parse(0, b"1").is_fail());
+    }
+
+    #[test]
+    fn separated() {
+        let letter = require(|c| c.is_ascii_lowercase(), readchar());
+        let comma = process(|_| (), require(|c| *c == b',', readchar()));
+        let p = sep_by(letter.clone(), comma.clone());
+        assert_eq!(p.parse(0, b""), Success(0, vec![]));
+        assert_eq!(p.parse(0, b"a"), Success(1, vec![b'a']));
+        assert_eq!(p.parse(0, b"a,b,c;"), Success(5, b"abc".to_vec()));
+        // the trailing separator is left alone
+        assert_eq!(p.parse(0, b"a,b,"), Success(3, b"ab".to_vec()));
+        assert_eq!(p.parse(0, b",,"), Success(0, vec![]));
+
+        let p = sep_by1(letter.clone(), comma.clone());
+        assert_eq!(p.parse(0, b"a,b"), Success(3, b"ab".to_vec()));
+        assert!(p.parse(0, b"").is_fail());
+        assert!(p.parse(0, b",a").is_fail());
+
+        // an item and a separator that can both be empty don't loop forever
+        let p = sep_by(primitive::space0(), primitive::space0());
+        assert_eq!(p.parse(0, b"x"), Success(0, vec![()]));
+        assert_eq!(p.parse(0, b"  x"), Success(2, vec![()]));
+        let p = sep_by_trailing(optional(letter.clone()), optional(comma.clone()), TrailingPolicy::Required);
+        assert_eq!(p.parse(0, b"a,b;"), Success(3, vec![Some(b'a'), Some(b'b')]));
+        let space = primitive::space0;
+        let p = sep_by_tokens(space(), space(), space(), TrailingPolicy::Optional, SkipPolicy::Between);
+        assert_eq!(p.parse(0, b" x"), Success(1, vec![()]));
+    }
+
+    #[test]
+    fn trailing_separator() {
+        let letter = require(|c| c.is_ascii_lowercase(), readchar());
+        let comma = require(|c| *c == b',', readchar());
+        let forbidden = sep_by_trailing(letter.clone(), comma.clone(), TrailingPolicy::Forbidden);
+        let optional = sep_by_trailing(letter.clone(), comma.clone(), TrailingPolicy::Optional);
+        let required = sep_by_trailing(letter, comma, TrailingPolicy::Required);
+
+        assert_eq!(forbidden.parse(0, b"a"), Success(1, b"a".to_vec()));
+        assert_eq!(forbidden.parse(0, b"a,"), Success(1, b"a".to_vec()));
+        assert_eq!(forbidden.parse(0, b"a,b"), Success(3, b"ab".to_vec()));
+        assert_eq!(forbidden.parse(0, b"a,b,"), Success(3, b"ab".to_vec()));
+        assert_eq!(forbidden.parse(0, b""), Success(0, vec![]));
+
+        assert_eq!(optional.parse(0, b"a"), Success(1, b"a".to_vec()));
+        assert_eq!(optional.parse(0, b"a,"), Success(2, b"a".to_vec()));
+        assert_eq!(optional.parse(0, b"a,b"), Success(3, b"ab".to_vec()));
+        assert_eq!(optional.parse(0, b"a,b,"), Success(4, b"ab".to_vec()));
+        assert_eq!(optional.parse(0, b""), Success(0, vec![]));
+
+        assert!(required.parse(0, b"a").is_fail());
+        assert_eq!(required.parse(0, b"a,"), Success(2, b"a".to_vec()));
+        assert!(required.parse(0, b"a,b").is_fail());
+        assert_eq!(required.parse(0, b"a,b,"), Success(4, b"ab".to_vec()));
+        assert_eq!(required.parse(0, b""), Success(0, vec![]));
+
+        // a lone separator is never consumed
+        assert_eq!(optional.parse(0, b","), Success(0, vec![]));
+    }
+
+    #[test]
+    fn tuples() {
+        let name = process(|s| String::from_utf8(s).unwrap(), many1(require(|c| c.is_ascii_lowercase(), readchar())));
+        let number = process(|d: Vec<u8>| d.len(), many1(require(|c| c.is_ascii_digit(), readchar())));
+        let p = pair(name.clone(), number.clone());
+        assert_eq!(p.parse(0, b"abc123"), Success(6, ("abc".to_string(), 3)));
+        // the second parser fails after the first one consumed input
+        assert!(p.parse(0, b"abc").is_fail());
+        assert!(p.parse(0, b"123").is_fail());
+
+        let equals = require(|c| *c == b'=', readchar());
+        let p = triple(name.clone(), equals, number.clone());
+        assert_eq!(p.parse(0, b"x=12"), Success(4, ("x".to_string(), b'=', 2)));
+        assert!(p.parse(0, b"x12").is_fail());
+
+        // nested pairs
+        let p = pair(pair(name.clone(), number), name);
+        assert_eq!(p.parse(0, b"a1b"), Success(3, (("a".to_string(), 1), "b".to_string())));
+    }
+
+    #[test]
+    fn sequences() {
+        let letter = require(|c| c.is_ascii_lowercase(), readchar());
+        let digit = process(|c| (c - b'0') as u32, require(|c| c.is_ascii_digit(), readchar()));
+        let dot = process(|_| (), require(|c| *c == b'.', readchar()));
+
+        let p = seq!(letter.clone(), digit.clone());
+        assert_eq!(p.parse(0, b"a1"), Success(2, (b'a', 1)));
+
+        let p = seq!(letter.clone(), dot.clone(), digit.clone(), dot.clone(), letter.clone());
+        assert_eq!(p.parse(0, b"a.1.b"), Success(5, (b'a', (), 1, (), b'b')));
+        // failure in the middle element
+        assert!(p.parse(0, b"a.x.b").is_fail());
+
+        #[derive(Debug, PartialEq, Eq)]
+        struct Version { major: u32, minor: u32 }
+        let p = process(|(major, _, minor)| Version { major, minor }, seq!(digit.clone(), dot, digit.clone()));
+        assert_eq!(p.parse(0, b"1.2"), Success(3, Version { major: 1, minor: 2 }));
+
+        let p = seq!(
+            letter.clone(), digit.clone(), letter.clone(), digit.clone(),
+            letter.clone(), digit.clone(), letter.clone(), digit.clone(),
+            letter.clone(), digit.clone(), letter.clone(), digit.clone(),
+        );
+        let result = p.parse(0, b"a1b2c3d4e5f6");
+        assert_eq!(result, Success(12, (b'a', 1, b'b', 2, b'c', 3, b'd', 4, b'e', 5, b'f', 6)));
+        assert!(p.parse(0, b"a1b2c3d4e5f").is_fail());
+    }
+
+    #[test]
+    fn key_value() {
+        let key = process(|k| String::from_utf8(k).unwrap(), primitive::alpha1());
+        let p = separated_pair(key, primitive::byte(b'='), number::uint());
+        assert_eq!(p.parse(0, b"a=1"), Success(3, ("a".to_string(), 1)));
+        // missing separator, missing value after the separator
+        assert!(p.parse(0, b"a1").is_fail());
+        assert!(p.parse(0, b"a=").is_fail());
+
+        let p = sep_by(p, primitive::byte(b','));
+        let expected = vec![("a".to_string(), 1), ("b".to_string(), 2)];
+        assert_eq!(p.parse(0, b"a=1,b=2"), Success(7, expected));
+    }
+
+    #[test]
+    fn delimiters() {
+        let open = require(|c| *c == b'[', readchar());
+        let close = require(|c| *c == b']', readchar());
+        let digits = star(require(|c| c.is_ascii_digit(), readchar()));
+        let p = delimited(open.clone(), process(|d: Vec<u8>| d.len(), digits.clone()), close.clone());
+        assert_eq!(p.parse(0, b"[123]"), Success(5, 3));
+        // empty content, since the content parser accepts it
+        assert_eq!(p.parse(0, b"[]"), Success(2, 0));
+        // missing delimiters
+        assert!(p.parse(0, b"[123").is_fail());
+        assert!(p.parse(0, b"123]").is_fail());
+
+        let p = delimited(open.clone(), delimited(open, digits, close.clone()), close);
+        assert_eq!(p.parse(0, b"[[42]]"), Success(6, b"42".to_vec()));
+        assert!(p.parse(0, b"[[42]").is_fail());
+    }
+
+    #[test]
+    fn prefix_suffix() {
+        let dollar = require(|c| *c == b'$', readchar());
+        let semicolon = require(|c| *c == b';', readchar());
+        let name = many1(require(|c| c.is_ascii_lowercase(), readchar()));
+
+        let variable = preceded(dollar, name);
+        assert_eq!(variable.parse(0, b"$abc;"), Success(4, b"abc".to_vec()));
+        // prefix failure, value failure
+        assert!(variable.parse(0, b"abc").is_fail());
+        assert!(variable.parse(0, b"$;").is_fail());
+
+        let statement = terminated(variable.clone(), semicolon.clone());
+        assert_eq!(statement.parse(0, b"$abc;"), Success(5, b"abc".to_vec()));
+        // suffix failure, after the value was parsed
+        assert!(statement.parse(0, b"$abc").is_fail());
+
+        // nested: both halves of the variable are consumed
+        let p = star(statement);
+        assert_eq!(p.parse(0, b"$a;$bc;$d"), Success(7, vec![b"a".to_vec(), b"bc".to_vec()]));
+    }
+
+    #[test]
+    fn skipped() {
+        let spaces = skip(star(require(|c| *c == b' ', readchar())));
+        assert_eq!(spaces.parse(0, b"   x"), Success(3, ()));
+        assert_eq!(spaces.parse(0, b"x"), Success(0, ()));
+        assert!(skip(readchar()).parse(0, b"").is_fail());
+
+        let word = many1(require(|c| c.is_ascii_lowercase(), readchar()));
+        let p = pair(terminated(word.clone(), spaces), word);
+        assert_eq!(p.parse(0, b"ab  cd"), Success(6, (b"ab".to_vec(), b"cd".to_vec())));
+    }
+
+    #[test]
+    fn negative_lookahead() {
+        let word = many1(require(|c| c.is_ascii_lowercase(), readchar()));
+        let reserved = oneof(vec![
+            concat(vec![require(|c| *c == b'i', readchar()), require(|c| *c == b'f', readchar())]),
+            concat(vec![require(|c| *c == b'd', readchar()), require(|c| *c == b'o', readchar())])
+        ]);
+        // a name that doesn't start with a reserved word
+        let name = preceded(not(reserved.clone()), word.clone());
+        assert_eq!(name.parse(0, b"abc"), Success(3, b"abc".to_vec()));
+        assert!(name.parse(0, b"if").is_fail());
+        assert!(name.parse(0, b"dot").is_fail());
+
+        // double negation: lookahead without consuming
+        let p = not(not(reserved));
+        assert_eq!(p.parse(0, b"if"), Success(0, ()));
+        assert!(p.parse(0, b"abc").is_fail());
+
+        // more input remains
+        let more = not(primitive::eof());
+        assert_eq!(more.parse(0, b"a"), Success(0, ()));
+        assert!(more.parse(1, b"a").is_fail());
+    }
+
+    #[test]
+    fn positive_lookahead() {
+        let digits = many1(require(|c| c.is_ascii_digit(), readchar()));
+        let p = peek(digits.clone());
+        assert_eq!(p.parse(0, b"12a"), Success(0, b"12".to_vec()));
+        assert!(p.parse(0, b"a").is_fail());
+        assert!(peek(readchar()).parse(1, b"a").is_fail());
+        assert_eq!(peek(primitive::eof()).parse(1, b"a"), Success(1, ()));
+
+        // peek, then parse the same region again
+        let p = pair(peek(digits.clone()), digits.clone());
+        assert_eq!(p.parse(0, b"12a"), Success(2, (b"12".to_vec(), b"12".to_vec())));
+
+        // choose an alternative from what comes next, without consuming it
+        let number = preceded(peek(require(|c| c.is_ascii_digit(), readchar())), process(|_| "number", digits));
+        let word = process(|_| "word", many1(require(|c| c.is_ascii_lowercase(), readchar())));
+        let p = oneof(vec![number, word]);
+        assert_eq!(p.parse(0, b"42"), Success(2, "number"));
+        assert_eq!(p.parse(0, b"ab"), Success(2, "word"));
+    }
+
+    #[test]
+    fn folded() {
+        let digit = process(|c| (c - b'0') as u32, require(|c| c.is_ascii_digit(), readchar()));
+        let sum = fold_many(digit.clone(), || 0, |acc, d| acc + d);
+        assert_eq!(sum.parse(0, b"1234"), Success(4, 10));
+        // zero iterations, and a failed trailing iteration keeps the accumulator
+        assert_eq!(sum.parse(0, b"x"), Success(0, 0));
+        assert_eq!(sum.parse(0, b"12x3"), Success(2, 3));
+
+        let sum = fold_many1(digit, || 0, |acc, d| acc + d);
+        assert!(sum.parse(0, b"x").is_fail());
+        assert_eq!(sum.parse(0, b"5"), Success(1, 5));
+
+        let p = fold_many(primitive::utf8_char(), String::new, |mut s, c| {
+            s.push(c);
+            s
+        });
+        assert_eq!(p.parse(0, "héllo".as_bytes()), Success(6, "héllo".to_string()));
+    }
+
+    #[test]
+    fn operator_precedence() {
+        // the shape of the expression
+        let atom = oneof(vec![text::identifier(), process(|n: u64| n.to_string(), number::uint())]);
+        let expression = PrattParser::new(atom)
+            .infix(primitive::byte(b'+'), 1, Assoc::Left, |a, b| format!("({}+{})", a, b))
+            .infix(primitive::byte(b'-'), 1, Assoc::Left, |a, b| format!("({}-{})", a, b))
+            .infix(primitive::byte(b'*'), 2, Assoc::Left, |a, b| format!("({}*{})", a, b))
+            .prefix(primitive::byte(b'-'), 3, |a| format!("(-{})", a))
+            .infix(primitive::byte(b'^'), 4, Assoc::Right, |a, b| format!("({}^{})", a, b))
+            .postfix(primitive::byte(b'?'), 5, |a| format!("({}?)", a))
+            .build();
+        let shape = |source: &str| expression.parse_str_all(source);
+        assert_eq!(shape("1+2*3"), Ok("(1+(2*3))".to_string()));
+        assert_eq!(shape("1*2+3"), Ok("((1*2)+3)".to_string()));
+        assert_eq!(shape("1-2-3"), Ok("((1-2)-3)".to_string()));
+        assert_eq!(shape("-x^2"), Ok("(-(x^2))".to_string()));
+        assert_eq!(shape("x^y^2"), Ok("(x^(y^2))".to_string()));
+        assert_eq!(shape("--x*y"), Ok("((-(-x))*y)".to_string()));
+        assert_eq!(shape("a?^b?"), Ok("((a?)^(b?))".to_string()));
+        assert_eq!(shape("x"), Ok("x".to_string()));
+
+        // an unknown operator ends the expression
+        assert_eq!(expression.parse_str("a!b"), Success(1, "a".to_string()));
+        assert_eq!(shape("a!b"), Err(ParseError::expected(1, Expected::EndOfInput)));
+        // a missing operand
+        assert!(shape("1+").is_err());
+        assert!(shape("-").is_err());
+        let description = "Pratt(Or(..), [Skip(..)], [Skip(..), Skip(..), Skip(..), Skip(..)], [Skip(..)])";
+        assert_eq!(expression.describe_with(1), description);
+
+        // a prefix binds tighter than the other operators of the same power, left or right associative
+        let expression = PrattParser::new(text::identifier())
+            .prefix(primitive::byte(b'-'), 2, |a| format!("(-{})", a))
+            .infix(primitive::byte(b'*'), 2, Assoc::Left, |a, b| format!("({}*{})", a, b))
+            .infix(primitive::byte(b'^'), 2, Assoc::Right, |a, b| format!("({}^{})", a, b))
+            .infix(primitive::byte(b'.'), 3, Assoc::Right, |a, b| format!("({}.{})", a, b))
+            .postfix(primitive::byte(b'!'), 2, |a| format!("({}!)", a))
+            .build();
+        let shape = |source: &str| expression.parse_str_all(source);
+        assert_eq!(shape("-a*b"), Ok("((-a)*b)".to_string()));
+        assert_eq!(shape("-a^b"), Ok("((-a)^b)".to_string()));
+        assert_eq!(shape("-a!"), Ok("((-a)!)".to_string()));
+        assert_eq!(shape("-a.b"), Ok("(-(a.b))".to_string()));
+
+        // a calculator, with parentheses
+        let calculator = recursive(|expression| {
+            let atom = oneof(vec![
+                process(|n| n as i64, number::uint()),
+                delimited(primitive::byte(b'('), expression, primitive::byte(b')'))
+            ]);
+            PrattParser::new(atom)
+                .infix(primitive::byte(b'+'), 1, Assoc::Left, |a, b| a + b)
+                .infix(primitive::byte(b'-'), 1, Assoc::Left, |a, b| a - b)
+                // (before *, or * would match the start of **)
+                .infix(primitive::tag(b"**"), 4, Assoc::Right, |a, b| a.pow(b as u32))
+                .infix(primitive::byte(b'*'), 2, Assoc::Left, |a, b| a * b)
+                .prefix(primitive::byte(b'-'), 3, |a| -a)
+                .build()
+        });
+        assert_eq!(calculator.parse_str_all("1+2*3"), Ok(7));
+        assert_eq!(calculator.parse_str_all("(1+2)*3"), Ok(9));
+        assert_eq!(calculator.parse_str_all("2**3**2"), Ok(512));
+        assert_eq!(calculator.parse_str_all("-(2-5)*-2"), Ok(-6));
+
+        // long chains and deep prefixes don't use the call stack
+        let source = format!("1{}", "+1".repeat(100_000));
+        assert_eq!(calculator.parse_str_all(&source), Ok(100_001));
+        let source = format!("{}7", "-".repeat(100_001));
+        assert_eq!(calculator.parse_str_all(&source), Ok(-7));
+        let source = format!("1{}", "**1".repeat(100_000));
+        assert_eq!(calculator.parse_str_all(&source), Ok(1));
+    }
+
+    #[test]
+    fn chained() {
+        // expressions are kept as strings, to check the associativity
+        let number = process(|c| (c as char).to_string(), require(|c| c.is_ascii_digit(), readchar()));
+        let plus = process(
+            |_| (|a, b| format!("({a}+{b})")) as fn(String, String) -> String,
+            require(|c| *c == b'+', readchar())
+        );
+        let power = process(
+            |_| (|a, b| format!("({a}^{b})")) as fn(String, String) -> String,
+            require(|c| *c == b'^', readchar())
+        );
+
+        let p = chainl1(number.clone(), plus.clone());
+        assert_eq!(p.parse(0, b"1+2+3"), Success(5, "((1+2)+3)".to_string()));
+        assert_eq!(p.parse(0, b"1"), Success(1, "1".to_string()));
+        // dangling operator
+        assert_eq!(p.parse(0, b"1+2+"), Success(3, "(1+2)".to_string()));
+        assert!(p.parse(0, b"+1").is_fail());
+
+        let p = chainr1(number.clone(), power);
+        assert_eq!(p.parse(0, b"2^3^2"), Success(5, "(2^(3^2))".to_string()));
+        assert_eq!(p.parse(0, b"2^"), Success(1, "2".to_string()));
+
+        // parenthesized sub-expression
+        let group = delimited(
+            require(|c| *c == b'(', readchar()),
+            chainl1(number.clone(), plus.clone()),
+            require(|c| *c == b')', readchar())
+        );
+        let p = chainl1(oneof(vec![group, number]), plus);
+        assert_eq!(p.parse(0, b"1+(2+3)+4"), Success(9, "((1+(2+3))+4)".to_string()));
+
+        // with actual values
+        let digit = process(|c| (c - b'0') as i64, require(|c| c.is_ascii_digit(), readchar()));
+        let minus = process(|_| (|a, b| a - b) as fn(i64, i64) -> i64, require(|c| *c == b'-', readchar()));
+        assert_eq!(chainl1(digit.clone(), minus.clone()).parse(0, b"9-3-2"), Success(5, 4));
+        assert_eq!(chainr1(digit, minus).parse(0, b"9-3-2"), Success(5, 8));
+    }
+
+    #[test]
+    fn lexemes() {
+        let number = lexeme(number::uint());
+        assert_eq!(number.parse(0, b"12  \n x"), Success(6, 12));
+        // at the end of the input
+        assert_eq!(number.parse(0, b"12"), Success(2, 12));
+        // no junk is consumed when the token fails
+        assert!(number.parse(0, b"  12").is_fail());
+
+        // spaces and comments
+        let junk = star(oneof(vec![
+            primitive::multispace1(),
+            skip(text::line_comment(b"//"))
+        ]));
+        let number = lexeme_with(number::uint(), junk.clone());
+        assert_eq!(number.parse(0, b"1 // one\n  2"), Success(11, 1));
+
+        // two lexemes with nothing in between
+        let plus = lexeme_with(primitive::byte(b'+'), junk);
+        let p = seq!(number.clone(), plus, number);
+        assert_eq!(p.parse(0, b"1+2"), Success(3, (1, b'+', 2)));
+        assert_eq!(p.parse(0, b"1 + // add\n 2 "), Success(14, (1, b'+', 2)));
+    }
+
+    #[test]
+    fn token_sequences() {
+        let junk = star(oneof(vec![primitive::multispace1(), skip(text::block_comment(b"/*", b"*/"))]));
+        let sum = sep_by1_tokens(junk.clone(), number::uint(), primitive::byte(b'+'), SkipPolicy::Between);
+        let assignment = tokens(junk.clone(), vec![
+            primitive::tag(b"let"),
+            primitive::alpha1(),
+            primitive::tag(b"="),
+            recognize(sum.clone()),
+            primitive::tag(b";")
+        ]);
+        assert_eq!(assignment.describe_with(0), "Tokens(..)");
+
+        // with and without interior whitespace and comments
+        let expected = vec![b"let".to_vec(), b"x".to_vec(), b"=".to_vec(), b"1+2".to_vec(), b";".to_vec()];
+        assert_eq!(assignment.parse_all(b"let x=1+2;"), Success(10, expected));
+        let source = b"let x = 1 /* one */ +\n 2 ;";
+        let Success(_, values) = assignment.parse_all(source) else { panic!("no match") };
+        assert_eq!(values[3], b"1 /* one */ +\n 2");
+        assert_eq!(values[4], b";");
+        assert_eq!(assignment.parse(0, b"let x = ;"), Fail(ParseError::expected(8, Expected::Class("digit"))));
+
+        // the junk is only skipped between the elements, unless the policy says otherwise
+        assert_eq!(sum.parse(0, b"1 + 2 "), Success(5, vec![1, 2]));
+        assert_eq!(sum.parse(0, b"1 + x"), Success(1, vec![1]));
+        assert!(sum.parse(0, b" 1").is_fail());
+        let sum = sep_by1_tokens(junk.clone(), number::uint(), primitive::byte(b'+'), SkipPolicy::Around);
+        assert_eq!(sum.parse(0, b" 1 + 2 "), Success(7, vec![1, 2]));
+        let p = tokens_with(junk.clone(), vec![primitive::tag(b"a"), primitive::tag(b"b")], SkipPolicy::Leading);
+        assert_eq!(p.parse(0, b" a b "), Success(4, vec![b"a".to_vec(), b"b".to_vec()]));
+        let p = tokens_with(junk.clone(), vec![primitive::tag(b"a"), primitive::tag(b"b")], SkipPolicy::Trailing);
+        assert!(p.parse(0, b" a b").is_fail());
+        assert_eq!(p.parse(0, b"a b "), Success(4, vec![b"a".to_vec(), b"b".to_vec()]));
+        // a trailing separator
+        let list = sep_by_tokens(
+            junk.clone(),
+            number::uint(),
+            primitive::byte(b','),
+            TrailingPolicy::Optional,
+            SkipPolicy::Between
+        );
+        assert_eq!(list.parse(0, b"1 , 2 , ]"), Success(7, vec![1, 2]));
+        assert_eq!(list.parse(0, b"]"), Success(0, vec![]));
+
+        // the children don't skip anything themselves
+        let decimal = recognize(seq!(primitive::digit1(), primitive::byte(b'.'), primitive::digit1()));
+        let p = tokens(junk, vec![primitive::tag(b"("), decimal, primitive::tag(b")")]);
+        assert_eq!(p.parse(0, b"( 1.5 )"), Success(7, vec![b"(".to_vec(), b"1.5".to_vec(), b")".to_vec()]));
+        assert_eq!(p.parse(0, b"(1 . 5)"), Fail(ParseError::expected(2, Expected::Byte(b'.'))));
+    }
+
+    #[test]
+    fn repeated_until() {
+        // readchar() would also match the terminator
+        let p = many_till(readchar(), primitive::tag(b"*/"));
+        assert_eq!(p.parse(0, b"ab*/c"), Success(4, (b"ab".to_vec(), b"*/".to_vec())));
+        assert_eq!(p.parse(0, b"*/"), Success(2, (vec![], b"*/".to_vec())));
+        // unterminated
+        assert!(p.parse(0, b"ab*").is_fail());
+
+        // the items must match until the end
+        let p = many_till(primitive::digit(), primitive::byte(b';'));
+        assert_eq!(p.parse(0, b"12;"), Success(3, (b"12".to_vec(), b';')));
+        assert!(p.parse(0, b"1a;").is_fail());
+    }
+
+    #[test]
+    fn conditional() {
+        assert_eq!(cond(true, readchar()).parse(0, b"a"), Success(1, Some(b'a')));
+        assert!(cond(true, readchar()).parse(0, b"").is_fail());
+        assert_eq!(cond(false, readchar()).parse(0, b"a"), Success(0, None));
+
+        // a flag byte, then a 2-byte payload only if the flag is set
+        let p = and_then(readchar(), |flag| cond(flag == 1, primitive::take(2)));
+        assert_eq!(p.parse(0, b"\x01ab"), Success(3, Some(b"ab".to_vec())));
+        assert_eq!(p.parse(0, b"\x00ab"), Success(1, None));
+        assert!(p.parse(0, b"\x01a").is_fail());
+    }
+
+    #[test]
+    fn defaults() {
+        let port = opt_or(80, number::uint());
+        assert_eq!(port.parse(0, b"8080"), Success(4, 8080));
+        assert_eq!(port.parse(0, b"x"), Success(0, 80));
+
+        let name = opt_or_else(|| b"anonymous".to_vec(), primitive::alpha1());
+        assert_eq!(name.parse(0, b"bob"), Success(3, b"bob".to_vec()));
+        assert_eq!(name.parse(0, b"42"), Success(0, b"anonymous".to_vec()));
+
+        // the inner parser only runs once on the default path
+        static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let counted = process(|c| {
+            CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            c
+        }, readchar());
+        let p = opt_or(0, require(|c| *c == b'a', counted));
+        assert_eq!(p.parse(0, b"b"), Success(0, 0));
+        assert_eq!(CALLS.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn skipped_repetitions() {
+        let spaces = skip_many(primitive::byte(b' '));
+        let source = [vec![b' '; 10_000], b"x".to_vec()].concat();
+        assert_eq!(spaces.parse(0, &source), Success(10_000, ()));
+        assert_eq!(spaces.parse(0, b"x"), Success(0, ()));
+
+        let spaces = skip_many1(primitive::byte(b' '));
+        assert_eq!(spaces.parse(0, b"  x"), Success(2, ()));
+        assert!(spaces.parse(0, b"x").is_fail());
+
+        // zero-width parsers don't loop forever (here, or in star)
+        assert_eq!(skip_many(primitive::eof()).parse(0, b""), Success(0, ()));
+        assert_eq!(skip_many(optional(readchar())).parse(0, b"ab"), Success(2, ()));
+        assert_eq!(star(optional(readchar())).parse(0, b"a"), Success(1, vec![Some(b'a'), None]));
+        assert_eq!(many1(star(readchar())).parse(0, b"ab"), Success(2, vec![b"ab".to_vec(), vec![]]));
+    }
+
+    #[test]
+    fn failures() {
+        assert_eq!(readchar().parse(2, b"ab"), Fail(ParseError::unexpected(2, b"ab")));
+
+        // the failure of the inner parser goes through the combinators
+        let assignment = separated_pair(number::uint(), primitive::byte(b'='), number::uint());
+        assert_eq!(assignment.parse(0, b"12=x"), Fail(ParseError::expected(3, Expected::Class("digit"))));
+        assert_eq!(assignment.parse(0, b"12:3"), Fail(ParseError::expected(2, Expected::Byte(b'='))));
+        let list = delimited(
+            primitive::byte(b'['),
+            sep_by(number::uint(), primitive::byte(b',')),
+            primitive::byte(b']')
+        );
+        assert_eq!(list.parse(0, b"[1,2;"), Fail(ParseError::expected(4, Expected::Byte(b']'))));
+
+        // oneof keeps the alternative that went the furthest
+        let p = oneof(vec![
+            skip(primitive::tag(b"x")),
+            skip(concat(vec![primitive::tag(b"ab"), primitive::tag(b"cd")])),
+            skip(primitive::tag(b"b"))
+        ]);
+        assert_eq!(p.parse(0, b"abce"), Fail(ParseError::expected(2, Expected::Tag(b"cd"))));
+
+        // a rejected value fails where it starts
+        let even = require(|n| n % 2 == 0, number::uint());
+        let error = ParseError::new(1, "value rejected by require()").with_kind(ErrorKind::PredicateFailed);
+        assert_eq!(even.parse(1, b" 13"), Fail(error));
+
+        let error = ParseError::expected(2, Expected::EndOfInput);
+        assert_eq!(number::uint().parse_all(b"12x"), Fail(error.clone()));
+        assert_eq!(error.to_string(), "expected end of input at offset 2");
+        assert_eq!(ParseError::new(0, "integer too large").to_string(), "integer too large at offset 0");
+    }
+
+    #[test]
+    fn expected_sets() {
+        // digits, then the end of the input
+        let digits = many_till(primitive::digit(), primitive::eof());
+        let error = ParseError {
+            expected: vec![Expected::EndOfInput, Expected::Class("digit")],
+            ..ParseError::new(2, "")
+        };
+        assert_eq!(digits.parse(0, b"12a"), Fail(error.clone()));
+        assert_eq!(error.to_string(), "expected one of: end of input, digit at offset 2");
+
+        // every alternative failing at the same position, without duplicates
+        let p = oneof(vec![
+            primitive::byte(b'a'),
+            primitive::one_of(b"ab"),
+            primitive::byte(b'c')
+        ]);
+        let error = ParseError {
+            expected: vec![Expected::Byte(b'a'), Expected::Byte(b'b'), Expected::Byte(b'c')],
+            ..ParseError::new(0, "")
+        };
+        assert_eq!(p.parse(0, b"x"), Fail(error));
+        let error = ParseError {
+            expected: vec![Expected::Tag(b"true"), Expected::Tag(b"false")],
+            ..ParseError::new(0, "")
+        };
+        assert_eq!(text::boolean().parse(0, b"maybe"), Fail(error));
+
+        // the failures that didn't go as far are dropped
+        let p = oneof(vec![
+            skip(pair(primitive::byte(b'-'), primitive::digit())),
+            skip(primitive::digit()),
+            skip(primitive::tag(b"-inf"))
+        ]);
+        let error = ParseError::expected(1, Expected::Class("digit"));
+        assert_eq!(p.parse(0, b"-x"), Fail(error));
+
+        // labeled predicates
+        let even = require_labeled("even number", |n| n % 2 == 0, number::uint());
+        let error = ParseError::expected(0, Expected::Class("even number")).with_kind(ErrorKind::PredicateFailed);
+        assert_eq!(even.parse(0, b"13"), Fail(error));
+        let p = oneof(vec![even, value(0, primitive::byte(b'_'))]);
+        let error = ParseError {
+            expected: vec![Expected::Class("even number"), Expected::Byte(b'_')],
+            ..ParseError::new(0, "").with_kind(ErrorKind::PredicateFailed)
+        };
+        assert_eq!(p.parse(0, b"13"), Fail(error));
+    }
+
+    #[test]
+    fn closures() {
+        // a set of values known at runtime
+        let allowed: Vec<u64> = "80,443,8080".split(',').map(|port| port.parse().unwrap()).collect();
+        let port = require(move |n| allowed.contains(n), number::uint());
+        assert_eq!(port.parse(0, b"443"), Success(3, 443));
+        assert!(port.parse(0, b"22").is_fail());
+        // the copies of the parser share the closure
+        let ports = sep_by(port.clone(), primitive::byte(b','));
+        assert_eq!(ports.parse(0, b"80,8080"), Success(7, vec![80, 8080]));
+
+        let prefix = format!("{}-", "user");
+        let name = process(
+            move |name: Vec<u8>| format!("{}{}", prefix, String::from_utf8_lossy(&name)),
+            primitive::alpha1()
+        );
+        assert_eq!(name.parse(0, b"bob"), Success(3, "user-bob".to_string()));
+        // fn pointers still work
+        assert_eq!(process(u64::count_ones, port).parse(0, b"80"), Success(2, 2));
+    }
+
+    #[test]
+    fn error_kinds() {
+        fn kinds<T>(result: Result<T>) -> Vec<ErrorKind> {
+            match result {
+                Fail(error) | Error(error) => error.all_kinds(),
+                Success(_, _) => panic!("no failure")
+            }
+        }
+        assert_eq!(kinds(readchar().parse(1, b"a")), vec![ErrorKind::UnexpectedEof]);
+        assert_eq!(kinds(primitive::none_of(b"a").parse(0, b"a")), vec![ErrorKind::UnexpectedByte { found: b'a' }]);
+        assert_eq!(kinds(primitive::tag(b"let").parse(0, b"var")), vec![ErrorKind::ExpectedTag { tag: b"let" }]);
+        let even = require(|n| n % 2 == 0, number::uint());
+        assert_eq!(kinds(even.parse(0, b"3")), vec![ErrorKind::PredicateFailed]);
+        assert_eq!(
+            kinds(context("value", number::uint()).parse(0, b"x")),
+            vec![ErrorKind::ExpectedClass { name: "digit" }, ErrorKind::Context("value")]
+        );
+
+        // a merged failure has the kinds of every alternative that failed there
+        let p = oneof(vec![
+            process(|_| 0, primitive::tag(b"null")),
+            require(|n| n % 2 == 0, number::uint()),
+            value(1, primitive::byte(b'-'))
+        ]);
+        assert_eq!(kinds(p.parse(0, b"3")), vec![
+            ErrorKind::PredicateFailed,
+            ErrorKind::ExpectedTag { tag: b"null" },
+            ErrorKind::ExpectedByte { byte: b'-' }
+        ]);
+        let p = oneof(vec![primitive::byte(b'-'), readchar()]);
+        assert_eq!(kinds(p.parse(0, b"")), vec![ErrorKind::UnexpectedEof, ErrorKind::ExpectedByte { byte: b'-' }]);
+    }
+
+    #[test]
+    fn contexts() {
+        let string = context("string literal", text::quoted_string());
+        let field = context("field value", oneof(vec![
+            string,
+            context("number", process(|n| n.to_string(), number::uint()))
+        ]));
+        let object = context("object", delimited(
+            primitive::byte(b'{'),
+            separated_pair(text::identifier(), primitive::byte(b':'), field),
+            primitive::byte(b'}')
+        ));
+        assert_eq!(object.parse(0, b"{a:12}"), Success(6, ("a".to_string(), "12".to_string())));
+
+        // the string went the furthest
+        let error = ParseError {
+            contexts: vec!["string literal", "field value", "object"],
+            ..ParseError::expected(6, Expected::Byte(b'"'))
+        };
+        assert_eq!(object.parse(0, b"{a:\"bc"), Fail(error.clone()));
+        assert_eq!(
+            error.to_string(),
+            "expected '\\\"' at offset 6, in string literal, in field value, in object"
+        );
+        // the number went the furthest
+        let error = ParseError {
+            contexts: vec!["object"],
+            ..ParseError::expected(5, Expected::Byte(b'}'))
+        };
+        assert_eq!(object.parse(0, b"{a:12"), Fail(error));
+        // both alternatives failed at the start of the field: the first one is kept
+        let error = ParseError {
+            expected: vec![Expected::Byte(b'"'), Expected::Class("digit")],
+            contexts: vec!["string literal", "field value", "object"],
+            ..ParseError::new(3, "")
+        };
+        assert_eq!(object.parse(0, b"{a:x}"), Fail(error));
+
+        // a fatal error is annotated on its way out too
+        let object = context("obj", cut(delimited(primitive::byte(b'{'), text::identifier(), primitive::byte(b'}'))));
+        let error = ParseError { contexts: vec!["obj"], ..ParseError::expected(0, Expected::Byte(b'{')) };
+        assert_eq!(object.parse(0, b"[a]"), Error(error));
+        let error = ParseError { contexts: vec!["obj"], ..ParseError::expected(2, Expected::Byte(b'}')) };
+        assert_eq!(object.parse(0, b"{a"), Error(error));
+    }
+
+    #[test]
+    fn named_rules() {
+        let factor = named("factor", number::uint());
+        assert_eq!(factor.describe(), r#"Named("factor", Uint)"#);
+        let term = named("term", pair(factor.clone(), preceded(primitive::byte(b'*'), factor)));
+        let expr = named("expr", pair(term.clone(), preceded(primitive::byte(b'+'), term)));
+
+        // three levels deep: the stack of the innermost rule is kept
+        let error = ParseError::expected(6, Expected::Class("digit")).with_rules(&["expr", "term", "factor"]);
+        assert_eq!(expr.parse(0, b"1*2+3*x"), Fail(error.clone()));
+        assert_eq!(error.rules(), ["expr", "term", "factor"]);
+        assert_eq!(error.to_string(), "expected digit at offset 6 (in expr > term > factor)");
+        // the stack is empty again after a failure
+        assert_eq!(rule_stack(), Vec::<&str>::new());
+        // a failure in the outer rule only
+        let error = ParseError::expected(3, Expected::Byte(b'+')).with_rules(&["expr"]);
+        assert_eq!(expr.parse(0, b"1*2-3*4"), Fail(error));
+
+        // successes leave nothing behind across parses
+        for _ in 0..3 {
+            assert_eq!(expr.parse(0, b"1*2+3*4"), Success(7, ((1, 2), (3, 4))));
+            assert_eq!(rule_stack(), Vec::<&str>::new());
+        }
+        // the rules are visible while they are parsed
+        let inner = named("outer", named("inner", process(|_| rule_stack(), pure(()))));
+        assert_eq!(inner.parse(0, b""), Success(0, vec!["outer", "inner"]));
+        assert_eq!(rule_stack(), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn furthest_failure() {
+        // each byte of the sequence has to match (so a failure is as deep as the matched prefix)
+        let bytes = |s: &[u8]| concat(s.iter().map(|&c| primitive::byte(c)).collect());
+        // three alternatives failing at offsets 1, 3 and 2: the one at 3 is reported
+        let p = oneof(vec![bytes(b"ax"), bytes(b"abcx"), bytes(b"abx")]);
+        assert_eq!(p.parse(0, b"abcd"), Fail(ParseError::expected(3, Expected::Byte(b'x'))));
+        // whatever the order of the alternatives
+        let p = oneof(vec![bytes(b"abcx"), bytes(b"ax")]);
+        assert_eq!(p.parse(0, b"abcd"), Fail(ParseError::expected(3, Expected::Byte(b'x'))));
+
+        // a tie at the deepest offset merges the expectations (the shallower one is dropped)
+        let p = oneof(vec![bytes(b"ax"), bytes(b"aby"), bytes(b"abz")]);
+        let error = ParseError {
+            expected: vec![Expected::Byte(b'y'), Expected::Byte(b'z')],
+            ..ParseError::new(2, "")
+        };
+        assert_eq!(p.parse(0, b"abc"), Fail(error));
+
+        // the first success is returned, the next alternatives are never run
+        let p = oneof(vec![primitive::digit(), process(|_| panic!("called"), readchar())]);
+        assert_eq!(p.parse(0, b"1"), Success(1, b'1'));
+    }
+
+    #[test]
+    fn error_rewriting() {
+        let ipv4 = pair(number::uint(), count(3, preceded(primitive::byte(b'.'), number::uint())));
+        let address = with_message(ipv4, "invalid IPv4 address");
+        assert_eq!(address.parse(0, b"1.2.3.4"), Success(7, (1, vec![2, 3, 4])));
+        // the expectations of the deep failure are replaced, its position is kept
+        assert_eq!(address.parse(0, b"1.2.x"), Fail(ParseError::new(4, "invalid IPv4 address")));
+        // contexts around it are added afterwards
+        let p = context("server", address.clone());
+        let error = ParseError { contexts: vec!["server"], ..ParseError::new(2, "invalid IPv4 address") };
+        assert_eq!(p.parse(0, b"1."), Fail(error.clone()));
+        assert_eq!(error.to_string(), "invalid IPv4 address at offset 2, in server");
+
+        // a hint added to the original failure
+        let p = map_err(number::uint(), |error| ParseError { message: "port numbers are decimal".into(), ..error });
+        let error = ParseError {
+            message: "port numbers are decimal".into(),
+            ..ParseError::expected(0, Expected::Class("digit"))
+        };
+        assert_eq!(p.parse(0, b"x"), Fail(error));
+        assert_eq!(p.parse(0, b"80"), Success(2, 80));
+        // fatal errors stay fatal
+        let p = map_err(cut(number::uint()), |error| ParseError { position: 0, ..error });
+        assert_eq!(p.parse(1, b" x"), Error(ParseError::expected(0, Expected::Class("digit"))));
+
+        // the rewritten failures still compete on their position
+        let host = with_message(primitive::alpha1(), "invalid host name");
+        let p = oneof(vec![process(|_| (), host), process(|_| (), address)]);
+        assert_eq!(p.parse(0, b"1.2.x"), Fail(ParseError::new(4, "invalid IPv4 address")));
+        let error = ParseError {
+            kinds: vec![
+                ErrorKind::Custom("invalid host name".into()),
+                ErrorKind::Custom("invalid IPv4 address".into())
+            ],
+            ..ParseError::new(0, "invalid host name")
+        };
+        assert_eq!(p.parse(0, b"-"), Fail(error));
+    }
+
+    // an application error type: only a code per failure
+    #[derive(Debug, PartialEq)]
+    struct Diagnostic {
+        position: usize,
+        code: u32
+    }
+
+    impl ParseFailure for Diagnostic {
+        fn new(position: usize, _message: &'static str) -> Self {
+            Diagnostic { position, code: 1 }
+        }
+
+        fn expected(position: usize, expected: Expected) -> Self {
+            let code = if expected == Expected::EndOfInput { 2 } else { 3 };
+            Diagnostic { position, code }
+        }
+
+        fn position(&self) -> usize {
+            self.position
+        }
+
+        fn merge(self, other: Self) -> Self {
+            if other.position > self.position { other } else { self }
+        }
+
+        fn with_message(self, _message: &'static str) -> Self {
+            Diagnostic { code: 4, ..self }
+        }
+    }
+
+    impl From<ParseError> for Diagnostic {
+        fn from(error: ParseError) -> Self {
+            Diagnostic { position: error.position, code: 10 + error.expected.len() as u32 }
+        }
+    }
+
+    #[test]
+    fn custom_errors() {
+        // the generic primitives fail with the error type of the grammar
+        let digit: Parser<u8, Diagnostic> = primitive::generic::digit();
+        let list = sep_by1(require(|c| *c != b'0', digit.clone()), primitive::generic::byte(b','));
+        assert_eq!(list.run_all(b"1,2,3"), Ok(b"123".to_vec()));
+        assert_eq!(list.parse(0, b"x"), Fail(Diagnostic { position: 0, code: 3 }));
+        // the codes of the combinators
+        assert_eq!(list.run_all(b"1,2,"), Err(Diagnostic { position: 3, code: 2 }));
+        assert_eq!(list.parse(0, b"0"), Fail(Diagnostic { position: 0, code: 1 }));
+        let p = with_message(list.clone(), "bad list");
+        assert_eq!(p.parse(0, b"x"), Fail(Diagnostic { position: 0, code: 4 }));
+
+        // the deepest failure is kept, and fatal errors stay fatal
+        let p = oneof(vec![preceded(digit.clone(), cut(digit.clone())), digit.clone()]);
+        assert_eq!(p.parse(0, b"1x"), Error(Diagnostic { position: 1, code: 3 }));
+        let p = oneof(vec![digit.clone(), preceded(primitive::generic::readchar(), digit.clone())]);
+        assert_eq!(p.parse(0, b"1x"), Success(1, b'1'));
+        assert_eq!(p.parse(0, b"xy"), Fail(Diagnostic { position: 1, code: 3 }));
+
+        // the parsers that fail with a ParseError go through err_into()
+        let p = pair(err_into(number::uint()), primitive::generic::byte(b'.'));
+        assert_eq!(p.parse(0, b"12."), Success(3, (12, b'.')));
+        assert_eq!(p.parse(0, b"x."), Fail(Diagnostic { position: 0, code: 11 }));
+        assert_eq!(p.parse(0, b"12"), Fail(Diagnostic { position: 2, code: 3 }));
+
+        // the whitespace helpers and the declarations too
+        let value: Declaration<u8, Diagnostic> = declare();
+        value.define(padded(lexeme(digit)));
+        assert_eq!(value.parser().parse(0, b" 1 x"), Success(3, b'1'));
+        assert_eq!(value.parser().parse(0, b" x"), Fail(Diagnostic { position: 1, code: 3 }));
+    }
+
+    #[test]
+    fn committed() {
+        // once the brace matched, the object has to be closed
+        let object = preceded(primitive::byte(b'{'), cut(terminated(number::uint(), primitive::byte(b'}'))));
+        let fallback = process(|_| 0, primitive::tag(b"{1"));
+        let p = oneof(vec![object.clone(), fallback]);
+        assert_eq!(p.parse(0, b"{1}"), Success(3, 1));
+        // the fallback would match, but isn't tried
+        let error = ParseError::expected(2, Expected::Byte(b'}'));
+        assert_eq!(p.parse(0, b"{1"), Error(error.clone()));
+        // before the cut, the alternatives are still tried
+        assert_eq!(oneof(vec![object.clone(), pure(7)]).parse(0, b"x"), Success(0, 7));
+
+        // the loop fails instead of stopping before the broken object
+        let p = star(object.clone());
+        assert_eq!(p.parse(0, b"{1}{2}x"), Success(6, vec![1, 2]));
+        assert_eq!(p.parse(0, b"{1}{2"), Error(ParseError::expected(5, Expected::Byte(b'}'))));
+        assert_eq!(optional(object).parse(0, b"{1"), Error(error));
+
+        // no effect on success
+        let p = cut(number::uint());
+        assert_eq!(p.parse(0, b"42x"), Success(2, 42));
+        assert_eq!(oneof(vec![cut(number::uint()), pure(0)]).parse(0, b"12"), Success(2, 12));
+    }
+
+    #[test]
+    fn fatal_errors() {
+        let digit = primitive::digit();
+        let fatal = cut(primitive::byte(b'!'));
+        assert!(fatal.parse(0, b"x").is_fatal());
+        assert!(!fatal.parse(0, b"x").is_fail());
+        assert!(digit.parse(0, b"x").is_fail());
+        assert!(!digit.parse(0, b"x").is_fatal());
+
+        // sequences keep the position of the child that failed
+        let p = concat(vec![digit.clone(), digit.clone(), fatal.clone()]);
+        assert_eq!(p.parse(0, b"12x"), Error(ParseError::expected(2, Expected::Byte(b'!'))));
+        let p = pair(digit.clone(), fatal.clone());
+        assert_eq!(p.parse(0, b"1x"), Error(ParseError::expected(1, Expected::Byte(b'!'))));
+        // an alternative that fails normally is skipped, a fatal one stops the choice
+        let p = oneof(vec![preceded(digit.clone(), fatal.clone()), value(b'-', primitive::byte(b'1'))]);
+        assert_eq!(p.parse(0, b"1"), Error(ParseError::expected(1, Expected::Byte(b'!'))));
+        let p = oneof(vec![preceded(digit.clone(), primitive::byte(b'!')), value(b'-', primitive::byte(b'1'))]);
+        assert_eq!(p.parse(0, b"1"), Success(1, b'-'));
+
+        // loops stop on a failure, but not on a fatal error
+        let item = preceded(primitive::byte(b'#'), fatal.clone());
+        assert_eq!(star(item.clone()).parse(0, b"#!#!x"), Success(4, b"!!".to_vec()));
+        assert!(star(item.clone()).parse(0, b"#!#x").is_fatal());
+        assert!(skip_many(item.clone()).parse(0, b"#!#x").is_fatal());
+        assert!(sep_by(item.clone(), primitive::byte(b',')).parse(0, b"#!,#x").is_fatal());
+        assert!(many_till(item.clone(), primitive::eof()).parse(0, b"#x").is_fatal());
+        assert!(not(item.clone()).parse(0, b"#x").is_fatal());
+        assert_eq!(not(item).parse(0, b"x"), Success(0, ()));
+
+        // the application sees both kinds of failure as an Err
+        assert_eq!(fatal.run(b"x"), Err(ParseError::expected(0, Expected::Byte(b'!'))));
+    }
+
+    #[test]
+    fn recovery() {
+        // name=number;
+        let statement = process(Some, preceded(pair(primitive::alpha1(), primitive::byte(b'=')), number::uint()));
+        let log = error_log();
+        let recovered = log.recover(statement.clone(), primitive::byte(b';'), || None);
+        let program = star(terminated(recovered, primitive::byte(b';')));
+        assert_eq!(program.parse(0, b"a=1;b=x;c=3;"), Success(12, vec![Some(1), None, Some(3)]));
+        assert_eq!(log.take(), vec![ParseError::expected(6, Expected::Class("digit"))]);
+        // nothing to recover from
+        assert_eq!(program.parse(0, b"a=1;c=3;"), Success(8, vec![Some(1), Some(3)]));
+        assert_eq!(log.take(), vec![]);
+
+        // sync is never found: the original failure is returned, and nothing is recorded
+        let p = log.recover(statement.clone(), primitive::byte(b';'), || None);
+        assert_eq!(p.parse(4, b"a=1;b="), Fail(ParseError::expected(6, Expected::Class("digit"))));
+        assert_eq!(log.take(), vec![]);
+        // the end of input as a synchronization point
+        let p = log.recover(statement, oneof(vec![skip(primitive::byte(b';')), primitive::eof()]), || None);
+        assert_eq!(p.parse(4, b"a=1;b="), Success(6, None));
+        assert_eq!(log.take(), vec![ParseError::expected(6, Expected::Class("digit"))]);
+
+        // lists of numbers: a bad item is skipped up to the next ',' or ']', a bad list up to the next ';'
+        let item = log.recover(process(Some, number::uint()), primitive::one_of(b",]"), || None);
+        let list = delimited(primitive::byte(b'['), sep_by(item, primitive::byte(b',')), primitive::byte(b']'));
+        let lists = star(terminated(log.recover(list, primitive::byte(b';'), Vec::new), primitive::byte(b';')));
+        assert_eq!(
+            lists.parse(0, b"[1,x,3];[4,5;[6];"),
+            Success(17, vec![vec![Some(1), None, Some(3)], vec![], vec![Some(6)]])
+        );
+        assert_eq!(log.take(), vec![
+            ParseError::expected(3, Expected::Class("digit")),
+            ParseError::expected(12, Expected::Byte(b']'))
+        ]);
+    }
+
+    #[test]
+    fn batch_errors() {
+        // name=number; with a bad statement skipped up to its ';'
+        let statement = process(Some, preceded(pair(primitive::alpha1(), primitive::byte(b'=')), number::uint()));
+        let source = b"a=1;b=x;c=3;=4;d=;e=5;";
+        let statements = |log: &ErrorLog| {
+            star(terminated(log.recover(statement.clone(), primitive::byte(b';'), || None), primitive::byte(b';')))
+        };
+
+        let log = error_log();
+        let (result, errors) = log.run_all(&statements(&log), source);
+        assert_eq!(result, Some(vec![Some(1), None, Some(3), None, None, Some(5)]));
+        assert_eq!(errors, vec![
+            ParseError::expected(6, Expected::Class("digit")),
+            ParseError::expected(12, Expected::Class("letter")),
+            ParseError::expected(17, Expected::Class("digit"))
+        ]);
+        // the log is emptied by each parse
+        assert_eq!(log.run_all(&statements(&log), b"a=1;"), (Some(vec![Some(1)]), vec![]));
+        // the failure that couldn't be recovered from comes last
+        let (result, errors) = log.run_all(&statements(&log), b"a=x;b=2;c");
+        assert_eq!(result, None);
+        assert_eq!(errors, vec![
+            ParseError::expected(2, Expected::Class("digit")),
+            ParseError::expected(8, Expected::EndOfInput)
+        ]);
+
+        // the parse is aborted after 2 errors
+        let log = error_log_with_limit(2);
+        let (result, errors) = log.run_all(&statements(&log), source);
+        assert_eq!(result, None);
+        assert_eq!(errors, vec![
+            ParseError::expected(6, Expected::Class("digit")),
+            ParseError::expected(12, Expected::Class("letter"))
+        ]);
+        assert_eq!(log.run_all(&statements(&log), b"a=1;b=x;"), (Some(vec![Some(1), None]), vec![
+            ParseError::expected(6, Expected::Class("digit"))
+        ]));
+    }
+
+    #[test]
+    fn whole_input() {
+        let number = all_consuming(number::uint());
+        assert_eq!(number.parse(0, b"42"), Success(2, 42));
+        assert_eq!(number.parse(1, b"x42"), Success(3, 42));
+        assert!(number.parse(0, b"42 ").is_fail());
+        assert_eq!(all_consuming(padded(number::uint())).parse(0, b"42 "), Success(3, 42));
+
+        let number = number::uint();
+        assert_eq!(number.parse_all(b"42"), Success(2, 42));
+        assert!(number.parse_all(b"42 ").is_fail());
+        assert_eq!(lexeme(number).parse_all(b"42 "), Success(3, 42));
+
+        // empty input
+        assert_eq!(star(readchar()).parse_all(b""), Success(0, vec![]));
+        assert_eq!(all_consuming(pure(())).parse(0, b""), Success(0, ()));
+    }
+
+    #[test]
+    fn std_result() {
+        let number = number::uint();
+        assert_eq!(number.run(b"42 "), Ok((2, 42)));
+        assert_eq!(number.run(b"x"), Err(ParseError::expected(0, Expected::Class("digit"))));
+        assert_eq!(number.run_all(b"42"), Ok(42));
+        assert_eq!(number.run_all(b"42 "), Err(ParseError::expected(2, Expected::EndOfInput)));
+
+        // failures go through ? like any other error
+        fn sum(source: &[u8]) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+            let numbers = sep_by(number::uint(), primitive::byte(b'+')).run_all(source)?;
+            Ok(numbers.iter().sum())
+        }
+        assert_eq!(sum(b"1+2+3").unwrap(), 6);
+        let error = sum(b"1+2+").unwrap_err();
+        assert_eq!(error.to_string(), "expected end of input at offset 3");
+        assert_eq!(error.downcast_ref::<ParseError>(), Some(&ParseError::expected(3, Expected::EndOfInput)));
+    }
+
+    #[test]
+    fn padding() {
+        let number = padded(number::uint());
+        assert_eq!(number.parse(0, b"42"), Success(2, 42));
+        assert_eq!(number.parse(0, b"  42"), Success(4, 42));
+        assert_eq!(number.parse(0, b"42 \n"), Success(4, 42));
+        assert_eq!(number.parse(0, b" 42 x"), Success(4, 42));
+        // the leading whitespace is not consumed when the inner parser fails
+        assert!(number.parse(0, b"  x").is_fail());
+        let p = oneof(vec![value(0, padded(primitive::tag(b"x"))), value(1, primitive::rest())]);
+        assert_eq!(p.parse(0, b"  y"), Success(3, 1));
+
+        // whole trimmed input
+        let whole = terminated(number, primitive::eof());
+        assert_eq!(whole.parse(0, b" 42 "), Success(4, 42));
+        assert!(whole.parse(0, b" 42 x").is_fail());
+
+        let p = padded_with(number::uint(), primitive::byte(b'_'));
+        assert_eq!(p.parse(0, b"_7_"), Success(3, 7));
+    }
+
+    #[test]
+    fn flattened() {
+        let words = flatten(sep_by(many1(primitive::alpha()), primitive::byte(b' ')));
+        assert_eq!(words.parse(0, b""), Success(0, vec![]));
+        assert_eq!(words.parse(0, b"a bcd ef"), Success(8, b"abcdef".to_vec()));
+
+        let text = join(process(|words| {
+            words.into_iter().map(|word| String::from_utf8(word).unwrap()).collect()
+        }, sep_by(many1(primitive::alpha()), primitive::byte(b','))));
+        assert_eq!(text.parse(0, b""), Success(0, String::new()));
+        assert_eq!(text.parse(0, b"ab,c,def"), Success(8, "abcdef".to_string()));
+    }
+
+    #[test]
+    fn counted_repetitions() {
+        let heading = many1_count(primitive::byte(b'#'));
+        assert_eq!(heading.parse(0, b"### title"), Success(3, 3));
+        assert!(heading.parse(0, b"title").is_fail());
+
+        let indent = many0_count(primitive::byte(b' '));
+        assert_eq!(indent.parse(0, b"x"), Success(0, 0));
+        let source = [vec![b' '; 100_000], b"x".to_vec()].concat();
+        assert_eq!(indent.parse(0, &source), Success(100_000, 100_000));
+
+        // a zero-width match is counted once, like in star()
+        assert_eq!(many0_count(primitive::eof()).parse(0, b""), Success(0, 1));
+    }
+
+    #[test]
+    fn verified() {
+        // the value is already mapped, the length check uses the raw span
+        let short = verify(process(|word| word.len() * 2, many1(primitive::alpha())), |_, span| span.len() <= 3);
+        assert_eq!(short.parse(0, b"abc"), Success(3, 6));
+        assert!(short.parse(0, b"abcd").is_fail());
+
+        // no leading zeros
+        let number = verify(number::uint(), |n, span| *n == 0 || span[0] != b'0');
+        assert_eq!(number.parse(0, b"120"), Success(3, 120));
+        assert_eq!(number.parse(0, b"0"), Success(1, 0));
+        assert!(number.parse(0, b"012").is_fail());
+
+        // zero-width match
+        let empty = verify(pure(()), |_, span| span.is_empty());
+        assert_eq!(empty.parse(1, b"ab"), Success(1, ()));
+    }
+
+    #[test]
+    fn optional_parts() {
+        let sign = optional(require(|c| *c == b'-', readchar()));
+        assert_eq!(sign.parse(0, b"-1"), Success(1, Some(b'-')));
+        assert_eq!(sign.parse(0, b"1"), Success(0, None));
+        assert_eq!(sign.parse(0, b""), Success(0, None));
+
+        // position handling in both branches
+        let digit = process(Some, require(|c| c.is_ascii_digit(), readchar()));
+        let p = concat(vec![sign, digit]);
+        assert_eq!(p.parse(0, b"-1"), Success(2, vec![Some(b'-'), Some(b'1')]));
+        assert_eq!(p.parse(0, b"1"), Success(1, vec![None, Some(b'1')]));
+        assert!(p.parse(0, b"-").is_fail());
+
+        // nested
+        let p = optional(optional(require(|c| *c == b'x', readchar())));
+        assert_eq!(p.parse(0, b"x"), Success(1, Some(Some(b'x'))));
+        assert_eq!(p.parse(0, b"y"), Success(0, Some(None)));
+    }
+
+    #[test]
+    fn constant() {
+        #[derive(Debug, PartialEq, Eq, Clone)]
+        enum Direction { North, South, East }
+        let p = oneof(vec![
+            value(Direction::North, primitive::tag(b"north")),
+            value(Direction::South, primitive::tag(b"south")),
+            value(Direction::East, primitive::tag(b"e"))
+        ]);
+        assert_eq!(p.parse(0, b"south"), Success(5, Direction::South));
+        // the position comes from the inner parser
+        assert_eq!(p.parse(0, b"east"), Success(1, Direction::East));
+        assert!(p.parse(0, b"west").is_fail());
+
+        // non-Copy values are cloned
+        let p = value(String::from("x"), readchar());
+        assert_eq!(p.parse(0, b"a"), Success(1, "x".to_string()));
+    }
+
+    #[test]
+    fn recognized() {
+        // the original spelling of a number
+        let number = seq!(number::int(), optional(preceded(primitive::byte(b'.'), primitive::digit1())));
+        let p = recognize(number);
+        assert_eq!(p.parse(0, b"-012.50x"), Success(7, b"-012.50".to_vec()));
+        assert!(p.parse(0, b"x").is_fail());
+
+        let p = recognize(concat(vec![primitive::alpha1(), primitive::digit1()]));
+        assert_eq!(p.parse(2, b"= abc123;"), Success(8, b"abc123".to_vec()));
+
+        // empty match
+        let p = recognize(star(primitive::digit()));
+        assert_eq!(p.parse(0, b"12a"), Success(2, b"12".to_vec()));
+        assert_eq!(p.parse(0, b"a"), Success(0, vec![]));
+    }
+
+    #[test]
+    fn consumed_input() {
+        let p = consumed(number::int());
+        assert_eq!(p.parse(0, b"+007;"), Success(4, (b"+007".to_vec(), 7)));
+        assert!(p.parse(0, b";").is_fail());
+
+        let p = consumed(star(primitive::digit()));
+        assert_eq!(p.parse(1, b"x12"), Success(3, (b"12".to_vec(), b"12".to_vec())));
+        // zero-width parser
+        let p = consumed(peek(primitive::alpha()));
+        assert_eq!(p.parse(0, b"ab"), Success(0, (vec![], b'a')));
+    }
+
+    #[test]
+    fn mapped() {
+        let string = readchar().map(|c| String::from_utf8(vec![c]).unwrap());
+        crate::assert_parses!(string, "test", "t".to_string(), stop = 1);
+    }
+
+    #[test]
+    fn mapped_option() {
+        let byte_value = map_opt(|n| u8::try_from(n).ok(), number::uint());
+        assert_eq!(byte_value.parse(0, b"255"), Success(3, 255));
+        assert!(byte_value.parse(0, b"256").is_fail());
+
+        #[derive(Debug, PartialEq, Eq, Clone)]
+        enum Color { Red, Green }
+        let color = map_opt(|name: Vec<u8>| match name.as_slice() {
+            b"red" => Some(Color::Red),
+            b"green" => Some(Color::Green),
+            _ => None
+        }, primitive::alpha1());
+        assert_eq!(color.parse(0, b"green"), Success(5, Color::Green));
+        assert!(color.parse(0, b"blue").is_fail());
+
+        // the None case falls through to the next alternative
+        let p = oneof(vec![process(Some, color), value(None, primitive::alpha1())]);
+        assert_eq!(p.parse(0, b"red"), Success(3, Some(Color::Red)));
+        assert_eq!(p.parse(0, b"blue"), Success(4, None));
+    }
+
+    #[test]
+    fn mapped_result() {
+        let text = map_res(String::from_utf8, primitive::take(2));
+        assert_eq!(text.parse(0, "é".as_bytes()), Success(2, "é".to_string()));
+        let error = ParseError::new(0, "invalid utf-8 sequence of 1 bytes from index 0");
+        assert_eq!(text.parse(0, b"\xff\xfe"), Fail(error));
+
+        let small = map_res(u8::try_from, number::uint());
+        assert_eq!(small.parse(0, b"200"), Success(3, 200));
+        let error = ParseError::new(0, "out of range integral type conversion attempted");
+        assert_eq!(small.parse(0, b"300"), Fail(error));
+        // (the message shows in the report)
+        let error = small.run_all(b"300").unwrap_err();
+        assert_eq!(error.to_string(), "out of range integral type conversion attempted at offset 0");
+
+        let p = concat(vec![small.clone(), process(|_| 0, primitive::byte(b'.')), small]);
+        assert_eq!(p.parse(0, b"1.2"), Success(3, vec![1, 0, 2]));
+        assert!(p.parse(0, b"1.999").is_fail());
+    }
+
+    #[test]
+    fn filtered() {
+        let p = readchar();
+        let f: fn(&u8) -> bool = |c| { *c == b't'};
+        let p = require(f, p);
+
+        crate::assert_parses!(p, "test", b't', stop = 1);
+
+        let p = readchar().filter(|c| *c == b'x');
+        crate::assert_fails!(p, "test", at = 0);
+    }
+
+    #[test]
+    fn or() {
+        let p = readchar().or(readchar());
+        crate::assert_parses!(p, "test", b't', stop = 1);
+    }
+
+    #[test]
+    fn grammars() {
+        grammar! {
+            expr: i64 = term ~ (additive ~ term)* => |(first, rest)| rest.into_iter().fold(first, apply);
+            additive: Vec<u8> = "+" | "-";
+            term: i64 = factor ~ (multiplicative ~ factor)* => |(first, rest)| rest.into_iter().fold(first, apply);
+            multiplicative: Vec<u8> = "*" | "/";
+            factor: i64 = number | parenthesized | negated;
+            parenthesized: i64 = "(" ~ expr ~ ")" => |(_, value, _)| value;
+            negated: i64 = "-" ~ factor => |(_, value)| -value;
+            number: i64 = {number::uint()} => |n| n as i64;
+        }
+        fn apply(left: i64, (operator, right): (Vec<u8>, i64)) -> i64 {
+            match operator.as_slice() {
+                b"+" => left + right,
+                b"-" => left - right,
+                b"*" => left * right,
+                _ => left / right
+            }
+        }
+
+        assert_eq!(expr().parse_all(b"1+2*3"), Success(5, 7));
+        assert_eq!(expr().parse_all(b"(1+2)*3"), Success(7, 9));
+        assert_eq!(expr().parse_all(b"10-2-3"), Success(6, 5));
+        assert_eq!(expr().parse_all(b"-(4/2)*((3))"), Success(12, -6));
+        assert!(expr().parse_all(b"(1+2").is_fail());
+        assert!(expr().parse_all(b"1+").is_fail());
+
+        // repetitions and options
+        grammar! {
+            list: Vec<u64> = "[" ~ item* ~ "]" => |(_, items, _)| items;
+            item: u64 = {number::uint()} ~ ","? => |(n, _)| n;
+            word: Vec<Vec<u8>> = ("a" | b"b")+;
+        }
+        assert_eq!(list().parse(0, b"[1,2,3]"), Success(7, vec![1, 2, 3]));
+        assert_eq!(list().parse(0, b"[]"), Success(2, vec![]));
+        assert_eq!(word().parse(0, b"abbc"), Success(3, vec![b"a".to_vec(), b"b".to_vec(), b"b".to_vec()]));
+        assert!(word().parse(0, b"c").is_fail());
+    }
+
+    #[test]
+    fn grammar_threads() {
+        // the first use of the rules, from two threads at once: both get the same complete grammar
+        grammar! {
+            nested: u64 = parenthesized | leaf;
+            parenthesized: u64 = "(" ~ nested ~ ")" => |(_, depth, _)| depth + 1;
+            leaf: u64 = "x" => |_| 0;
+        }
+        std::thread::scope(|scope| {
+            let threads: Vec<_> = (1..=2).map(|n| scope.spawn(move || {
+                let source = format!("{}x{}", "(".repeat(n), ")".repeat(n));
+                nested().parse(0, source.as_bytes())
+            })).collect();
+            for (n, thread) in (1..=2).zip(threads) {
+                assert_eq!(thread.join().unwrap(), Success(2 * n + 1, n as u64));
+            }
+        });
+    }
+
+    #[test]
+    fn longest() {
+        let lt = process(|_| "<", require(|c| *c == b'<', readchar()));
+        let le = process(|_| "<=", concat(vec![
+            require(|c| *c == b'<', readchar()),
+            require(|c| *c == b'=', readchar())
+        ]));
+        // the order of the alternatives doesn't matter
+        let p = longest_of(vec![lt.clone(), le.clone()]);
+        assert_eq!(p.parse(0, b"<=1"), Success(2, "<="));
+        assert_eq!(p.parse(0, b"<1"), Success(1, "<"));
+        let p = longest_of(vec![le, lt.clone()]);
+        assert_eq!(p.parse(0, b"<=1"), Success(2, "<="));
+        assert!(p.parse(0, b">").is_fail());
+        assert!(longest_of::<u8, ParseError, u8>(vec![]).parse(0, b"x").is_fail());
+
+        // tie: the first alternative wins
+        let p = longest_of(vec![process(|_| "first", readchar()), process(|_| "second", readchar()), lt]);
+        assert_eq!(p.parse(0, b"<"), Success(1, "first"));
+    }
+
+    #[test]
+    fn permutations() {
+        let p = permutation(vec![
+            require(|c| *c == b'a', readchar()),
+            require(|c| *c == b'b', readchar()),
+            require(|c| *c == b'c', readchar())
+        ]);
+        for input in [b"abc", b"acb", b"bac", b"bca", b"cab", b"cba"] {
+            assert_eq!(p.parse(0, input), Success(3, b"abc".to_vec()));
+        }
+        // missing and duplicate elements
+        assert!(p.parse(0, b"ab").is_fail());
+        assert!(p.parse(0, b"abac").is_fail());
+        // extra input is left alone
+        assert_eq!(p.parse(0, b"cbaa"), Success(3, b"abc".to_vec()));
+    }
+
+    #[test]
+    fn and() {
+        // just clone all parsers
+        // parsers are read-only once created, but it's not like they're expansive to clone anyway
+        // (and i'm a rust beginner)
+        let p = concat(vec![
+            readchar(),
+            readchar(),
+            readchar(),
+            readchar()
+        ]);
+
+        // parse all the characters
+        crate::assert_parses!(p, "test", b"test".to_vec());
+
+        // not enough characters -> Fail to parse
+        crate::assert_fails!(p, "tes", at = 3);
+    }
+
+    #[test]
+    fn trivial() {
+        assert_eq!(pure(42).parse(0, b""), Success(0, 42));
+        assert_eq!(pure(42).parse(1, b"ab"), Success(1, 42));
+        assert!(fail::<u8>().parse(0, b"ab").is_fail());
+
+        // fail() is the neutral element of oneof
+        let p = oneof(vec![fail(), readchar()]);
+        assert_eq!(p.parse(0, b"a"), Success(1, b'a'));
+        assert!(p.parse(1, b"a").is_fail());
+
+        // pure doesn't move the position
+        let p = concat(vec![readchar(), pure(b'-'), readchar()]);
+        assert_eq!(p.parse(0, b"ab"), Success(2, vec![b'a', b'-', b'b']));
+
+        // continuation that doesn't need any more input
+        let p = and_then(readchar(), |c| if c == b'0' { pure(0) } else { fail() });
+        assert_eq!(p.parse(0, b"0"), Success(1, 0));
+        assert!(p.parse(0, b"1").is_fail());
+    }
+
+    #[test]
+    fn char() {
+        let result = readchar().parse(0, "test".as_bytes());
+        assert_eq!(result, Success(1, "t".as_bytes()[0]));
+    }
+}
@@ -0,0 +1,435 @@
+use std::borrow::Cow;
+use std::fmt;
+use crate::primitive::CharClass;
+
+// what a parser would have accepted where it failed
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum Expected {
+    Byte(u8),
+    // named set of bytes or rule ("digit", "identifier", a require() label...)
+    Class(&'static str),
+    Tag(&'static [u8]),
+    // any byte of a class ([a-z_-])
+    Set(CharClass),
+    EndOfInput
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Byte(c) => write!(f, "'{}'", c.escape_ascii()),
+            Expected::Class(name) => write!(f, "{}", name),
+            Expected::Tag(tag) => write!(f, "\"{}\"", tag.escape_ascii()),
+            Expected::Set(class) => write!(f, "{}", class),
+            Expected::EndOfInput => write!(f, "end of input")
+        }
+    }
+}
+
+// category of a failure, for programs that handle errors (the message is for people)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    UnexpectedEof,
+    UnexpectedByte { found: u8 },
+    // one kind per variant of Expected
+    ExpectedByte { byte: u8 },
+    ExpectedTag { tag: &'static [u8] },
+    ExpectedClass { name: &'static str },
+    ExpectedSet { class: CharClass },
+    ExpectedEnd,
+    // require() or verify() rejected the value
+    PredicateFailed,
+    // the failure went through context(name)
+    Context(&'static str),
+    // anything else, described by the message
+    Custom(Cow<'static, str>)
+}
+
+impl ErrorKind {
+    // what was found at position: a byte, or the end of input
+    pub fn at(position: usize, source: &[u8]) -> ErrorKind {
+        match source.get(position) {
+            Some(&found) => ErrorKind::UnexpectedByte { found },
+            None => ErrorKind::UnexpectedEof
+        }
+    }
+}
+
+impl From<&Expected> for ErrorKind {
+    fn from(expected: &Expected) -> Self {
+        match *expected {
+            Expected::Byte(byte) => ErrorKind::ExpectedByte { byte },
+            Expected::Class(name) => ErrorKind::ExpectedClass { name },
+            Expected::Tag(tag) => ErrorKind::ExpectedTag { tag },
+            Expected::Set(class) => ErrorKind::ExpectedSet { class },
+            Expected::EndOfInput => ErrorKind::ExpectedEnd
+        }
+    }
+}
+
+// why a parser failed, and where
+// (most messages are static strings, so a failure usually doesn't allocate)
+// the message can be empty when the expected set says it all
+// contexts are the names of the rules the failure went through, innermost first
+// kinds are the categories of the failure that the expected set and the contexts don't tell
+// (several when failures were merged, see all_kinds())
+// notes are the rarely needed details (see Notes)
+// (new fields may be added: build errors with new(), expected() and unexpected())
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[non_exhaustive]
+pub struct ParseError {
+    pub position: usize,
+    pub message: Cow<'static, str>,
+    pub expected: Vec<Expected>,
+    pub contexts: Vec<&'static str>,
+    pub kinds: Vec<ErrorKind>,
+    // (boxed: notes are rare, and this keeps the error small)
+    pub notes: Option<Box<Notes>>
+}
+
+// the hint is an extra note for the user ("did you mean `false`?", see text::suggesting())
+// rules are the named() rules being parsed at the failure, outermost first (see combinator::named())
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[non_exhaustive]
+pub struct Notes {
+    pub hint: Option<String>,
+    pub rules: Vec<&'static str>
+}
+
+impl ParseError {
+    // a non-empty message is a Custom kind
+    pub fn new(position: usize, message: impl Into<Cow<'static, str>>) -> ParseError {
+        let message = message.into();
+        let kinds = if message.is_empty() { Vec::new() } else { vec![ErrorKind::Custom(message.clone())] };
+        ParseError { position, message, expected: Vec::new(), contexts: Vec::new(), kinds, notes: None }
+    }
+
+    pub fn expected(position: usize, expected: Expected) -> ParseError {
+        ParseError { expected: vec![expected], ..ParseError::new(position, "") }
+    }
+
+    // the input at position was not accepted (either a byte, or the end of input)
+    pub fn unexpected(position: usize, source: &[u8]) -> ParseError {
+        <ParseError as ParseFailure>::unexpected(position, source)
+    }
+
+    // replace the kinds of the error
+    pub fn with_kind(self, kind: ErrorKind) -> ParseError {
+        ParseError { kinds: vec![kind], ..self }
+    }
+
+    pub fn hint(&self) -> Option<&str> {
+        self.notes.as_ref()?.hint.as_deref()
+    }
+
+    pub fn rules(&self) -> &[&'static str] {
+        self.notes.as_ref().map_or(&[], |notes| &notes.rules)
+    }
+
+    // the notes, created if needed
+    fn notes_mut(&mut self) -> &mut Notes {
+        self.notes.get_or_insert_with(Box::default)
+    }
+
+    // every category of the failure: the kinds, then one per expectation and one per context
+    pub fn all_kinds(&self) -> Vec<ErrorKind> {
+        let mut kinds = self.kinds.clone();
+        kinds.extend(self.expected.iter().map(ErrorKind::from));
+        kinds.extend(self.contexts.iter().map(|&name| ErrorKind::Context(name)));
+        kinds
+    }
+
+    // the error that went the furthest into the input (self in case of a tie)
+    // the parser that failed further is usually the one the input was meant for
+    // on a tie, the expectations and kinds of both errors are combined (without duplicates),
+    // and the notes of self are completed by those of other
+    pub fn merge(mut self, other: ParseError) -> ParseError {
+        if other.position > self.position {
+            return other
+        }
+        if other.position == self.position {
+            if self.message.is_empty() {
+                self.message = other.message;
+            }
+            if let Some(other) = other.notes {
+                let notes = self.notes_mut();
+                if notes.hint.is_none() {
+                    notes.hint = other.hint;
+                }
+                if notes.rules.is_empty() {
+                    notes.rules = other.rules;
+                }
+            }
+            for expected in other.expected {
+                if !self.expected.contains(&expected) {
+                    self.expected.push(expected);
+                }
+            }
+            for kind in other.kinds {
+                if !self.kinds.contains(&kind) {
+                    self.kinds.push(kind);
+                }
+            }
+        }
+        self
+    }
+}
+
+// the longest source line shown by display_with_source() (in characters)
+const EXCERPT_WIDTH: usize = 80;
+
+impl ParseError {
+    // 1-based line and column of the failure
+    // the column counts characters when the line is valid utf-8, bytes otherwise
+    pub fn line_column(&self, source: &[u8]) -> (usize, usize) {
+        let (start, end) = self.line_bounds(source);
+        let line = source[..start].iter().filter(|&&c| c == b'\n').count() + 1;
+        // (a failure at the '\n' of a "\r\n" is at the end of the line)
+        let offset = (self.position.min(source.len()) - start).min(end - start);
+        let column = match std::str::from_utf8(&source[start..end]) {
+            Ok(text) => text.char_indices().take_while(|&(i, _)| i < offset).count(),
+            Err(_) => offset
+        };
+        (line, column + 1)
+    }
+
+    // start and end of the line of the failure (without the line ending)
+    fn line_bounds(&self, source: &[u8]) -> (usize, usize) {
+        let position = self.position.min(source.len());
+        let start = source[..position].iter().rposition(|&c| c == b'\n').map_or(0, |i| i + 1);
+        let mut end = source[position..].iter().position(|&c| c == b'\n').map_or(source.len(), |i| position + i);
+        if end > start && source[end - 1] == b'\r' {
+            end -= 1;
+        }
+        (start, end)
+    }
+
+    // report for end users: the line and column, then the line of the source with a caret under the column
+    // error at line 2, column 5: expected ']'
+    //     [1, 2
+    //         ^
+    // (long lines are cut around the column, tabs are kept so that the caret stays aligned)
+    pub fn display_with_source(&self, source: &[u8]) -> String {
+        let (line, column) = self.line_column(source);
+        let (start, end) = self.line_bounds(source);
+        // one char per column (an invalid byte is shown as a replacement character)
+        let text: Vec<char> = match std::str::from_utf8(&source[start..end]) {
+            Ok(text) => text.chars().collect(),
+            Err(_) => source[start..end].iter()
+                .map(|&c| if c.is_ascii() { c as char } else { char::REPLACEMENT_CHARACTER })
+                .collect()
+        };
+        // index of the failure in text, and window of the line that is shown
+        let index = column - 1;
+        let (from, to) = if text.len() > EXCERPT_WIDTH {
+            let from = index.saturating_sub(EXCERPT_WIDTH / 2).min(text.len() - EXCERPT_WIDTH);
+            (from, from + EXCERPT_WIDTH)
+        } else {
+            (0, text.len())
+        };
+
+        let mut report = format!("error at line {}, column {}: ", line, column);
+        // (writing to a String can't fail)
+        let _ = self.describe(&mut report);
+        let _ = self.describe_contexts(&mut report);
+        report.push('\n');
+        let mut caret = String::new();
+        if from > 0 {
+            report.push_str("...");
+            caret.push_str("   ");
+        }
+        report.extend(&text[from..to]);
+        if to < text.len() {
+            report.push_str("...");
+        }
+        for &c in &text[from..index] {
+            caret.push(if c == '\t' { '\t' } else { ' ' });
+        }
+        report.push('\n');
+        report.push_str(&caret);
+        report.push('^');
+        report
+    }
+
+    // the message and the expected set
+    fn describe(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.expected.is_empty() {
+            if !self.message.is_empty() {
+                write!(f, ", ")?;
+            }
+            write!(f, "expected ")?;
+            if self.expected.len() > 1 {
+                write!(f, "one of: ")?;
+            }
+            for (i, expected) in self.expected.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", expected)?;
+            }
+        }
+        if let Some(hint) = self.hint() {
+            write!(f, " ({})", hint)?;
+        }
+        Ok(())
+    }
+
+    fn describe_contexts(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        for context in &self.contexts {
+            write!(f, ", in {}", context)?;
+        }
+        if !self.rules().is_empty() {
+            write!(f, " (in {})", self.rules().join(" > "))?;
+        }
+        Ok(())
+    }
+}
+
+// what the combinators need from an error type: Parser<T, E> can fail with any type implementing it
+// (ParseError is the default one)
+pub trait ParseFailure: Sized + Send + 'static {
+    fn new(position: usize, message: &'static str) -> Self;
+    fn expected(position: usize, expected: Expected) -> Self;
+    // a failure with a message built at run time ("\"*/\" not found")
+    // (by default, the message is kept as the hint of a generic failure)
+    fn custom(position: usize, message: String) -> Self {
+        Self::new(position, "invalid input").with_hint(message)
+    }
+    // the input at position was not accepted (either a byte, or the end of input)
+    fn unexpected(position: usize, source: &[u8]) -> Self {
+        let failure = match source.get(position) {
+            Some(c) => Self::custom(position, format!("unexpected '{}'", c.escape_ascii())),
+            None => Self::new(position, "unexpected end of input")
+        };
+        failure.with_kind(ErrorKind::at(position, source))
+    }
+    fn position(&self) -> usize;
+    // the failure to keep when two parsers failed at the same point (see ParseError::merge())
+    fn merge(self, other: Self) -> Self;
+    // used by context(), with_message() and the combinators that know the kind of their failure
+    // (all do nothing by default)
+    fn add_context(&mut self, _name: &'static str) {}
+    fn with_message(self, _message: &'static str) -> Self {
+        self
+    }
+    fn with_kind(self, _kind: ErrorKind) -> Self {
+        self
+    }
+    fn with_hint(self, _hint: String) -> Self {
+        self
+    }
+    // the rules being parsed at the failure, outermost first
+    // (called by every named() rule the failure goes through: only the first, innermost stack is kept)
+    fn with_rules(self, _rules: &[&'static str]) -> Self {
+        self
+    }
+}
+
+impl ParseFailure for ParseError {
+    fn new(position: usize, message: &'static str) -> Self {
+        ParseError::new(position, message)
+    }
+
+    fn expected(position: usize, expected: Expected) -> Self {
+        ParseError::expected(position, expected)
+    }
+
+    fn custom(position: usize, message: String) -> Self {
+        ParseError::new(position, message)
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn merge(self, other: Self) -> Self {
+        ParseError::merge(self, other)
+    }
+
+    fn add_context(&mut self, name: &'static str) {
+        self.contexts.push(name);
+    }
+
+    // the expected set and the kinds are replaced too: the message says it all
+    fn with_message(self, message: &'static str) -> Self {
+        ParseError { contexts: self.contexts, ..ParseError::new(self.position, message) }
+    }
+
+    fn with_kind(self, kind: ErrorKind) -> Self {
+        ParseError::with_kind(self, kind)
+    }
+
+    fn with_hint(mut self, hint: String) -> Self {
+        self.notes_mut().hint = Some(hint);
+        self
+    }
+
+    fn with_rules(mut self, rules: &[&'static str]) -> Self {
+        if self.rules().is_empty() {
+            self.notes_mut().rules = rules.to_vec();
+        }
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.describe(f)?;
+        write!(f, " at offset {}", self.position)?;
+        self.describe_contexts(f)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_excerpts() {
+        // first line
+        let error = ParseError::expected(4, Expected::Byte(b']'));
+        let source = b"[1, 2\n[3]\n";
+        assert_eq!(error.line_column(source), (1, 5));
+        assert_eq!(error.display_with_source(source), "error at line 1, column 5: expected ']'\n[1, 2\n    ^");
+
+        // last line, without a trailing newline (the failure is at the end of the input)
+        let error = ParseError { contexts: vec!["list"], ..ParseError::expected(10, Expected::Byte(b']')) };
+        let source = b"[1]\r\n[2, 3";
+        assert_eq!(error.line_column(source), (2, 6));
+        assert_eq!(
+            error.display_with_source(source),
+            "error at line 2, column 6: expected ']', in list\n[2, 3\n     ^"
+        );
+        let error = ParseError::new(2, "unexpected ')'");
+        assert_eq!(error.display_with_source(b"a\n)"), "error at line 2, column 1: unexpected ')'\n)\n^");
+
+        // multi-byte characters count as one column, tabs are kept in front of the caret
+        let error = ParseError::expected(8, Expected::Byte(b'='));
+        let source = "x\n\tcafé:1".as_bytes();
+        assert_eq!(error.line_column(source), (2, 6));
+        assert_eq!(error.display_with_source(source), "error at line 2, column 6: expected '='\n\tcafé:1\n\t    ^");
+        // not valid utf-8: one column per byte
+        let error = ParseError::new(2, "bad");
+        assert_eq!(error.line_column(b"\xff\xfex"), (1, 3));
+        assert_eq!(error.display_with_source(b"\xff\xfex"), "error at line 1, column 3: bad\n\u{fffd}\u{fffd}x\n  ^");
+        // at the '\n' of a "\r\n": the caret is after the end of the line
+        assert_eq!(error.line_column(b"\xff\r\n"), (1, 2));
+        assert_eq!(error.display_with_source(b"\xff\r\n"), "error at line 1, column 2: bad\n\u{fffd}\n ^");
+        assert_eq!(error.display_with_source(b"x\r\n"), "error at line 1, column 2: bad\nx\n ^");
+
+        // a long line is cut around the failure
+        let mut source = vec![b'a'; 200];
+        source[100] = b'!';
+        let error = ParseError::new(100, "unexpected '!'");
+        let report = error.display_with_source(&source);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "error at line 1, column 101: unexpected '!'");
+        assert_eq!(lines[1], format!("...{}!{}...", "a".repeat(40), "a".repeat(39)));
+        assert_eq!(lines[2], format!("{}^", " ".repeat(43)));
+    }
+}
@@ -0,0 +1,288 @@
+use std::sync::Arc;
+use crate::{Parse, Parser, Result};
+use crate::Result::*;
+use crate::error::{ErrorKind, Expected, ParseError, ParseFailure};
+
+// parsers over slices of any item type: the tokens of a separate lexer, chars, records...
+// Parse has the type of the items as its last parameter (u8 by default), so the combinators, seq!, the methods of
+// ParserExt and grammar! (with type Item = ...;) all run over a &[I] (positions are indices in the slice)
+// this module only has the primitives that read items: item(), item_if(), select() and item_eq() read one item,
+// tag() a sequence of items, and end() succeeds after the last one
+// (left out of the prelude: tag() and end() have the same names as the byte ones)
+//
+// let number = select("number", |token: &Token| match token { Token::Number(n) => Some(*n), _ => None });
+// let sum = sep_by(number, item_eq(Token::Plus));
+
+// a parser over the items I (the item type first, to read ItemParser<Token, Expression>)
+pub type ItemParser<I, T, E = ParseError> = Parser<T, E, I>;
+
+// the failure of a parser that didn't want the item at position
+fn unexpected<I, E: ParseFailure>(position: usize, source: &[I]) -> E {
+    match source.get(position) {
+        Some(_) => E::new(position, "unexpected item"),
+        None => E::new(position, "unexpected end of input").with_kind(ErrorKind::UnexpectedEof)
+    }
+}
+
+
+// one item, turned into a value by f (the parser fails where f returns None)
+// the failure expects the name ("expected identifier at offset 3"), or is an unexpected item without one
+type SelectFn<I, T> = dyn Fn(&I) -> Option<T> + Send + Sync;
+
+struct SelectParser<I, T> {
+    name: Option<&'static str>,
+    f: Arc<SelectFn<I, T>>
+}
+
+impl<I: 'static, T: 'static, E: ParseFailure> Parse<T, E, I> for SelectParser<I, T> {
+    fn create(&self) -> ItemParser<I, T, E> {
+        Box::new(SelectParser { name: self.name, f: self.f.clone() })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        match self.name {
+            Some(name) => format!("Select({})", name),
+            None => "Item".to_string()
+        }
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        if let Some(data) = source.get(position).and_then(|item| (self.f)(item)) {
+            return Success(position + 1, data)
+        }
+        match self.name {
+            Some(name) => Fail(E::expected(position, Expected::Class(name))),
+            None => Fail(unexpected(position, source))
+        }
+    }
+}
+
+pub fn select<I: 'static, T: 'static, E: ParseFailure>(
+    name: &'static str,
+    f: impl Fn(&I) -> Option<T> + Send + Sync + 'static
+) -> ItemParser<I, T, E> {
+    SelectParser { name: Some(name), f: Arc::new(f) }.create()
+}
+
+// the next item, whatever it is
+pub fn item<I: Clone + 'static, E: ParseFailure>() -> ItemParser<I, I, E> {
+    SelectParser { name: None, f: Arc::new(|item: &I| Some(item.clone())) }.create()
+}
+
+pub fn item_if<I: Clone + 'static, E: ParseFailure>(
+    name: &'static str,
+    predicate: impl Fn(&I) -> bool + Send + Sync + 'static
+) -> ItemParser<I, I, E> {
+    select(name, move |item: &I| predicate(item).then(|| item.clone()))
+}
+
+pub fn item_eq<I: PartialEq + Clone + Send + Sync + 'static, E: ParseFailure>(value: I) -> ItemParser<I, I, E> {
+    SelectParser { name: None, f: Arc::new(move |item: &I| (*item == value).then(|| item.clone())) }.create()
+}
+
+// a sequence of items (fails at its start position, like the byte tag())
+struct TagParser<I> {
+    items: Arc<[I]>
+}
+
+impl<I: PartialEq + Clone + Send + Sync + 'static, E: ParseFailure> Parse<Vec<I>, E, I> for TagParser<I> {
+    fn create(&self) -> ItemParser<I, Vec<I>, E> {
+        Box::new(TagParser { items: self.items.clone() })
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<Vec<I>, E> {
+        let rest = source.get(position..).unwrap_or_default();
+        if rest.starts_with(&self.items) {
+            return Success(position + self.items.len(), self.items.to_vec())
+        }
+        // the start of the tag, cut by the end of the source
+        if self.items.starts_with(rest) {
+            return Fail(E::new(position, "unexpected end of input").with_kind(ErrorKind::UnexpectedEof))
+        }
+        Fail(E::new(position, "unexpected item"))
+    }
+}
+
+pub fn tag<I: PartialEq + Clone + Send + Sync + 'static, E: ParseFailure>(items: Vec<I>) -> ItemParser<I, Vec<I>, E> {
+    TagParser { items: items.into() }.create()
+}
+
+// succeeds at the end of the source only
+struct EndParser {}
+
+impl<I: 'static, E: ParseFailure> Parse<(), E, I> for EndParser {
+    fn create(&self) -> ItemParser<I, (), E> {
+        Box::new(EndParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[I]) -> Result<(), E> {
+        if position >= source.len() {
+            return Success(position, ())
+        }
+        Fail(E::expected(position, Expected::EndOfInput))
+    }
+}
+
+pub fn end<I: 'static, E: ParseFailure>() -> ItemParser<I, (), E> {
+    EndParser {}.create()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParserExt;
+    use crate::combinator::{context, cut, delimited, oneof, pair, preceded, process, recursive, sep_by, star};
+    use crate::{grammar, number, primitive, seq, text};
+
+    #[derive(Clone, Eq, PartialEq, Debug)]
+    enum Token {
+        Let,
+        Name(String),
+        Number(u64),
+        Equal,
+        Plus,
+        Open,
+        Close
+    }
+
+    fn lexer() -> Parser<Vec<Token>> {
+        let token = oneof(vec![
+            crate::combinator::value(Token::Let, primitive::tag(b"let")),
+            process(Token::Name, text::identifier()),
+            process(Token::Number, number::uint()),
+            crate::combinator::value(Token::Equal, primitive::byte(b'=')),
+            crate::combinator::value(Token::Plus, primitive::byte(b'+')),
+            crate::combinator::value(Token::Open, primitive::byte(b'(')),
+            crate::combinator::value(Token::Close, primitive::byte(b')'))
+        ]);
+        preceded(primitive::space0(), star(crate::combinator::lexeme(token)))
+    }
+
+    // let name = expression, where an expression is a sum of numbers, names and (expressions)
+    #[derive(Eq, PartialEq, Debug)]
+    enum Expression {
+        Number(u64),
+        Name(String),
+        Sum(Vec<Expression>)
+    }
+
+    fn name() -> ItemParser<Token, String> {
+        select("name", |token: &Token| match token {
+            Token::Name(name) => Some(name.clone()),
+            _ => None
+        })
+    }
+
+    fn sum(first: Expression, mut rest: Vec<Expression>) -> Expression {
+        match rest.len() {
+            0 => first,
+            _ => {
+                rest.insert(0, first);
+                Expression::Sum(rest)
+            }
+        }
+    }
+
+    fn assignment() -> ItemParser<Token, (String, Expression)> {
+        let expression = recursive(|expression| {
+            let operand = oneof(vec![
+                select("number", |token: &Token| match token {
+                    Token::Number(n) => Some(Expression::Number(*n)),
+                    _ => None
+                }),
+                name().map(Expression::Name),
+                delimited(item_eq(Token::Open), expression, cut(item_eq(Token::Close)))
+            ]);
+            pair(operand.clone(), star(preceded(item_eq(Token::Plus), operand))).map(|(first, rest)| sum(first, rest))
+        });
+        process(
+            |(_, name, _, expression, _)| (name, expression),
+            seq!(item_eq(Token::Let), name(), item_eq(Token::Equal), expression, end())
+        )
+    }
+
+    // the same grammar, with the rules of grammar! over the tokens
+    fn token(token: Token) -> ItemParser<Token, Token> {
+        item_eq(token)
+    }
+
+    grammar! {
+        type Item = Token;
+        let_rule: (String, Expression) = {token(Token::Let)} ~ {name()} ~ {token(Token::Equal)} ~ expression ~ {end()}
+            => |(_, name, _, expression, _)| (name, expression);
+        expression: Expression = operand ~ ({token(Token::Plus)} ~ operand)*
+            => |(first, rest): (_, Vec<_>)| sum(first, rest.into_iter().map(|(_, operand)| operand).collect());
+        operand: Expression = number | {name().map(Expression::Name)} | group;
+        group: Expression = {token(Token::Open)} ~ expression ~ {token(Token::Close)}
+            => |(_, expression, _)| expression;
+    }
+
+    #[test]
+    fn tokens() {
+        let tokens = lexer().parse_all(b" let x = 1 + (y + 2)").value().unwrap();
+        assert_eq!(tokens.len(), 10);
+        let expected = ("x".to_string(), Expression::Sum(vec![
+            Expression::Number(1),
+            Expression::Sum(vec![Expression::Name("y".to_string()), Expression::Number(2)])
+        ]));
+        assert_eq!(assignment().parse_all(&tokens), Success(10, expected));
+
+        // the positions are token indices
+        let tokens = lexer().parse_all(b"let = 1").value().unwrap();
+        assert_eq!(assignment().parse(0, &tokens), Fail(ParseError::expected(1, Expected::Class("name"))));
+        // (cut: the missing ) is fatal)
+        let tokens = lexer().parse_all(b"let x = (1 + 2").value().unwrap();
+        let error = assignment().parse(0, &tokens);
+        assert_eq!(error, Error(ParseError::new(7, "unexpected end of input").with_kind(ErrorKind::UnexpectedEof)));
+        let tokens = lexer().parse_all(b"let x = 1 2").value().unwrap();
+        assert_eq!(assignment().parse(0, &tokens), Fail(ParseError::expected(4, Expected::EndOfInput)));
+    }
+
+    #[test]
+    fn token_grammar() {
+        let tokens = lexer().parse_all(b"let x = (1 + y) + 2").value().unwrap();
+        let (_, (name, expression)) = let_rule().parse_all(&tokens).expect_success("let");
+        assert_eq!(name, "x");
+        assert_eq!(expression, Expression::Sum(vec![
+            Expression::Sum(vec![Expression::Number(1), Expression::Name("y".to_string())]),
+            Expression::Number(2)
+        ]));
+        assert!(let_rule().parse_all(&tokens[..4]).is_fail());
+
+        // context() and describe() work the same as over bytes
+        let named = context("let", let_rule());
+        let error = named.run(&tokens[1..]).unwrap_err();
+        assert_eq!(error.contexts, vec!["let"]);
+        assert_eq!(select::<Token, u64, ParseError>("number", |_| None).describe(), "Select(number)");
+    }
+
+    fn number() -> ItemParser<Token, Expression> {
+        select("number", |token: &Token| match token {
+            Token::Number(n) => Some(Expression::Number(*n)),
+            _ => None
+        })
+    }
+
+    #[test]
+    fn items() {
+        let p: ItemParser<char, Vec<char>> = tag(vec!['a', 'b']);
+        assert_eq!(p.parse(0, &['a', 'b', 'c']), Success(2, vec!['a', 'b']));
+        assert_eq!(p.parse(0, &['a', 'c']), Fail(ParseError::new(0, "unexpected item")));
+        let error = ParseError::new(0, "unexpected end of input").with_kind(ErrorKind::UnexpectedEof);
+        assert_eq!(p.parse(0, &['a']), Fail(error));
+
+        let vowels = item_if::<char, ParseError>("vowel", |c| "aeiou".contains(*c)).star();
+        assert_eq!(vowels.parse(0, &['e', 'a', 'x']), Success(2, vec!['e', 'a']));
+        assert_eq!(vowels.match_len(&['a', 'x']), Some(1));
+        let any = item::<i32, ParseError>();
+        assert_eq!(any.parse(1, &[1, 2]), Success(2, 2));
+        assert!(any.parse(2, &[1, 2]).is_fail());
+        let numbers = sep_by(item::<i32, ParseError>(), item_eq(0));
+        assert_eq!(numbers.run_all(&[1, 0, 2, 0, 3]), Ok(vec![1, 2, 3]));
+
+        // the item parsers over bytes are byte parsers
+        let numbers = sep_by(number::uint(), item_eq(b','));
+        assert_eq!(numbers.parse_all(b"1,22,333"), Success(8, vec![1, 22, 333]));
+    }
+}
@@ -1,318 +1,605 @@
-use std::ops::Deref;
+// parser combinators over byte slices
+// primitive has the base parsers (bytes, tags, whitespace...), combinator builds parsers out of other parsers,
+// number and text parse common tokens, error describes failures,
+// parseable lets types declare their own parser (Parseable, implemented for the std types),
+// and search uses a parser as a pattern (find_all(), replace_all())
+// (use parser::prelude::* brings all of them in scope, number and text as modules: number::uint()...)
+// state has the parsers that thread a state of the application through the parse
+// (left out of the prelude: its combinators have the same names as the stateless ones)
+// the parsers read bytes by default, but Parse<T, E, I> runs over a &[I] of any item type (the tokens of a lexer...):
+// items has the parsers that read those items (not in the prelude), the combinators are the same
+// token is for a grammar over the tokens of a lexer (tokenize(), then the combinators), with the spans of the tokens
+// string is for parsing a &str, with positions at char boundaries and chars and Strings for values
+// stream parses the records of a std::io::Read one at a time, through a bounded buffer
+// test_util has assert_parses!() and assert_fails!(), for the tests of grammars (testing feature)
+
+use std::fmt;
+use std::ops::{Add, BitOr, Deref, Shr};
 use crate::Result::*;
+use crate::combinator::{oneof, optional, pair, process, require, star};
+use crate::error::{Expected, ParseError, ParseFailure};
+
+pub mod error;
+pub mod primitive;
+pub mod combinator;
+pub mod number;
+pub mod text;
+pub mod parseable;
+pub mod search;
+pub mod state;
+pub mod items;
+pub mod token;
+pub mod string;
+pub mod stream;
+#[cfg(any(test, feature = "testing"))]
+pub mod test_util;
+
+// #[derive(Parse)] implements Parseable (the derive macro and the Parse trait don't share a namespace)
+#[cfg(feature = "derive")]
+pub use parser_derive::Parse;
+
+// everything needed to write a grammar: the parser types, the parsers and the error types
+// (Result is left out, so that it doesn't hide std::result::Result: its variants are parser::Result::*)
+pub mod prelude {
+    pub use crate::{number, text, Parse, Parser, ParserExt};
+    pub use crate::combinator::*;
+    pub use crate::primitive::*;
+    pub use crate::error::{ErrorKind, Expected, ParseError, ParseFailure};
+    pub use crate::parseable::{parse_value, Parseable, Parsed};
+    pub use crate::search::{find_all, replace_all};
+}
 
 // parsing types
 // the [derive] is to check equality in tests
-#[derive(Eq, PartialEq, Debug)]
-enum Result<T> {
-    Fail,
+// Fail can be backtracked (oneof() tries the next alternative, star() stops looping)
+// Error is fatal: every combinator propagates it as is (see cut())
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Result<T, E = ParseError> {
+    Fail(E),
+    Error(E),
     Success(usize, T),
 }
 
+impl<T, E> Result<T, E> {
+    pub fn is_fail(&self) -> bool {
+        matches!(self, Fail(_))
+    }
+
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Error(_))
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, Success(_, _))
+    }
+
+    // transform the parsed value (the position and the failures are kept)
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Result<U, E> {
+        match self {
+            Success(position, data) => Success(position, f(data)),
+            Fail(error) => Fail(error),
+            Error(error) => Error(error)
+        }
+    }
+
+    // continue after a success, from its end position and with its value
+    // (p.parse(0, source).and_then(|position, _| q.parse(position, source)))
+    pub fn and_then<U>(self, f: impl FnOnce(usize, T) -> Result<U, E>) -> Result<U, E> {
+        match self {
+            Success(position, data) => f(position, data),
+            Fail(error) => Fail(error),
+            Error(error) => Error(error)
+        }
+    }
+
+    // the end position and the value, without the failure
+    pub fn ok(self) -> Option<(usize, T)> {
+        match self {
+            Success(position, data) => Some((position, data)),
+            _ => None
+        }
+    }
+
+    // end position of a success
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            Success(position, _) => Some(*position),
+            _ => None
+        }
+    }
+
+    pub fn value(self) -> Option<T> {
+        self.ok().map(|(_, data)| data)
+    }
+}
+
+impl<T, E: std::fmt::Debug> Result<T, E> {
+    // the end position and the value, or a panic with the message and the failure (mostly for tests)
+    pub fn expect_success(self, message: &str) -> (usize, T) {
+        match self {
+            Success(position, data) => (position, data),
+            Fail(error) => panic!("{}: {:?}", message, error),
+            Error(error) => panic!("{}: fatal {:?}", message, error)
+        }
+    }
+}
+
+impl<T, E: ParseFailure> Result<T, E> {
+    // std Result for ? flows: the whole source (of length source_len) has to be consumed
+    // (a fatal error is an Err like any other failure)
+    pub fn into_std(self, source_len: usize) -> std::result::Result<(usize, T), E> {
+        match self {
+            Success(position, data) if position == source_len => Ok((position, data)),
+            Success(position, _) => Err(E::expected(position, Expected::EndOfInput)),
+            Fail(error) | Error(error) => Err(error)
+        }
+    }
+}
+
+// the end position and the value of a success (for filter_map() and the like)
+impl<T, E> From<Result<T, E>> for Option<(usize, T)> {
+    fn from(result: Result<T, E>) -> Self {
+        result.ok()
+    }
+}
+
+// same as into_std(), without checking the end position
+impl<T, E> From<Result<T, E>> for std::result::Result<(usize, T), E> {
+    fn from(result: Result<T, E>) -> Self {
+        match result {
+            Success(position, data) => Ok((position, data)),
+            Fail(error) | Error(error) => Err(error)
+        }
+    }
+}
+
 /*
 Parse trait: create() -> Parser; parse()
 Parser type: clone(); parse()
+
+Parse is not sealed: an application can write its own parsers, as long as create() returns a copy
+(Parser::clone() relies on it)
 */
 
-trait Parse<T> {
-    fn create(&self) -> Parser<T>; // create a Box<dyn Parse> trait object
-    fn parse(&self, position: usize, source: &[u8]) -> Result<T>;
-}
+// I is the type of the items of the source: bytes by default, or the tokens of a lexer (see items)
+pub trait Parse<T, E = ParseError, I = u8> {
+    fn create(&self) -> Parser<T, E, I>; // create a Box<dyn Parse> trait object
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E>;
+
+    // parse the whole source (fails if anything is left after the parser)
+    fn parse_all(&self, source: &[I]) -> Result<T, E> where E: ParseFailure {
+        match self.parse(0, source) {
+            Success(position, data) if position == source.len() => Success(position, data),
+            Success(position, _) => Fail(E::expected(position, Expected::EndOfInput)),
+            Fail(error) => Fail(error),
+            Error(error) => Error(error)
+        }
+    }
 
-// Sync is for static definitions (thread-safety)
-type Parser<T> = Box<dyn Parse<T> + Sync>;
+    // entry points for applications: a std Result, so failures work with ? and Box<dyn Error>
+    // the end position is returned along with the data
+    fn run(&self, source: &[I]) -> std::result::Result<(usize, T), E> {
+        match self.parse(0, source) {
+            Success(position, data) => Ok((position, data)),
+            Fail(error) | Error(error) => Err(error)
+        }
+    }
 
-impl<T> Parse<T> for Parser<T> {
-    // create() is not strictly required (clone is used already)
-    // the trait name is reused because i'm lazy
-    fn create(&self) -> Parser<T> {
-        todo!()
+    // same as run(), but the whole source has to be consumed
+    fn run_all(&self, source: &[I]) -> std::result::Result<T, E> where E: ParseFailure {
+        match self.parse_all(source) {
+            Success(_, data) => Ok(data),
+            Fail(error) | Error(error) => Err(error)
+        }
     }
 
-    fn parse(&self, position: usize, source: &[u8]) -> Result<T> {
-        self.deref().parse(position, source)
+    // parse from the start of a string or of bytes: number::uint().parse_str("12 apples") is Success(2, 12)
+    // (only for parsers over bytes, see Byte)
+    fn parse_str(&self, s: &str) -> Result<T, E> where I: Byte {
+        self.parse(0, I::from_bytes(s.as_bytes()))
     }
-}
 
-impl<T> Clone for Parser<T> {
-    fn clone(&self) -> Self {
-        self.deref().create()
+    fn parse_bytes(&self, bytes: &[u8]) -> Result<T, E> where I: Byte {
+        self.parse(0, I::from_bytes(bytes))
     }
-}
 
+    // same, but the whole input has to be consumed, and the value is returned as a std Result (see run_all())
+    fn parse_str_all(&self, s: &str) -> std::result::Result<T, E> where E: ParseFailure, I: Byte {
+        self.run_all(I::from_bytes(s.as_bytes()))
+    }
 
+    fn parse_bytes_all(&self, bytes: &[u8]) -> std::result::Result<T, E> where E: ParseFailure, I: Byte {
+        self.run_all(I::from_bytes(bytes))
+    }
 
-// base parser
+    // queries for when the value doesn't matter (is it an identifier? how far does the token go?)
+    // matches() only needs a success from the start of the input: the rest doesn't have to be consumed
+    // (matches_all() is for the whole input), and a fatal error doesn't match
+    fn matches(&self, input: &[I]) -> bool {
+        self.match_len(input).is_some()
+    }
+
+    fn matches_at(&self, position: usize, input: &[I]) -> bool {
+        self.match_len_at(position, input).is_some()
+    }
 
-struct CharParser {}
+    fn matches_all(&self, input: &[I]) -> bool {
+        self.match_len(input) == Some(input.len())
+    }
 
+    // the number of items (bytes) consumed by a success
+    fn match_len(&self, input: &[I]) -> Option<usize> {
+        self.match_len_at(0, input)
+    }
+
+    fn match_len_at(&self, position: usize, input: &[I]) -> Option<usize> {
+        self.parse(position, input).position().map(|end| end - position)
+    }
 
-impl Parse<u8> for CharParser {
-    fn create(&self) -> Parser<u8> {
-        Box::new(CharParser{})
+    // the alternatives of a oneof() parser (lets | flatten a chain of alternatives)
+    fn alternatives(&self) -> Option<&[Parser<T, E, I>]> {
+        None
     }
 
-    fn parse(&self, position: usize, source: &[u8]) -> Result<u8> {
-        if position < source.len() {
-            Success(position + 1, source[position])
-        } else {
-            Fail
+    // the shape of the parser, for debugging: Star(Or([Tag("let"), Identifier]))
+    // depth is the number of nested levels that can still be shown (see describe_node())
+    // by default, the name of the parser struct without its Parser suffix
+    fn describe_with(&self, _depth: usize) -> String {
+        parser_name(std::any::type_name::<Self>()).to_string()
+    }
+
+    // describe_with(), cut to a reasonable size for huge grammars
+    fn describe(&self) -> String {
+        let description = self.describe_with(DESCRIBE_DEPTH);
+        match description.char_indices().nth(DESCRIBE_LENGTH) {
+            Some((end, _)) => format!("{}...", &description[..end]),
+            None => description
         }
     }
 }
 
-fn readchar() -> Parser<u8> {
-    CharParser{}.create()
+// the item type of the parsers over bytes (only u8): the string entry points of Parse need it
+// (a where Self: Parse<T, E, u8> clause would keep Parse from being a trait object)
+pub trait Byte: Sized {
+    fn from_bytes(bytes: &[u8]) -> &[Self];
 }
 
+impl Byte for u8 {
+    fn from_bytes(bytes: &[u8]) -> &[u8] {
+        bytes
+    }
+}
 
-// parser combinators
+// limits of describe(): nested levels, children shown for a list of parsers, and length of the description
+const DESCRIBE_DEPTH: usize = 8;
+const DESCRIBE_WIDTH: usize = 8;
+const DESCRIBE_LENGTH: usize = 2000;
 
-struct AndParser<T> {
-    parsers: Vec<Parser<T>>
+// parser::combinator::StarParser<u8, parser::error::ParseError> -> Star
+fn parser_name(type_name: &str) -> &str {
+    let name = type_name.split('<').next().unwrap_or(type_name);
+    let name = name.rsplit("::").next().unwrap_or(name);
+    name.strip_suffix("Parser").unwrap_or(name)
 }
 
-impl<T: 'static> Parse<Vec<T>> for AndParser<T> {
-    fn create(&self) -> Parser<Vec<T>> {
-        //let parsers = self.parsers.clone();
-        Box::new(AndParser { parsers: self.parsers.clone() })
-    }
-
-    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<T>> {
-        let mut cursor = position;
-        let mut parsed = Vec::new();
-        for p in &self.parsers {
-            let r = p.parse(cursor, source);
-            match r {
-                Fail => {
-                    return Fail
-                }
-                Success(pos, data) => {
-                    parsed.push(data);
-                    cursor = pos;
-                }
-            }
-        }
-        Success(cursor, parsed)
+// Name(child, ...): the children are only described if depth allows it (Name(..) otherwise)
+fn describe_node(name: &str, depth: usize, children: impl FnOnce(usize) -> Vec<String>) -> String {
+    if depth == 0 {
+        return format!("{}(..)", name)
     }
+    format!("{}({})", name, children(depth - 1).join(", "))
 }
 
-fn concat<T: 'static>(parsers: Vec<Parser<T>>) -> Parser<Vec<T>> {
-    AndParser { parsers }.create()
+// Name([child, ...]) for a list of parsers, with only the first DESCRIBE_WIDTH children
+fn describe_list<T, E: ParseFailure, I>(name: &str, depth: usize, parsers: &[Parser<T, E, I>]) -> String {
+    describe_node(name, depth, |depth| {
+        let mut children: Vec<String> = parsers.iter().take(DESCRIBE_WIDTH).map(|p| p.describe_with(depth)).collect();
+        if parsers.len() > DESCRIBE_WIDTH {
+            children.push(format!("...{} more", parsers.len() - DESCRIBE_WIDTH));
+        }
+        vec![format!("[{}]", children.join(", "))]
+    })
 }
 
+// Sync is for static definitions (thread-safety, see combinator::GrammarCell)
+// Send lets parsers be shared through an Arc (recursive grammars)
+// E is the type of the failures (any ParseFailure, ParseError unless the grammar has its own)
+// I is the type of the items of the source (u8 unless the grammar runs over tokens)
+pub type Parser<T, E = ParseError, I = u8> = Box<dyn Parse<T, E, I> + Send + Sync>;
 
-struct OrParser<T> {
-    parsers: Vec<Parser<T>>
-}
+impl<T, E: ParseFailure, I> Parse<T, E, I> for Parser<T, E, I> {
+    fn create(&self) -> Parser<T, E, I> {
+        self.deref().create()
+    }
 
-impl<T: 'static> Parse<T> for OrParser<T> {
-    fn create(&self) -> Parser<T> {
-        Box::new(OrParser { parsers: self.parsers.clone() })
+    fn parse(&self, position: usize, source: &[I]) -> Result<T, E> {
+        self.deref().parse(position, source)
     }
 
-    fn parse(&self, position: usize, source: &[u8]) -> Result<T> {
-        for p in &self.parsers {
-            match p.parse(position, source) {
-                Fail => (),
-                Success(pos, data) => return Success(pos, data)
-            }
-        }
-        Fail
+    fn alternatives(&self) -> Option<&[Parser<T, E, I>]> {
+        self.deref().alternatives()
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        self.deref().describe_with(depth)
     }
 }
 
-fn oneof<T: 'static>(parsers: Vec<Parser<T>>) -> Parser<T> {
-    OrParser {parsers}.create()
+// {:?} and {} both show describe()
+impl<T, E, I> fmt::Debug for dyn Parse<T, E, I> + Send + Sync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.describe())
+    }
 }
 
-// only accept results that are matched by the filter function
-struct FilterParser<T> {
-    parser: Parser<T>,
-    filter: fn(&T) -> bool
+impl<T, E, I> fmt::Display for dyn Parse<T, E, I> + Send + Sync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.describe())
+    }
 }
 
-impl<T: 'static> Parse<T> for FilterParser<T> {
-    fn create(&self) -> Parser<T> {
-        Box::new(FilterParser{parser: self.parser.clone(), filter: self.filter.clone() })
-    }
-
-    fn parse(&self, position: usize, source: &[u8]) -> Result<T> {
-        match self.parser.parse(position, source) {
-            Fail => {
-                Fail
-            }
-            Success(position, data) => {
-                if (self.filter)(&data) {
-                    Success(position, data)
-                } else {
-                    Fail
-                }
-            }
-        }
+impl<T, E: ParseFailure, I> Clone for Parser<T, E, I> {
+    fn clone(&self) -> Self {
+        self.deref().create()
     }
 }
 
-fn require<T: 'static>(f: fn(&T) -> bool, p: Parser<T>) -> Parser<T> {
-    FilterParser { parser: p, filter: f }.create()
+// method chaining: the combinators read left to right
+// readchar().star().filter(|s| !s.is_empty()).map(|s| s.len())
+// is process(|s| s.len(), require(|s| !s.is_empty(), star(readchar())))
+// (each method moves the parser into the combinator, nothing more is cloned)
+pub trait ParserExt<T, E, I = u8> {
+    fn map<U: 'static>(self, f: impl Fn(T) -> U + Send + Sync + 'static) -> Parser<U, E, I>;
+    fn filter(self, f: impl Fn(&T) -> bool + Send + Sync + 'static) -> Parser<T, E, I>;
+    fn then<U: 'static>(self, other: Parser<U, E, I>) -> Parser<(T, U), E, I>;
+    fn or(self, other: Parser<T, E, I>) -> Parser<T, E, I>;
+    fn star(self) -> Parser<Vec<T>, E, I>;
+    fn opt(self) -> Parser<Option<T>, E, I>;
 }
 
+impl<T: 'static, E: ParseFailure, I: 'static> ParserExt<T, E, I> for Parser<T, E, I> {
+    fn map<U: 'static>(self, f: impl Fn(T) -> U + Send + Sync + 'static) -> Parser<U, E, I> {
+        process(f, self)
+    }
 
-// apply a function to the result of a successful parsing
-struct MapParser<T, U> {
-    parser: Parser<T>,
-    f: fn(T) -> U
-}
+    fn filter(self, f: impl Fn(&T) -> bool + Send + Sync + 'static) -> Parser<T, E, I> {
+        require(f, self)
+    }
 
-impl<T: 'static, U: 'static> Parse<U> for MapParser<T, U> {
-    fn create(&self) -> Parser<U> {
-        Box::new(MapParser { parser: self.parser.clone(), f: self.f })
+    fn then<U: 'static>(self, other: Parser<U, E, I>) -> Parser<(T, U), E, I> {
+        pair(self, other)
     }
 
-    fn parse(&self, position: usize, source: &[u8]) -> Result<U> {
-        let result = self.parser.parse(position, source);
-        match result {
-            Fail => {
-                Fail
-            }
-            Success(position, data) => {
-                Success(position, (self.f)(data))
-            }
-        }
+    fn or(self, other: Parser<T, E, I>) -> Parser<T, E, I> {
+        self | other
     }
-}
 
-fn process<T: 'static, U: 'static>(f: fn(T) -> U, parser: Parser<T>) -> Parser<U> {
-    MapParser { parser, f }.create()
-}
+    fn star(self) -> Parser<Vec<T>, E, I> {
+        star(self)
+    }
 
-// make a parser able to repeat as much as possible
-struct StarParser<T> {
-    parser: Parser<T>
+    fn opt(self) -> Parser<Option<T>, E, I> {
+        optional(self)
+    }
 }
 
-impl<T: 'static> Parse<Vec<T>> for StarParser<T> {
-    fn create(&self) -> Parser<Vec<T>> {
-        Box::new(StarParser {parser: self.parser.clone()})
-    }
-
-    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<T>> {
-        let mut cursor = position;
-        let mut results = Vec::new();
-        loop {
-            match self.parser.parse(cursor, source) {
-                Fail => {
-                    break
-                }
-                Success(position, data) => {
-                    results.push(data);
-                    cursor = position;
-                }
-            }
-        }
-        // star() always succeeds, even if nothing is parsed
-        Success(cursor, results)
+// operators
+// a | b is oneof(vec![a, b]) (a | b | c is a single oneof() of the three)
+// a + b and a >> b are pair(a, b)
+// beware of the precedence of rust operators: + binds tighter than >>, and both bind tighter than |
+// - a | b + c is a | pair(b, c) (write (a | b) + c to sequence the alternative)
+// - a + b + c is pair(pair(a, b), c), with ((A, B), C) values
+// - a + b >> c is also pair(pair(a, b), c) (clippy asks for the parentheses when + and >> are mixed)
+impl<T: 'static, E: ParseFailure, I: 'static> BitOr for Parser<T, E, I> {
+    type Output = Parser<T, E, I>;
+
+    fn bitor(self, other: Parser<T, E, I>) -> Parser<T, E, I> {
+        let mut parsers = match self.alternatives().map(<[_]>::to_vec) {
+            Some(parsers) => parsers,
+            None => vec![self]
+        };
+        parsers.push(other);
+        oneof(parsers)
     }
 }
 
-fn star<T: 'static>(parser: Parser<T>) -> Parser<Vec<T>> {
-    StarParser {parser}.create()
-}
+impl<T: 'static, U: 'static, E: ParseFailure, I: 'static> Add<Parser<U, E, I>> for Parser<T, E, I> {
+    type Output = Parser<(T, U), E, I>;
 
-// TODO: additional combinators (chain, const, many, tag,...)
-// these ones do not need any more struct/trait implementation
-// (they are just shortcuts to quickly implement parsers)
+    fn add(self, other: Parser<U, E, I>) -> Parser<(T, U), E, I> {
+        pair(self, other)
+    }
+}
 
+impl<T: 'static, U: 'static, E: ParseFailure, I: 'static> Shr<Parser<U, E, I>> for Parser<T, E, I> {
+    type Output = Parser<(T, U), E, I>;
 
+    fn shr(self, other: Parser<U, E, I>) -> Parser<(T, U), E, I> {
+        pair(self, other)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::combinator::*;
+    use crate::primitive::{byte, one_of, pure, tag};
 
     #[test]
-    fn starred() {
-        let p = readchar();
-        let p = star(p);
-        let result = p.parse(0, "test".as_bytes());
-        assert!(matches!(result, Success(4, _)));
-        if let Success(_position, chars) = result {
-            let str = String::from_utf8(chars).unwrap();
-            assert_eq!(str, "test");
-        }
+    fn result_adapters() {
+        let number = number::uint();
+        let failure = || number.parse(0, b"x");
+        assert!(number.parse(0, b"12").is_success());
+        assert!(!failure().is_success());
+        assert!(!cut(number.clone()).parse(0, b"x").is_success());
+
+        assert_eq!(number.parse(0, b"12").map(|n| n * 2), Success(2, 24));
+        assert_eq!(failure().map(|n| n * 2), failure());
+        assert!(cut(number.clone()).parse(0, b"x").map(|n| n * 2).is_fatal());
+
+        // a second number after a comma
+        let comma = primitive::byte(b',');
+        let second = |source: &'static [u8]| number.parse(0, source).and_then(|position, first| {
+            comma.parse(position, source)
+                .and_then(|position, _| number.parse(position, source))
+                .map(|second| first + second)
+        });
+        assert_eq!(second(b"1,2"), Success(3, 3));
+        assert_eq!(second(b"1;2"), Fail(ParseError::expected(1, Expected::Byte(b','))));
+        assert_eq!(second(b"x"), failure());
+
+        assert_eq!(number.parse(0, b"12").ok(), Some((2, 12)));
+        assert_eq!(failure().ok(), None);
+        assert_eq!(number.parse(0, b"12 ").position(), Some(2));
+        assert_eq!(failure().position(), None);
+        assert_eq!(number.parse(0, b"12").value(), Some(12));
+        assert_eq!(failure().value(), None);
+
+        assert_eq!(number.parse(0, b"12").expect_success("a number"), (2, 12));
+        let result = number.parse(0, b"12");
+        assert_eq!(result.clone(), result);
+    }
 
-        // star combined with mapped
-        let p = process(|chars| String::from_utf8(chars).unwrap(), p);
-        let result = p.parse(0, "test".as_bytes());
-        assert!(matches!(result, Success(4, _)));
-        if let Success(4, s) = result {
-            assert_eq!(s, "test");
-        }
+    #[test]
+    #[should_panic(expected = "a number: ParseError")]
+    fn expect_success_fail() {
+        number::uint().parse(0, b"x").expect_success("a number");
     }
 
     #[test]
-    fn mapped() {
-        let string = process(|c| { String::from_utf8(vec![c]).unwrap() }, readchar());
-        let result = string.parse(0, "test".as_bytes());
-        assert!(matches!(result, Success(1, _)));
-        if let Success(_, s) = result {
-            assert_eq!(s, "t");
+    fn std_conversions() {
+        // the numbers among the inputs
+        let number = number::uint();
+        let inputs: Vec<&[u8]> = vec![b"12", b"x", b"3 ", b""];
+        let numbers: Vec<u64> = inputs.iter()
+            .filter_map(|source| Option::from(number.parse(0, source)))
+            .map(|(_, n)| n)
+            .collect();
+        assert_eq!(numbers, vec![12, 3]);
+
+        // "a+b", both numbers checked with ?
+        fn add(source: &[u8]) -> std::result::Result<u64, ParseError> {
+            let number = number::uint();
+            let (position, a) = std::result::Result::from(number.parse(0, source))?;
+            let (position, _) = std::result::Result::from(primitive::byte(b'+').parse(position, source))?;
+            let (_, b) = number.parse(position, source).into_std(source.len())?;
+            Ok(a + b)
         }
+        assert_eq!(add(b"1+2"), Ok(3));
+        assert_eq!(add(b"1-2"), Err(ParseError::expected(1, Expected::Byte(b'+'))));
+        assert_eq!(add(b"1+2 "), Err(ParseError::expected(3, Expected::EndOfInput)));
+
+        assert_eq!(number.parse(0, b"7").into_std(1), Ok((1, 7)));
+        assert!(cut(number).parse(0, b"x").into_std(1).is_err());
     }
 
     #[test]
-    fn filtered() {
-        let p = readchar();
-        let f: fn(&u8) -> bool = |c| { *c == 't' as u8};
-        let p = require(f, p);
-
-        let result = p.parse(0, "test".as_bytes());
-        assert!(matches!(result, Success(1, _)));
-        if let Success(1, ch) = result {
-            assert_eq!(ch, 't' as u8)
-        }
+    fn entry_points() {
+        let number = number::uint();
+        // the stop position of the plain variants
+        assert_eq!(number.parse_str("12 apples"), Success(2, 12));
+        assert_eq!(number.parse_bytes(b"12"), Success(2, 12));
+        assert_eq!(number.parse_str("apples"), Fail(ParseError::expected(0, Expected::Class("digit"))));
+
+        // the whole input
+        assert_eq!(number.parse_str_all("12"), Ok(12));
+        assert_eq!(number.parse_bytes_all(b"12"), Ok(12));
+        assert_eq!(number.parse_str_all("12 apples"), Err(ParseError::expected(2, Expected::EndOfInput)));
+        assert_eq!(number.parse_bytes_all(b""), Err(ParseError::expected(0, Expected::Class("digit"))));
+    }
 
-        let p = require(| c | { *c == 'x' as u8}, readchar());
-        let result = p.parse(0, "test".as_bytes());
-        assert!(matches!(result, Fail));
+    #[test]
+    fn match_queries() {
+        let identifier = text::identifier();
+        // prefix and full matches
+        assert!(identifier.matches(b"x1 = 2"));
+        assert!(!identifier.matches_all(b"x1 = 2"));
+        assert!(identifier.matches_all(b"x1"));
+        assert_eq!(identifier.match_len(b"x1 = 2"), Some(2));
+        assert_eq!(identifier.match_len_at(5, b"x1 = y2"), Some(2));
+        assert!(identifier.matches_at(5, b"x1 = y2"));
+        // failures
+        assert!(!identifier.matches(b"1x"));
+        assert_eq!(identifier.match_len(b""), None);
+        assert!(!identifier.matches_at(3, b"x1 = 2"));
+        assert!(!cut(byte(b'a')).matches(b"b"));
+        // an empty match is a match
+        assert_eq!(primitive::space0().match_len(b"x"), Some(0));
+        assert!(tag(b"let").matches(b"let x"));
+        assert!(skip(tag(b"--")).matches_all(b"--"));
     }
 
     #[test]
-    fn or() {
-        let p = oneof(vec![readchar(), readchar()]);
-        let result = p.parse(0, "test".as_bytes());
-        assert!(matches!(result, Success(1, _)));
-        if let Success(1, ch) = result {
-            assert_eq!(ch, 't' as u8)
-        }
+    fn chained_methods() {
+        // process(|s| s.len(), require(|s| !s.is_empty(), star(digit)))
+        let digits = primitive::digit().star().filter(|s| !s.is_empty()).map(|s| s.len());
+        assert_eq!(digits.parse(0, b"123x"), Success(3, 3));
+        assert!(digits.parse(0, b"x").is_fail());
+
+        // signed number: an optional sign, then the digits
+        let sign = primitive::byte(b'-').or(primitive::byte(b'+')).opt();
+        let signed = sign.then(number::uint()).map(|(sign, n)| if sign == Some(b'-') { -(n as i64) } else { n as i64 });
+        assert_eq!(signed.parse(0, b"-12"), Success(3, -12));
+        assert_eq!(signed.parse(0, b"+7"), Success(2, 7));
+        assert_eq!(signed.parse(0, b"7"), Success(1, 7));
+        assert_eq!(signed.parse(0, b"-x"), number::uint().map(|n| n as i64).parse(1, b"-x"));
     }
 
     #[test]
-    fn and() {
-        // just clone all parsers
-        // parsers are read-only once created, but it's not like they're expansive to clone anyway
-        // (and i'm a rust beginner)
-        let p = concat(vec![
-            readchar(),
-            readchar(),
-            readchar(),
-            readchar()
-        ]);
-
-        // parse all the characters
-        let result = p.parse(0, "test".as_bytes());
-        assert!(matches!(result, Success(4, _)));
-        if let Success(4, chars) = result {
-            assert_eq!("test", String::from_utf8(chars).unwrap());
+    fn operators() {
+        let p = primitive::digit() | primitive::alpha() | primitive::byte(b'_');
+        let q = oneof(vec![primitive::digit(), primitive::alpha(), primitive::byte(b'_')]);
+        // a flat oneof of the three
+        assert_eq!(p.alternatives().map(<[_]>::len), Some(3));
+        for source in [&b"1"[..], b"a", b"_", b"-", b""] {
+            assert_eq!(p.parse(0, source), q.parse(0, source));
         }
 
-        // not enough characters -> Fail to parse
-        let result = p.parse(0, "tes".as_bytes());
-        assert_eq!(result, Fail)
+        let sign = primitive::byte(b'-') | primitive::byte(b'+');
+        let signed = (sign | pure(b'+')) + number::uint();
+        assert_eq!(signed.parse(0, b"-12"), Success(3, (b'-', 12)));
+        assert_eq!(signed.parse(0, b"7"), Success(1, (b'+', 7)));
+        let assignment = primitive::alpha1() >> (primitive::byte(b'=') + number::uint());
+        assert_eq!(assignment.parse(0, b"x=1"), Success(3, (b"x".to_vec(), (b'=', 1))));
+        let p = (primitive::alpha() + primitive::byte(b'=')) >> primitive::digit();
+        assert_eq!(p.parse(0, b"x=1"), Success(3, ((b'x', b'='), b'1')));
     }
 
     #[test]
-    fn char() {
-        let result = readchar().parse(0, "test".as_bytes());
-        assert_eq!(result, Success(1, "t".as_bytes()[0]));
+    fn descriptions() {
+        let statement = star(oneof(vec![process(|_| "let".to_string(), tag(b"let")), text::identifier()]));
+        assert_eq!(statement.describe(), r#"Star(Or([Map(Tag("let")), Identifier]))"#);
+        let items = combinator::sep_by(number::uint(), one_of(b",;"));
+        let list = combinator::context("list", seq!(byte(b'['), items, byte(b']')));
+        assert_eq!(format!("{:?}", list), r#"Context("list", Seq(Byte('['), SepBy(Uint, OneOf(",;")), Byte(']')))"#);
+        assert_eq!(combinator::count(3, byte(b'\n')).to_string(), r#"Repeat(3..=3, Byte('\n'))"#);
+        // a recursive parser is not expanded
+        let nested = combinator::recursive(|this| {
+            let inner = combinator::delimited(byte(b'('), optional(this), byte(b')'));
+            process(|depth: Option<usize>| depth.map_or(1, |depth| depth + 1), inner)
+        });
+        assert_eq!(nested.describe(), "Lazy");
+        let group = combinator::preceded(byte(b'#'), nested);
+        assert_eq!(group.describe(), "Preceded(Byte('#'), Lazy)");
+
+        // the first alternatives of a long list
+        let letters = oneof((0..1000).map(|_| byte(b'a')).collect());
+        let shown = ["Byte('a')"; DESCRIBE_WIDTH].join(", ");
+        assert_eq!(letters.describe(), format!("Or([{}, ...992 more])", shown));
+        // deep nesting
+        let mut deep = byte(b'a');
+        for _ in 0..100 {
+            deep = combinator::cut(deep);
+        }
+        let description = deep.describe();
+        let expected = format!("{}Cut(..){}", "Cut(".repeat(DESCRIBE_DEPTH), ")".repeat(DESCRIBE_DEPTH));
+        assert_eq!(description, expected);
+        // lists of lists
+        let mut wide = oneof((0..20).map(|_| byte(b'a')).collect());
+        for _ in 0..3 {
+            wide = oneof(vec![wide; 10]);
+        }
+        assert!(wide.describe().len() <= DESCRIBE_LENGTH + 3);
+        assert!(wide.describe().ends_with("..."));
     }
 }
@@ -0,0 +1,302 @@
+use crate::{Parse, Parser, Result};
+use crate::Result::*;
+use crate::error::{Expected, ParseFailure};
+use crate::primitive::run_length;
+
+// unsigned decimal integer
+// fails (without consuming the digits) if the value doesn't fit in a u64
+struct UintParser {}
+
+impl<E: ParseFailure> Parse<u64, E> for UintParser {
+    fn create(&self) -> Parser<u64, E> {
+        Box::new(UintParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<u64, E> {
+        let len = run_length(|c| c.is_ascii_digit(), position, source);
+        if len == 0 {
+            return Fail(E::expected(position, Expected::Class("digit")))
+        }
+        let mut value: u64 = 0;
+        for &c in &source[position..position + len] {
+            match value.checked_mul(10).and_then(|v| v.checked_add((c - b'0') as u64)) {
+                Some(v) => value = v,
+                None => return Fail(E::new(position, "integer too large"))
+            }
+        }
+        Success(position + len, value)
+    }
+}
+
+pub fn uint() -> Parser<u64> {
+    generic::uint()
+}
+
+// signed decimal integer, with an optional '+' or '-' sign
+// negative numbers are accumulated downwards, so that i64::MIN doesn't overflow
+struct IntParser {}
+
+impl<E: ParseFailure> Parse<i64, E> for IntParser {
+    fn create(&self) -> Parser<i64, E> {
+        Box::new(IntParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<i64, E> {
+        let (negative, start) = match source.get(position) {
+            Some(b'-') => (true, position + 1),
+            Some(b'+') => (false, position + 1),
+            _ => (false, position)
+        };
+        let len = run_length(|c| c.is_ascii_digit(), start, source);
+        if len == 0 {
+            return Fail(E::expected(start, Expected::Class("digit")))
+        }
+        let mut value: i64 = 0;
+        for &c in &source[start..start + len] {
+            let digit = (c - b'0') as i64;
+            let next = value.checked_mul(10).and_then(|v| {
+                if negative { v.checked_sub(digit) } else { v.checked_add(digit) }
+            });
+            match next {
+                Some(v) => value = v,
+                None => return Fail(E::new(position, "integer out of range"))
+            }
+        }
+        Success(start + len, value)
+    }
+}
+
+pub fn int() -> Parser<i64> {
+    generic::int()
+}
+
+// decimal floating point number: [sign] digits [. digits] [(e|E) [sign] digits]
+// at least one digit is required before or after the '.' ("1.", ".5" and "1.5" are accepted)
+// "inf" and "NaN" are not numbers here
+// an incomplete number (".", "1e", "1e+") fails as a whole instead of matching a prefix
+struct FloatParser {}
+
+impl FloatParser {
+    // end of the recognized number, if any
+    fn recognize(position: usize, source: &[u8]) -> Option<usize> {
+        let digits = |start| run_length(|c| c.is_ascii_digit(), start, source);
+        let sign = |start| match source.get(start) {
+            Some(b'+') | Some(b'-') => start + 1,
+            _ => start
+        };
+
+        let mut cursor = sign(position);
+        let integer = digits(cursor);
+        cursor += integer;
+        let mut fraction = 0;
+        if source.get(cursor) == Some(&b'.') {
+            fraction = digits(cursor + 1);
+            cursor += 1 + fraction;
+        }
+        if integer + fraction == 0 {
+            return None
+        }
+        if let Some(b'e') | Some(b'E') = source.get(cursor) {
+            let start = sign(cursor + 1);
+            let exponent = digits(start);
+            if exponent == 0 {
+                return None
+            }
+            cursor = start + exponent;
+        }
+        Some(cursor)
+    }
+}
+
+impl<E: ParseFailure> Parse<f64, E> for FloatParser {
+    fn create(&self) -> Parser<f64, E> {
+        Box::new(FloatParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<f64, E> {
+        let end = match FloatParser::recognize(position, source) {
+            Some(end) => end,
+            None => return Fail(E::expected(position, Expected::Class("number")))
+        };
+        // the recognized text is ascii, and always valid for str::parse
+        let text = std::str::from_utf8(&source[position..end]).unwrap();
+        match text.parse() {
+            Ok(value) => Success(end, value),
+            Err(_) => Fail(E::new(position, "invalid number"))
+        }
+    }
+}
+
+pub fn float() -> Parser<f64> {
+    generic::float()
+}
+
+// integer literal, in decimal or with a radix prefix (0x or 0X, 0o, 0b)
+// digits may be separated by '_', but not right after the prefix or at the end ("0x_1", "1_")
+// the literal stops at the first byte that is not a digit of its radix: "0b102" is 0b10
+// fails on overflow, and on a prefix without digits ("0x")
+struct NumberLiteralParser {}
+
+impl<E: ParseFailure> Parse<u64, E> for NumberLiteralParser {
+    fn create(&self) -> Parser<u64, E> {
+        Box::new(NumberLiteralParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<u64, E> {
+        let (radix, start) = match source.get(position..position + 2) {
+            Some(b"0x") | Some(b"0X") => (16, position + 2),
+            Some(b"0o") => (8, position + 2),
+            Some(b"0b") => (2, position + 2),
+            _ => (10, position)
+        };
+        let rest = source.get(start..).unwrap_or_default();
+        let len = rest.iter()
+            .position(|&c| c != b'_' && !(c as char).is_digit(radix))
+            .unwrap_or(rest.len());
+        let digits = &rest[..len];
+        if digits.is_empty() || digits[0] == b'_' || digits[len - 1] == b'_' {
+            return Fail(E::expected(start, Expected::Class("digit")))
+        }
+
+        let mut value: u64 = 0;
+        for &c in digits.iter().filter(|&&c| c != b'_') {
+            let digit = (c as char).to_digit(radix).unwrap() as u64;
+            match value.checked_mul(radix as u64).and_then(|v| v.checked_add(digit)) {
+                Some(v) => value = v,
+                None => return Fail(E::new(position, "integer too large"))
+            }
+        }
+        Success(start + len, value)
+    }
+}
+
+pub fn number_literal() -> Parser<u64> {
+    generic::number_literal()
+}
+
+// the same parsers, for any error type (the ones above fail with a ParseError)
+// let value: Parser<i64, Diagnostic> = number::generic::int();
+pub mod generic {
+    use super::*;
+
+    pub fn uint<E: ParseFailure>() -> Parser<u64, E> {
+        UintParser {}.create()
+    }
+
+    pub fn int<E: ParseFailure>() -> Parser<i64, E> {
+        IntParser {}.create()
+    }
+
+    pub fn float<E: ParseFailure>() -> Parser<f64, E> {
+        FloatParser {}.create()
+    }
+
+    pub fn number_literal<E: ParseFailure>() -> Parser<u64, E> {
+        NumberLiteralParser {}.create()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinator::{concat, oneof, process};
+    use crate::primitive::{byte, digit1};
+
+    #[test]
+    fn unsigned() {
+        assert_eq!(uint().parse(0, b"0"), Success(1, 0));
+        assert_eq!(uint().parse(0, b"0042x"), Success(4, 42));
+        assert_eq!(uint().parse(0, b"18446744073709551615"), Success(20, u64::MAX));
+        assert!(uint().parse(0, b"18446744073709551616").is_fail());
+        assert!(uint().parse(0, b"x").is_fail());
+        assert!(uint().parse(0, b"").is_fail());
+
+        // an overflowing number doesn't consume anything, so it can be handled by another alternative
+        let big = process(|digits: Vec<u8>| digits.len() as u64, digit1());
+        let p = oneof(vec![uint(), big]);
+        assert_eq!(p.parse(0, b"99999999999999999999999"), Success(23, 23));
+
+        let p = concat(vec![uint(), process(|_| 0, byte(b'.')), uint()]);
+        assert_eq!(p.parse(0, b"1.5"), Success(3, vec![1, 0, 5]));
+    }
+
+    #[test]
+    fn signed() {
+        assert_eq!(int().parse(0, b"42"), Success(2, 42));
+        assert_eq!(int().parse(0, b"+42"), Success(3, 42));
+        assert_eq!(int().parse(0, b"-42"), Success(3, -42));
+        assert_eq!(int().parse(0, b"-0"), Success(2, 0));
+        assert_eq!(int().parse(0, b"-9223372036854775808"), Success(20, i64::MIN));
+        assert_eq!(int().parse(0, b"9223372036854775807"), Success(19, i64::MAX));
+
+        // overflow in both directions
+        assert!(int().parse(0, b"-9223372036854775809").is_fail());
+        assert!(int().parse(0, b"9223372036854775808").is_fail());
+
+        // a sign without digits is not consumed
+        assert!(int().parse(0, b"-").is_fail());
+        assert!(int().parse(0, b"+x").is_fail());
+        let p = oneof(vec![int(), process(|_| 0, byte(b'-'))]);
+        assert_eq!(p.parse(0, b"-x"), Success(1, 0));
+    }
+
+    #[test]
+    fn floating() {
+        assert_eq!(float().parse(0, b"1"), Success(1, 1.0));
+        assert_eq!(float().parse(0, b"-0.5"), Success(4, -0.5));
+        assert_eq!(float().parse(0, b"3e-10"), Success(5, 3e-10));
+        assert_eq!(float().parse(0, b"2.5E+3"), Success(6, 2500.0));
+        assert_eq!(float().parse(0, b"1."), Success(2, 1.0));
+        assert_eq!(float().parse(0, b".5"), Success(2, 0.5));
+
+        // subnormals
+        assert_eq!(float().parse(0, b"5e-324"), Success(6, f64::from_bits(1)));
+        assert_eq!(float().parse(0, b"1e-310"), Success(6, 1e-310));
+
+        // not numbers
+        assert!(float().parse(0, b".").is_fail());
+        assert!(float().parse(0, b"-").is_fail());
+        assert!(float().parse(0, b"inf").is_fail());
+        assert!(float().parse(0, b"NaN").is_fail());
+
+        // an incomplete exponent is not consumed, so that an integer can still match
+        assert!(float().parse(0, b"1e").is_fail());
+        assert!(float().parse(0, b"1e+").is_fail());
+        let p = oneof(vec![float(), process(|i| i as f64, int())]);
+        assert_eq!(p.parse(0, b"12e"), Success(2, 12.0));
+
+        // letters after a complete number are left alone
+        assert_eq!(float().parse(0, b"1.5kg"), Success(3, 1.5));
+        assert_eq!(float().parse(0, b"1e3m"), Success(3, 1000.0));
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(number_literal().parse(0, b"0xDEAD_BEEF"), Success(11, 0xdead_beef));
+        assert_eq!(number_literal().parse(0, b"0Xff"), Success(4, 255));
+        assert_eq!(number_literal().parse(0, b"0o755"), Success(5, 0o755));
+        assert_eq!(number_literal().parse(0, b"0b1010"), Success(6, 10));
+        assert_eq!(number_literal().parse(0, b"1_000_000"), Success(9, 1_000_000));
+        assert_eq!(number_literal().parse(0, b"0"), Success(1, 0));
+        assert_eq!(number_literal().parse(0, b"0xffffffffffffffff"), Success(18, u64::MAX));
+
+        // stops at the first invalid digit
+        assert_eq!(number_literal().parse(0, b"0b102"), Success(4, 2));
+        assert_eq!(number_literal().parse(0, b"0o78"), Success(3, 7));
+
+        // no digits, misplaced separators, overflow
+        assert!(number_literal().parse(0, b"0x").is_fail());
+        assert!(number_literal().parse(0, b"0o").is_fail());
+        assert!(number_literal().parse(0, b"0x_1").is_fail());
+        assert!(number_literal().parse(0, b"1_").is_fail());
+        assert!(number_literal().parse(0, b"_1").is_fail());
+        assert!(number_literal().parse(0, b"0x1_0000_0000_0000_0000").is_fail());
+
+        // mapped into a user-defined node
+        #[derive(Debug, PartialEq, Eq)]
+        struct Literal(u64);
+        let p = process(Literal, number_literal());
+        assert_eq!(p.parse(0, b"0x10"), Success(4, Literal(16)));
+    }
+}
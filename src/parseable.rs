@@ -0,0 +1,150 @@
+use std::str::FromStr;
+use crate::{number, text, Parse, Parser};
+use crate::combinator::map_res;
+use crate::error::ParseError;
+
+// a type that knows how to parse itself: declare its parser once, then parse it anywhere
+// (in a grammar with T::parser(), from bytes with parse_value(), from a &str with "...".parse::<Parsed<T>>())
+pub trait Parseable: Sized + 'static {
+    fn parser() -> Parser<Self>;
+}
+
+// the whole input has to be consumed
+pub fn parse_value<T: Parseable>(input: &[u8]) -> std::result::Result<T, ParseError> {
+    T::parser().run_all(input)
+}
+
+// FromStr for any Parseable type (the orphan rule keeps FromStr from being implemented for T itself)
+// let Parsed(port) = "8080".parse::<Parsed<u16>>()?;
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Parsed<T>(pub T);
+
+impl<T: Parseable> FromStr for Parsed<T> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        parse_value(s.as_bytes()).map(Parsed)
+    }
+}
+
+// the std types, with the syntax of the number and text parsers:
+// - integers in decimal, with a sign for the signed ones ("42", "-7", "+7"), and in the range of the type
+// - f64 as number::float() ("1.5", "-2e3", ".5")
+// - bool as text::boolean() ("true" or "false")
+// - String as a double-quoted string with escapes, as text::quoted_string() ("\"a\\tb\"")
+macro_rules! impl_parseable_uint {
+    ($($t:ty)*) => {$(
+        impl Parseable for $t {
+            fn parser() -> Parser<$t> {
+                map_res(<$t>::try_from, number::uint())
+            }
+        }
+    )*}
+}
+
+macro_rules! impl_parseable_int {
+    ($($t:ty)*) => {$(
+        impl Parseable for $t {
+            fn parser() -> Parser<$t> {
+                map_res(<$t>::try_from, number::int())
+            }
+        }
+    )*}
+}
+
+impl_parseable_uint!(u8 u16 u32 usize);
+impl_parseable_int!(i8 i16 i32 isize);
+
+impl Parseable for u64 {
+    fn parser() -> Parser<u64> {
+        number::uint()
+    }
+}
+
+impl Parseable for i64 {
+    fn parser() -> Parser<i64> {
+        number::int()
+    }
+}
+
+impl Parseable for f64 {
+    fn parser() -> Parser<f64> {
+        number::float()
+    }
+}
+
+impl Parseable for bool {
+    fn parser() -> Parser<bool> {
+        text::boolean()
+    }
+}
+
+impl Parseable for String {
+    fn parser() -> Parser<String> {
+        text::quoted_string()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Result::*;
+    use crate::combinator::{pair, preceded, process};
+    use crate::error::Expected;
+    use crate::primitive;
+
+    // name=value
+    #[derive(Eq, PartialEq, Debug)]
+    struct Setting {
+        name: String,
+        value: u32
+    }
+
+    impl Parseable for Setting {
+        fn parser() -> Parser<Setting> {
+            process(
+                |(name, value)| Setting { name, value },
+                pair(String::parser(), preceded(primitive::byte(b'='), u32::parser()))
+            )
+        }
+    }
+
+    #[test]
+    fn std_types() {
+        assert_eq!(parse_value::<u8>(b"255"), Ok(255));
+        let error = ParseError::new(0, "out of range integral type conversion attempted");
+        assert_eq!(parse_value::<u8>(b"256"), Err(error));
+        assert_eq!(parse_value::<i8>(b"-128"), Ok(-128));
+        assert_eq!(parse_value::<u64>(b"18446744073709551615"), Ok(u64::MAX));
+        assert_eq!(parse_value::<i64>(b"+7"), Ok(7));
+        assert_eq!(parse_value::<f64>(b"-2.5e1"), Ok(-25.0));
+        assert_eq!(parse_value::<bool>(b"false"), Ok(false));
+        assert_eq!(parse_value::<String>(br#""a\tb""#), Ok("a\tb".to_string()));
+        // no sign for unsigned types
+        assert!(parse_value::<u32>(b"-1").is_err());
+    }
+
+    #[test]
+    fn user_types() {
+        let setting = Setting { name: "retries".to_string(), value: 3 };
+        assert_eq!(parse_value::<Setting>(br#""retries"=3"#), Ok(setting));
+        assert_eq!(
+            Setting::parser().parse(0, br#""retries"=x"#),
+            Fail(ParseError::expected(10, Expected::Class("digit")))
+        );
+        // in a grammar, like any other parser
+        let settings = crate::combinator::sep_by(Setting::parser(), primitive::byte(b','));
+        let Success(_, values) = settings.parse(0, br#""a"=1,"b"=2"#) else { panic!("no match") };
+        assert_eq!(values.len(), 2);
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!("123".parse::<Parsed<u32>>(), Ok(Parsed(123)));
+        assert_eq!("123abc".parse::<Parsed<u32>>(), Err(ParseError::expected(3, Expected::EndOfInput)));
+        assert_eq!("true".parse::<Parsed<bool>>().map(|Parsed(b)| b), Ok(true));
+        let error = "\"x\"=".parse::<Parsed<Setting>>().unwrap_err();
+        assert_eq!(error.to_string(), "expected digit at offset 4");
+    }
+}
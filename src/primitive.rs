@@ -0,0 +1,1469 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Bound, Range, RangeBounds, RangeInclusive};
+use std::sync::Arc;
+use crate::{Parse, Parser, Result};
+use crate::combinator::process;
+use crate::Result::*;
+use crate::error::{ErrorKind, Expected, ParseFailure};
+
+// base parsers
+
+struct CharParser {}
+
+
+impl<E: ParseFailure> Parse<u8, E> for CharParser {
+    fn create(&self) -> Parser<u8, E> {
+        Box::new(CharParser{})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<u8, E> {
+        if position < source.len() {
+            Success(position + 1, source[position])
+        } else {
+            Fail(E::new(position, "unexpected end of input").with_kind(ErrorKind::UnexpectedEof))
+        }
+    }
+}
+
+pub fn readchar() -> Parser<u8> {
+    generic::readchar()
+}
+
+// always succeed with a copy of the value, without consuming anything
+// (over any item type: opt_or() and cond() use it in the parsers over tokens too)
+pub(crate) struct PureParser<T> {
+    pub(crate) value: T
+}
+
+impl<T: Clone + Send + Sync + 'static, E: ParseFailure, I> Parse<T, E, I> for PureParser<T> {
+    fn create(&self) -> Parser<T, E, I> {
+        Box::new(PureParser { value: self.value.clone() })
+    }
+
+    fn parse(&self, position: usize, _source: &[I]) -> Result<T, E> {
+        Success(position, self.value.clone())
+    }
+}
+
+pub fn pure<T: Clone + Send + Sync + 'static>(value: T) -> Parser<T> {
+    generic::pure(value)
+}
+
+// always fail
+// (fn() -> T keeps the struct Send + Sync whatever T is)
+struct FailParser<T> {
+    phantom: PhantomData<fn() -> T>
+}
+
+impl<T: 'static, E: ParseFailure> Parse<T, E> for FailParser<T> {
+    fn create(&self) -> Parser<T, E> {
+        Box::new(FailParser { phantom: PhantomData })
+    }
+
+    fn parse(&self, position: usize, _source: &[u8]) -> Result<T, E> {
+        Fail(E::new(position, "fail() never matches"))
+    }
+}
+
+pub fn fail<T: 'static>() -> Parser<T> {
+    generic::fail()
+}
+
+// a parser written as a function of the position and the source
+// the function returns Success(end, value) with end between position and the end of the source,
+// and must not consume anything on Fail: the caller goes on from the start position
+// (the position of the failure only tells where the input was rejected)
+// (the function is shared between the copies of the parser)
+type ParseFn<T, E> = dyn Fn(usize, &[u8]) -> Result<T, E> + Send + Sync;
+
+struct FromFnParser<T, E> {
+    f: Arc<ParseFn<T, E>>
+}
+
+impl<T: 'static, E: ParseFailure> Parse<T, E> for FromFnParser<T, E> {
+    fn create(&self) -> Parser<T, E> {
+        Box::new(FromFnParser { f: self.f.clone() })
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<T, E> {
+        let result = (self.f)(position, source);
+        if let Success(end, _) = result {
+            debug_assert!(
+                position <= end && end <= source.len(),
+                "from_fn() parser succeeded at {} from {} (source of {} bytes)", end, position, source.len()
+            );
+        }
+        result
+    }
+}
+
+pub fn from_fn<T: 'static, E: ParseFailure>(
+    f: impl Fn(usize, &[u8]) -> Result<T, E> + Send + Sync + 'static
+) -> Parser<T, E> {
+    FromFnParser { f: Arc::new(f) }.create()
+}
+
+
+// match one specific byte
+struct ByteParser {
+    byte: u8
+}
+
+impl<E: ParseFailure> Parse<u8, E> for ByteParser {
+    fn create(&self) -> Parser<u8, E> {
+        Box::new(ByteParser { byte: self.byte })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        format!("Byte('{}')", self.byte.escape_ascii())
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<u8, E> {
+        match source.get(position) {
+            Some(&c) if c == self.byte => Success(position + 1, c),
+            _ => Fail(E::expected(position, Expected::Byte(self.byte)))
+        }
+    }
+}
+
+pub fn byte(c: u8) -> Parser<u8> {
+    generic::byte(c)
+}
+
+// match one byte accepted by the predicate
+// (same as require(f, readchar()), without the extra boxed parser)
+// the label names the accepted bytes in failures ("digit")
+struct SatisfyParser {
+    predicate: fn(u8) -> bool,
+    label: Option<&'static str>
+}
+
+impl<E: ParseFailure> Parse<u8, E> for SatisfyParser {
+    fn create(&self) -> Parser<u8, E> {
+        Box::new(SatisfyParser { predicate: self.predicate, label: self.label })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        match self.label {
+            Some(label) => format!("Satisfy({:?})", label),
+            None => "Satisfy".to_string()
+        }
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<u8, E> {
+        match source.get(position) {
+            Some(&c) if (self.predicate)(c) => Success(position + 1, c),
+            _ => Fail(expected_or_unexpected(self.label, position, source))
+        }
+    }
+}
+
+pub fn satisfy(f: fn(u8) -> bool) -> Parser<u8> {
+    generic::satisfy(f)
+}
+
+pub fn satisfy_labeled(label: &'static str, f: fn(u8) -> bool) -> Parser<u8> {
+    generic::satisfy_labeled(label, f)
+}
+
+// failure of an optionally labeled parser
+fn expected_or_unexpected<E: ParseFailure>(label: Option<&'static str>, position: usize, source: &[u8]) -> E {
+    match label {
+        Some(label) => E::expected(position, Expected::Class(label)),
+        None => E::unexpected(position, source)
+    }
+}
+
+// match one byte in a range (b'a'..=b'z', b'0'..b'8')
+// (an empty range never matches)
+struct ByteRangeParser {
+    range: RangeInclusive<u8>
+}
+
+impl<E: ParseFailure> Parse<u8, E> for ByteRangeParser {
+    fn create(&self) -> Parser<u8, E> {
+        Box::new(ByteRangeParser { range: self.range.clone() })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        format!("ByteRange('{}'..='{}')", self.range.start().escape_ascii(), self.range.end().escape_ascii())
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<u8, E> {
+        match source.get(position) {
+            Some(c) if self.range.contains(c) => Success(position + 1, *c),
+            _ => Fail(E::unexpected(position, source))
+        }
+    }
+}
+
+pub fn byte_range(range: impl RangeBounds<u8>) -> Parser<u8> {
+    generic::byte_range(range)
+}
+
+// a set of bytes, that can be combined, negated and printed
+// CharClass::range(b'a', b'z').union(CharClass::of(b"_-")) is [a-z_-]
+// (a 256-bit bitmap: the lookup and the set operations don't depend on the size of the classes)
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub struct CharClass {
+    bitmap: [u64; 4]
+}
+
+impl CharClass {
+    pub fn empty() -> CharClass {
+        CharClass { bitmap: [0; 4] }
+    }
+
+    pub fn any() -> CharClass {
+        CharClass { bitmap: [u64::MAX; 4] }
+    }
+
+    // the bytes from first to last (both included)
+    pub fn range(first: u8, last: u8) -> CharClass {
+        let mut class = CharClass::empty();
+        for c in first..=last {
+            class.insert(c);
+        }
+        class
+    }
+
+    // the bytes of a literal
+    pub fn of(set: &[u8]) -> CharClass {
+        let mut class = CharClass::empty();
+        for &c in set {
+            class.insert(c);
+        }
+        class
+    }
+
+    pub fn union(self, other: CharClass) -> CharClass {
+        CharClass { bitmap: std::array::from_fn(|i| self.bitmap[i] | other.bitmap[i]) }
+    }
+
+    pub fn intersection(self, other: CharClass) -> CharClass {
+        CharClass { bitmap: std::array::from_fn(|i| self.bitmap[i] & other.bitmap[i]) }
+    }
+
+    pub fn difference(self, other: CharClass) -> CharClass {
+        self.intersection(other.negate())
+    }
+
+    pub fn negate(self) -> CharClass {
+        CharClass { bitmap: self.bitmap.map(|bits| !bits) }
+    }
+
+    pub fn contains(&self, c: u8) -> bool {
+        self.bitmap[(c / 64) as usize] & (1 << (c % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.bitmap.iter().map(|bits| bits.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bitmap == [0; 4]
+    }
+
+    // the bytes of the class, in increasing order
+    pub fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=255).filter(|&c| self.contains(c))
+    }
+
+    fn insert(&mut self, c: u8) {
+        self.bitmap[(c / 64) as usize] |= 1 << (c % 64);
+    }
+}
+
+// compact, like a regex class: the runs of 3 bytes or more as ranges, then the other bytes
+// ('-' is last, so that it can't be read as a range, and ']', '\' and '^' are escaped with a backslash)
+// a class of more than half the bytes is shown negated ([^"\\])
+impl fmt::Display for CharClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.len() > 128 {
+            return write!(f, "[^{}]", self.negate().members())
+        }
+        write!(f, "[{}]", self.members())
+    }
+}
+
+impl fmt::Debug for CharClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CharClass({})", self)
+    }
+}
+
+impl CharClass {
+    // the inside of the brackets of Display
+    fn members(&self) -> String {
+        let escape = |c: u8| match c {
+            b']' | b'\\' | b'^' => format!("\\{}", c as char),
+            b'"' | b'\'' => (c as char).to_string(),
+            _ => c.escape_ascii().to_string()
+        };
+        let mut ranges = String::new();
+        let mut singles = Vec::new();
+        let mut c = 0;
+        while c <= 255 {
+            if !self.contains(c as u8) {
+                c += 1;
+                continue
+            }
+            let start = c;
+            while c < 255 && self.contains(c as u8 + 1) {
+                c += 1;
+            }
+            if c - start >= 2 {
+                ranges.push_str(&format!("{}-{}", escape(start as u8), escape(c as u8)));
+            } else {
+                singles.extend(start as u8..=c as u8);
+            }
+            c += 1;
+        }
+        // '-' last
+        singles.sort_by_key(|&c| c == b'-');
+        ranges + &singles.into_iter().map(escape).collect::<String>()
+    }
+}
+
+// any byte of a class
+struct ClassParser {
+    class: CharClass
+}
+
+impl<E: ParseFailure> Parse<u8, E> for ClassParser {
+    fn create(&self) -> Parser<u8, E> {
+        Box::new(ClassParser { class: self.class })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        format!("Class({})", self.class)
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<u8, E> {
+        match source.get(position) {
+            Some(&c) if self.class.contains(c) => Success(position + 1, c),
+            _ => Fail(E::expected(position, Expected::Set(self.class)))
+        }
+    }
+}
+
+pub fn class(class: CharClass) -> Parser<u8> {
+    generic::class(class)
+}
+
+// match one byte out of a set
+// the set is stored as a 256-bit bitmap, so the lookup does not depend on its size
+// (a negated set is too large to be listed in the expected bytes of a failure)
+struct ByteSetParser {
+    set: CharClass,
+    negated: bool
+}
+
+impl<E: ParseFailure> Parse<u8, E> for ByteSetParser {
+    fn create(&self) -> Parser<u8, E> {
+        Box::new(ByteSetParser { set: self.set, negated: self.negated })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        // the set as it was given (a negated bitmap holds the other bytes)
+        let set = if self.negated { self.set.negate() } else { self.set };
+        let set: Vec<u8> = set.bytes().collect();
+        format!("{}(\"{}\")", if self.negated { "NoneOf" } else { "OneOf" }, set.escape_ascii())
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<u8, E> {
+        match source.get(position) {
+            Some(&c) if self.set.contains(c) => Success(position + 1, c),
+            _ if self.negated => Fail(E::unexpected(position, source)),
+            // one expectation per byte of the set (merged at the same position)
+            _ => Fail(self.set.bytes()
+                .map(|c| E::expected(position, Expected::Byte(c)))
+                .reduce(E::merge)
+                .unwrap_or_else(|| E::new(position, "")))
+        }
+    }
+}
+
+pub fn one_of(set: &'static [u8]) -> Parser<u8> {
+    generic::one_of(set)
+}
+
+pub fn none_of(set: &'static [u8]) -> Parser<u8> {
+    generic::none_of(set)
+}
+
+// match a literal sequence of bytes
+// with no_case, ascii letters are compared case-insensitively (other bytes must be equal)
+struct TagParser {
+    tag: &'static [u8],
+    no_case: bool
+}
+
+impl<E: ParseFailure> Parse<Vec<u8>, E> for TagParser {
+    fn create(&self) -> Parser<Vec<u8>, E> {
+        Box::new(TagParser { tag: self.tag, no_case: self.no_case })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        format!("{}(\"{}\")", if self.no_case { "TagNoCase" } else { "Tag" }, self.tag.escape_ascii())
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<u8>, E> {
+        // the range check also handles the tag running past the end of the source
+        let end = position + self.tag.len();
+        match source.get(position..end) {
+            Some(bytes) if bytes == self.tag => Success(end, bytes.to_vec()),
+            Some(bytes) if self.no_case && bytes.eq_ignore_ascii_case(self.tag) => {
+                // keep the bytes as they appear in the source
+                Success(end, bytes.to_vec())
+            }
+            _ => Fail(E::expected(position, Expected::Tag(self.tag)))
+        }
+    }
+}
+
+pub fn tag(expected: &'static [u8]) -> Parser<Vec<u8>> {
+    generic::tag(expected)
+}
+
+pub fn tag_no_case(expected: &'static [u8]) -> Parser<Vec<u8>> {
+    generic::tag_no_case(expected)
+}
+
+pub fn tag_str(expected: &'static str) -> Parser<Vec<u8>> {
+    generic::tag_str(expected)
+}
+
+
+
+// utf-8
+
+// decode the utf-8 scalar value starting at position, along with its length in bytes
+// invalid, overlong and truncated sequences are rejected
+pub(crate) fn decode_utf8(position: usize, source: &[u8]) -> Option<(char, usize)> {
+    let width = match *source.get(position)? {
+        0x00..=0x7f => 1,
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        // continuation bytes, overlong 2-byte leads, out of range leads
+        _ => return None
+    };
+    let bytes = source.get(position..position + width)?;
+    let c = std::str::from_utf8(bytes).ok()?.chars().next()?;
+    Some((c, width))
+}
+
+// one utf-8 encoded char, accepted by the predicate
+struct Utf8CharParser {
+    predicate: fn(char) -> bool
+}
+
+impl<E: ParseFailure> Parse<char, E> for Utf8CharParser {
+    fn create(&self) -> Parser<char, E> {
+        Box::new(Utf8CharParser { predicate: self.predicate })
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<char, E> {
+        match decode_utf8(position, source) {
+            Some((c, width)) if (self.predicate)(c) => Success(position + width, c),
+            Some(_) => Fail(E::unexpected(position, source)),
+            None if position < source.len() => Fail(E::new(position, "invalid utf-8")),
+            None => Fail(E::unexpected(position, source))
+        }
+    }
+}
+
+pub fn utf8_char() -> Parser<char> {
+    generic::utf8_char()
+}
+
+pub fn utf8_satisfy(f: fn(char) -> bool) -> Parser<char> {
+    generic::utf8_satisfy(f)
+}
+
+
+// ascii character classes
+
+pub fn digit() -> Parser<u8> {
+    generic::digit()
+}
+
+pub fn alpha() -> Parser<u8> {
+    generic::alpha()
+}
+
+pub fn alphanumeric() -> Parser<u8> {
+    generic::alphanumeric()
+}
+
+pub fn hex_digit() -> Parser<u8> {
+    generic::hex_digit()
+}
+
+// length of the run of bytes accepted by the predicate, starting at position
+pub(crate) fn run_length(predicate: fn(u8) -> bool, position: usize, source: &[u8]) -> usize {
+    let rest = source.get(position..).unwrap_or_default();
+    rest.iter().position(|&c| !predicate(c)).unwrap_or(rest.len())
+}
+
+// match the longest run of bytes accepted by the predicate
+// fails if the run is shorter than min
+struct RunParser {
+    predicate: fn(u8) -> bool,
+    min: usize,
+    label: Option<&'static str>
+}
+
+impl<E: ParseFailure> Parse<Vec<u8>, E> for RunParser {
+    fn create(&self) -> Parser<Vec<u8>, E> {
+        Box::new(RunParser { predicate: self.predicate, min: self.min, label: self.label })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        match self.label {
+            Some(label) => format!("Run({:?}, {}..)", label, self.min),
+            None => format!("Run({}..)", self.min)
+        }
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<u8>, E> {
+        let len = run_length(self.predicate, position, source);
+        if len < self.min {
+            Fail(expected_or_unexpected(self.label, position + len, source))
+        } else {
+            Success(position + len, source[position..position + len].to_vec())
+        }
+    }
+}
+
+pub fn take_while(predicate: fn(u8) -> bool) -> Parser<Vec<u8>> {
+    generic::take_while(predicate)
+}
+
+pub fn take_while1(predicate: fn(u8) -> bool) -> Parser<Vec<u8>> {
+    generic::take_while1(predicate)
+}
+
+pub fn digit1() -> Parser<Vec<u8>> {
+    generic::digit1()
+}
+
+pub fn alpha1() -> Parser<Vec<u8>> {
+    generic::alpha1()
+}
+
+// same as RunParser, but the matched bytes are thrown away instead of copied
+struct SkipRunParser {
+    predicate: fn(u8) -> bool,
+    min: usize,
+    label: &'static str
+}
+
+impl<E: ParseFailure> Parse<(), E> for SkipRunParser {
+    fn create(&self) -> Parser<(), E> {
+        Box::new(SkipRunParser { predicate: self.predicate, min: self.min, label: self.label })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        format!("SkipRun({:?}, {}..)", self.label, self.min)
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<(), E> {
+        let len = run_length(self.predicate, position, source);
+        if len < self.min {
+            Fail(E::expected(position + len, Expected::Class(self.label)))
+        } else {
+            Success(position + len, ())
+        }
+    }
+}
+
+
+// whitespace
+// space: ' ' and '\t', multispace: also '\n' and '\r'
+
+fn is_space(c: u8) -> bool {
+    c == b' ' || c == b'\t'
+}
+
+fn is_multispace(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+pub fn space0() -> Parser<()> {
+    generic::space0()
+}
+
+pub fn space1() -> Parser<()> {
+    generic::space1()
+}
+
+pub fn multispace0() -> Parser<()> {
+    generic::multispace0()
+}
+
+pub fn multispace1() -> Parser<()> {
+    generic::multispace1()
+}
+
+
+// line endings
+
+// "\n" or "\r\n"
+struct LineEndingParser {}
+
+impl<E: ParseFailure> Parse<(), E> for LineEndingParser {
+    fn create(&self) -> Parser<(), E> {
+        Box::new(LineEndingParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<(), E> {
+        match source.get(position..) {
+            Some([b'\n', ..]) => Success(position + 1, ()),
+            Some([b'\r', b'\n', ..]) => Success(position + 2, ()),
+            _ => Fail(E::expected(position, Expected::Class("line ending")))
+        }
+    }
+}
+
+pub fn line_ending() -> Parser<()> {
+    generic::line_ending()
+}
+
+// the content of the current line, up to (and not including) the line ending or the end of input
+// a lone '\r' is not a line ending: it makes the parser fail instead of being part of the line
+struct NotLineEndingParser {}
+
+impl<E: ParseFailure> Parse<Vec<u8>, E> for NotLineEndingParser {
+    fn create(&self) -> Parser<Vec<u8>, E> {
+        Box::new(NotLineEndingParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<u8>, E> {
+        let rest = source.get(position..).unwrap_or_default();
+        let len = rest.iter().position(|&c| c == b'\n' || c == b'\r').unwrap_or(rest.len());
+        match rest.get(len..) {
+            Some([b'\r', b'\n', ..]) | Some([b'\n', ..]) | Some([]) => {
+                Success(position + len, rest[..len].to_vec())
+            }
+            _ => Fail(E::new(position + len, "'\\r' without '\\n'"))
+        }
+    }
+}
+
+pub fn not_line_ending() -> Parser<Vec<u8>> {
+    generic::not_line_ending()
+}
+
+// a whole line: its content followed by a line ending, or by the end of input
+// (the end of input is only accepted after a non-empty line, so star(line()) terminates)
+struct LineParser {}
+
+impl<E: ParseFailure> Parse<Vec<u8>, E> for LineParser {
+    fn create(&self) -> Parser<Vec<u8>, E> {
+        Box::new(LineParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<u8>, E> {
+        if position >= source.len() {
+            return Fail(E::unexpected(position, source))
+        }
+        match (NotLineEndingParser {}).parse(position, source) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(end, content) => match (LineEndingParser {}).parse(end, source) {
+                Success(end, ()) => Success(end, content),
+                Fail(_) => Success(end, content),
+                Error(error) => Error(error)
+            }
+        }
+    }
+}
+
+pub fn line() -> Parser<Vec<u8>> {
+    generic::line()
+}
+
+
+// exactly n bytes, whatever they are
+struct TakeParser {
+    n: usize
+}
+
+impl<E: ParseFailure> Parse<Vec<u8>, E> for TakeParser {
+    fn create(&self) -> Parser<Vec<u8>, E> {
+        Box::new(TakeParser { n: self.n })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        format!("Take({})", self.n)
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<u8>, E> {
+        match source.get(position..position.saturating_add(self.n)) {
+            Some(bytes) => Success(position + self.n, bytes.to_vec()),
+            None => Fail(E::unexpected(source.len(), source))
+        }
+    }
+}
+
+pub fn take(n: usize) -> Parser<Vec<u8>> {
+    generic::take(n)
+}
+
+// everything up to the first occurrence of the delimiter
+// fails if the delimiter is not found; consume also matches the delimiter itself
+struct TakeUntilParser {
+    delimiter: &'static [u8],
+    consume: bool
+}
+
+impl<E: ParseFailure> Parse<Vec<u8>, E> for TakeUntilParser {
+    fn create(&self) -> Parser<Vec<u8>, E> {
+        Box::new(TakeUntilParser { delimiter: self.delimiter, consume: self.consume })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        format!("TakeUntil(\"{}\")", self.delimiter.escape_ascii())
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<u8>, E> {
+        let rest = source.get(position..).unwrap_or_default();
+        let found = (0..=rest.len()).find(|&i| rest[i..].starts_with(self.delimiter));
+        match found {
+            Some(len) => {
+                let end = if self.consume { len + self.delimiter.len() } else { len };
+                Success(position + end, rest[..len].to_vec())
+            }
+            None => Fail(E::custom(position, format!("\"{}\" not found", self.delimiter.escape_ascii())))
+        }
+    }
+}
+
+pub fn take_until(delimiter: &'static [u8]) -> Parser<Vec<u8>> {
+    generic::take_until(delimiter)
+}
+
+pub fn take_until_and_consume(delimiter: &'static [u8]) -> Parser<Vec<u8>> {
+    generic::take_until_and_consume(delimiter)
+}
+
+// everything left in the input (always succeeds)
+struct RestParser {}
+
+impl<E: ParseFailure> Parse<Vec<u8>, E> for RestParser {
+    fn create(&self) -> Parser<Vec<u8>, E> {
+        Box::new(RestParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<u8>, E> {
+        let rest = source.get(position..).unwrap_or_default();
+        Success(position + rest.len(), rest.to_vec())
+    }
+}
+
+pub fn rest() -> Parser<Vec<u8>> {
+    generic::rest()
+}
+
+// same as rest(), but only the number of bytes is returned
+struct RestLenParser {}
+
+impl<E: ParseFailure> Parse<usize, E> for RestLenParser {
+    fn create(&self) -> Parser<usize, E> {
+        Box::new(RestLenParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<usize, E> {
+        let len = source.len().saturating_sub(position);
+        Success(position + len, len)
+    }
+}
+
+pub fn rest_len() -> Parser<usize> {
+    generic::rest_len()
+}
+
+// succeed (without consuming anything) only at the end of the input
+struct EofParser {}
+
+impl<E: ParseFailure> Parse<(), E> for EofParser {
+    fn create(&self) -> Parser<(), E> {
+        Box::new(EofParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<(), E> {
+        if position == source.len() {
+            Success(position, ())
+        } else {
+            Fail(E::expected(position, Expected::EndOfInput))
+        }
+    }
+}
+
+pub fn eof() -> Parser<()> {
+    generic::eof()
+}
+
+// literals as parsers, for the constructors taking impl Into<Parser<T>> (see alt!() and cat!())
+// "let" and b"let" are tags, b';' is a byte, b'a'..=b'z' a byte range, a CharClass any of its bytes
+// (bytes and ranges can also be read into Vec<u8>, to be mixed with tags)
+impl<E: ParseFailure> From<&'static str> for Parser<Vec<u8>, E> {
+    fn from(expected: &'static str) -> Self {
+        generic::tag_str(expected)
+    }
+}
+
+impl<const N: usize, E: ParseFailure> From<&'static [u8; N]> for Parser<Vec<u8>, E> {
+    fn from(expected: &'static [u8; N]) -> Self {
+        generic::tag(expected)
+    }
+}
+
+impl<E: ParseFailure> From<u8> for Parser<u8, E> {
+    fn from(c: u8) -> Self {
+        generic::byte(c)
+    }
+}
+
+impl<E: ParseFailure> From<u8> for Parser<Vec<u8>, E> {
+    fn from(c: u8) -> Self {
+        process(|c| vec![c], generic::byte(c))
+    }
+}
+
+impl<E: ParseFailure> From<Range<u8>> for Parser<u8, E> {
+    fn from(range: Range<u8>) -> Self {
+        generic::byte_range(range)
+    }
+}
+
+impl<E: ParseFailure> From<Range<u8>> for Parser<Vec<u8>, E> {
+    fn from(range: Range<u8>) -> Self {
+        process(|c| vec![c], generic::byte_range(range))
+    }
+}
+
+impl<E: ParseFailure> From<RangeInclusive<u8>> for Parser<u8, E> {
+    fn from(range: RangeInclusive<u8>) -> Self {
+        generic::byte_range(range)
+    }
+}
+
+impl<E: ParseFailure> From<RangeInclusive<u8>> for Parser<Vec<u8>, E> {
+    fn from(range: RangeInclusive<u8>) -> Self {
+        process(|c| vec![c], generic::byte_range(range))
+    }
+}
+
+impl<E: ParseFailure> From<CharClass> for Parser<u8, E> {
+    fn from(set: CharClass) -> Self {
+        generic::class(set)
+    }
+}
+
+impl<E: ParseFailure> From<CharClass> for Parser<Vec<u8>, E> {
+    fn from(set: CharClass) -> Self {
+        process(|c| vec![c], generic::class(set))
+    }
+}
+
+// the same parsers, for any error type (the ones above fail with a ParseError)
+// let digit: Parser<u8, Diagnostic> = primitive::generic::digit();
+pub mod generic {
+    use super::*;
+
+    pub fn readchar<E: ParseFailure>() -> Parser<u8, E> {
+        CharParser{}.create()
+    }
+
+    pub fn pure<T: Clone + Send + Sync + 'static, E: ParseFailure>(value: T) -> Parser<T, E> {
+        PureParser { value }.create()
+    }
+
+    pub fn fail<T: 'static, E: ParseFailure>() -> Parser<T, E> {
+        FailParser { phantom: PhantomData }.create()
+    }
+
+    pub fn byte<E: ParseFailure>(c: u8) -> Parser<u8, E> {
+        ByteParser { byte: c }.create()
+    }
+
+    pub fn satisfy<E: ParseFailure>(f: fn(u8) -> bool) -> Parser<u8, E> {
+        SatisfyParser { predicate: f, label: None }.create()
+    }
+
+    pub fn satisfy_labeled<E: ParseFailure>(label: &'static str, f: fn(u8) -> bool) -> Parser<u8, E> {
+        SatisfyParser { predicate: f, label: Some(label) }.create()
+    }
+
+    pub fn byte_range<E: ParseFailure>(range: impl RangeBounds<u8>) -> Parser<u8, E> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => Some(start),
+            Bound::Excluded(&start) => start.checked_add(1),
+            Bound::Unbounded => Some(u8::MIN)
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => Some(end),
+            Bound::Excluded(&end) => end.checked_sub(1),
+            Bound::Unbounded => Some(u8::MAX)
+        };
+        let range = match (start, end) {
+            (Some(start), Some(end)) => start..=end,
+            #[allow(clippy::reversed_empty_ranges)]
+            _ => 1..=0
+        };
+        ByteRangeParser { range }.create()
+    }
+
+    pub fn class<E: ParseFailure>(class: CharClass) -> Parser<u8, E> {
+        ClassParser { class }.create()
+    }
+
+    pub fn one_of<E: ParseFailure>(set: &'static [u8]) -> Parser<u8, E> {
+        ByteSetParser { set: CharClass::of(set), negated: false }.create()
+    }
+
+    pub fn none_of<E: ParseFailure>(set: &'static [u8]) -> Parser<u8, E> {
+        ByteSetParser { set: CharClass::of(set).negate(), negated: true }.create()
+    }
+
+    pub fn tag<E: ParseFailure>(expected: &'static [u8]) -> Parser<Vec<u8>, E> {
+        TagParser { tag: expected, no_case: false }.create()
+    }
+
+    pub fn tag_no_case<E: ParseFailure>(expected: &'static [u8]) -> Parser<Vec<u8>, E> {
+        TagParser { tag: expected, no_case: true }.create()
+    }
+
+    pub fn tag_str<E: ParseFailure>(expected: &'static str) -> Parser<Vec<u8>, E> {
+        tag(expected.as_bytes())
+    }
+
+    pub fn utf8_char<E: ParseFailure>() -> Parser<char, E> {
+        Utf8CharParser { predicate: |_| true }.create()
+    }
+
+    pub fn utf8_satisfy<E: ParseFailure>(f: fn(char) -> bool) -> Parser<char, E> {
+        Utf8CharParser { predicate: f }.create()
+    }
+
+    pub fn digit<E: ParseFailure>() -> Parser<u8, E> {
+        satisfy_labeled("digit", |c| c.is_ascii_digit())
+    }
+
+    pub fn alpha<E: ParseFailure>() -> Parser<u8, E> {
+        satisfy_labeled("letter", |c| c.is_ascii_alphabetic())
+    }
+
+    pub fn alphanumeric<E: ParseFailure>() -> Parser<u8, E> {
+        satisfy_labeled("letter or digit", |c| c.is_ascii_alphanumeric())
+    }
+
+    pub fn hex_digit<E: ParseFailure>() -> Parser<u8, E> {
+        satisfy_labeled("hex digit", |c| c.is_ascii_hexdigit())
+    }
+
+    pub fn take_while<E: ParseFailure>(predicate: fn(u8) -> bool) -> Parser<Vec<u8>, E> {
+        RunParser { predicate, min: 0, label: None }.create()
+    }
+
+    pub fn take_while1<E: ParseFailure>(predicate: fn(u8) -> bool) -> Parser<Vec<u8>, E> {
+        RunParser { predicate, min: 1, label: None }.create()
+    }
+
+    pub fn digit1<E: ParseFailure>() -> Parser<Vec<u8>, E> {
+        RunParser { predicate: |c| c.is_ascii_digit(), min: 1, label: Some("digit") }.create()
+    }
+
+    pub fn alpha1<E: ParseFailure>() -> Parser<Vec<u8>, E> {
+        RunParser { predicate: |c| c.is_ascii_alphabetic(), min: 1, label: Some("letter") }.create()
+    }
+
+    pub fn space0<E: ParseFailure>() -> Parser<(), E> {
+        SkipRunParser { predicate: is_space, min: 0, label: "space" }.create()
+    }
+
+    pub fn space1<E: ParseFailure>() -> Parser<(), E> {
+        SkipRunParser { predicate: is_space, min: 1, label: "space" }.create()
+    }
+
+    pub fn multispace0<E: ParseFailure>() -> Parser<(), E> {
+        SkipRunParser { predicate: is_multispace, min: 0, label: "whitespace" }.create()
+    }
+
+    pub fn multispace1<E: ParseFailure>() -> Parser<(), E> {
+        SkipRunParser { predicate: is_multispace, min: 1, label: "whitespace" }.create()
+    }
+
+    pub fn line_ending<E: ParseFailure>() -> Parser<(), E> {
+        LineEndingParser {}.create()
+    }
+
+    pub fn not_line_ending<E: ParseFailure>() -> Parser<Vec<u8>, E> {
+        NotLineEndingParser {}.create()
+    }
+
+    pub fn line<E: ParseFailure>() -> Parser<Vec<u8>, E> {
+        LineParser {}.create()
+    }
+
+    pub fn take<E: ParseFailure>(n: usize) -> Parser<Vec<u8>, E> {
+        TakeParser { n }.create()
+    }
+
+    pub fn take_until<E: ParseFailure>(delimiter: &'static [u8]) -> Parser<Vec<u8>, E> {
+        TakeUntilParser { delimiter, consume: false }.create()
+    }
+
+    pub fn take_until_and_consume<E: ParseFailure>(delimiter: &'static [u8]) -> Parser<Vec<u8>, E> {
+        TakeUntilParser { delimiter, consume: true }.create()
+    }
+
+    pub fn rest<E: ParseFailure>() -> Parser<Vec<u8>, E> {
+        RestParser {}.create()
+    }
+
+    pub fn rest_len<E: ParseFailure>() -> Parser<usize, E> {
+        RestLenParser {}.create()
+    }
+
+    pub fn eof<E: ParseFailure>() -> Parser<(), E> {
+        EofParser {}.create()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinator::{concat, oneof, process, star};
+    use crate::error::ParseError;
+
+    #[test]
+    fn failures() {
+        assert_eq!(byte(b'a').parse(0, b"b"), Fail(ParseError::expected(0, Expected::Byte(b'a'))));
+        assert_eq!(digit().parse(1, b"1x"), Fail(ParseError::expected(1, Expected::Class("digit"))));
+        assert_eq!(tag(b"let").parse(0, b"le"), Fail(ParseError::expected(0, Expected::Tag(b"let"))));
+        assert_eq!(digit1().parse(0, b""), Fail(ParseError::expected(0, Expected::Class("digit"))));
+        assert_eq!(space1().parse(0, b"x"), Fail(ParseError::expected(0, Expected::Class("space"))));
+        assert_eq!(eof().parse(0, b"a"), Fail(ParseError::expected(0, Expected::EndOfInput)));
+
+        // no label
+        assert_eq!(satisfy(|c| c == b'a').parse(1, b"1x"), Fail(ParseError::unexpected(1, b"1x")));
+        assert_eq!(take_while1(|c| c == b'a').parse(0, b""), Fail(ParseError::unexpected(0, b"")));
+        assert_eq!(take(3).parse(0, b"ab"), Fail(ParseError::unexpected(2, b"ab")));
+        assert_eq!(ParseError::unexpected(1, b"1x").message, "unexpected 'x'");
+
+        // sets
+        let error = one_of(b"ba").parse(0, b"\n");
+        assert_eq!(error, Fail(ParseError {
+            expected: vec![Expected::Byte(b'a'), Expected::Byte(b'b')],
+            ..ParseError::new(0, "")
+        }));
+        if let Fail(error) = error {
+            assert_eq!(error.to_string(), "expected one of: 'a', 'b' at offset 0");
+        }
+        assert_eq!(none_of(b"ab").parse(0, b"a"), Fail(ParseError::unexpected(0, b"a")));
+    }
+
+    #[test]
+    fn tagged() {
+        let p = tag(b"let");
+        assert_eq!(p.parse(0, b"let x"), Success(3, b"let".to_vec()));
+        assert!(p.parse(0, b"lex").is_fail());
+
+        // match at an offset
+        let p = tag_str("x = ");
+        assert_eq!(p.parse(4, b"let x = 1"), Success(8, b"x = ".to_vec()));
+
+        // partial match at the end of the input
+        assert!(tag(b"let").parse(0, b"le").is_fail());
+        assert!(tag(b"let").parse(3, b"let").is_fail());
+
+        // the empty tag always matches, without moving
+        assert_eq!(tag(b"").parse(2, b"ab"), Success(2, vec![]));
+        assert_eq!(tag(b"").parse(0, b""), Success(0, vec![]));
+
+        // no input is consumed on failure, so the next alternative can match
+        let p = oneof(vec![tag(b"lex"), tag(b"let")]);
+        assert_eq!(p.parse(0, b"let"), Success(3, b"let".to_vec()));
+    }
+
+    #[test]
+    fn tagged_no_case() {
+        let p = tag_no_case(b"content-length");
+        assert_eq!(p.parse(0, b"Content-Length: 3"), Success(14, b"Content-Length".to_vec()));
+        assert_eq!(p.parse(0, b"CONTENT-LENGTH"), Success(14, b"CONTENT-LENGTH".to_vec()));
+        // only letters are case-insensitive
+        assert!(p.parse(0, b"content_length").is_fail());
+        assert!(tag_no_case(b"\xe9").parse(0, b"\xc9").is_fail());
+        // truncated input
+        assert!(p.parse(0, b"Content-Len").is_fail());
+        assert!(p.parse(20, b"Content-Length").is_fail());
+    }
+
+    #[test]
+    fn single_byte() {
+        assert_eq!(byte(b'x').parse(0, b"xy"), Success(1, b'x'));
+        assert!(byte(b'x').parse(1, b"xy").is_fail());
+        assert!(byte(b'x').parse(2, b"xy").is_fail());
+
+        let digit = satisfy(|c| c.is_ascii_digit());
+        assert_eq!(digit.parse(0, b"4a"), Success(1, b'4'));
+        assert!(digit.parse(1, b"4a").is_fail());
+        assert!(digit.parse(0, b"").is_fail());
+
+        // "(" digits ")"
+        let p = concat(vec![byte(b'('), digit.clone(), digit.clone(), byte(b')')]);
+        assert_eq!(p.parse(0, b"(42)"), Success(4, b"(42)".to_vec()));
+        assert!(p.parse(0, b"(4)").is_fail());
+
+        // star stops right before the first rejected byte
+        let p = star(digit);
+        assert_eq!(p.parse(0, b"123abc"), Success(3, b"123".to_vec()));
+        assert_eq!(p.parse(0, b"abc"), Success(0, vec![]));
+        let p = star(byte(b'a'));
+        assert_eq!(p.parse(0, b"aab"), Success(2, b"aa".to_vec()));
+    }
+
+    #[test]
+    fn byte_sets() {
+        let op = one_of(b"+-*/");
+        assert_eq!(op.parse(0, b"*2"), Success(1, b'*'));
+        assert!(op.parse(1, b"*2").is_fail());
+        assert!(op.parse(0, b"").is_fail());
+
+        // duplicates don't change the set
+        assert_eq!(one_of(b"aaa").parse(0, b"a"), Success(1, b'a'));
+        assert!(one_of(b"aaa").parse(0, b"b").is_fail());
+
+        // the empty set matches nothing, its complement matches any byte
+        assert!(one_of(b"").parse(0, b"a").is_fail());
+        assert_eq!(none_of(b"").parse(0, b"\xff"), Success(1, 0xff));
+        assert!(none_of(b"").parse(0, b"").is_fail());
+
+        // quoted text
+        let p = concat(vec![
+            byte(b'"'),
+            process(|chars| chars.len() as u8, star(none_of(b"\"\n"))),
+            byte(b'"')
+        ]);
+        assert_eq!(p.parse(0, b"\"abc\" rest"), Success(5, vec![b'"', 3, b'"']));
+        assert!(p.parse(0, b"\"ab\nc\"").is_fail());
+    }
+
+    #[test]
+    fn utf8() {
+        assert_eq!(utf8_char().parse(0, b"a"), Success(1, 'a'));
+        assert_eq!(utf8_char().parse(0, "é!".as_bytes()), Success(2, 'é'));
+        assert_eq!(utf8_char().parse(0, "日本".as_bytes()), Success(3, '日'));
+        assert_eq!(utf8_char().parse(3, "日本".as_bytes()), Success(6, '本'));
+        assert_eq!(utf8_char().parse(0, "😀".as_bytes()), Success(4, '😀'));
+        assert!(utf8_char().parse(0, b"").is_fail());
+
+        // truncated at the end of the input
+        assert!(utf8_char().parse(0, &"日".as_bytes()[..2]).is_fail());
+        assert!(utf8_char().parse(0, &"😀".as_bytes()[..3]).is_fail());
+        // overlong encodings of '/' and of U+0000
+        assert!(utf8_char().parse(0, b"\xc0\xaf").is_fail());
+        assert!(utf8_char().parse(0, b"\xe0\x80\xaf").is_fail());
+        // lone continuation byte, encoded surrogate, out of range
+        assert!(utf8_char().parse(0, b"\x80").is_fail());
+        assert!(utf8_char().parse(0, b"\xed\xa0\x80").is_fail());
+        assert!(utf8_char().parse(0, b"\xf4\x90\x80\x80").is_fail());
+
+        let p = star(utf8_satisfy(char::is_alphabetic));
+        let result = p.parse(0, "héllo wörld".as_bytes());
+        assert_eq!(result, Success(6, vec!['h', 'é', 'l', 'l', 'o']));
+    }
+
+    #[test]
+    fn byte_ranges() {
+        let lower = byte_range(b'a'..=b'z');
+        assert_eq!(lower.describe(), "ByteRange('a'..='z')");
+        assert_eq!(lower.parse(0, b"q"), Success(1, b'q'));
+        assert_eq!(lower.parse(0, b"z"), Success(1, b'z'));
+        assert_eq!(lower.parse(0, b"Q"), Fail(ParseError::unexpected(0, b"Q")));
+        assert_eq!(lower.parse(0, b""), Fail(ParseError::unexpected(0, b"")));
+        // exclusive and open ranges
+        assert!(byte_range(b'0'..b'8').parse(0, b"8").is_fail());
+        assert_eq!(byte_range(b'0'..b'8').parse(0, b"7"), Success(1, b'7'));
+        assert_eq!(byte_range(0x80..).parse(0, b"\xff"), Success(1, 0xff));
+        assert_eq!(byte_range(..).parse(0, b"\x00"), Success(1, 0));
+        // empty ranges match nothing
+        assert!(byte_range(b'a'..b'a').parse(0, b"a").is_fail());
+        assert!(byte_range(..0).parse(0, b"\x00").is_fail());
+    }
+
+    #[test]
+    fn char_classes() {
+        let lower = CharClass::range(b'a', b'z');
+        let word = lower.union(CharClass::of(b"_-"));
+        assert!(word.contains(b'q') && word.contains(b'_') && word.contains(b'-'));
+        assert!(!word.contains(b'A'));
+        assert_eq!(word.len(), 28);
+        assert_eq!(word.to_string(), "[a-z_-]");
+        assert_eq!(format!("{:?}", word), "CharClass([a-z_-])");
+
+        // de morgan, and other sanity checks
+        let digits = CharClass::range(b'0', b'9');
+        assert_eq!(lower.union(digits).negate(), lower.negate().intersection(digits.negate()));
+        assert_eq!(lower.intersection(word).negate(), lower.negate().union(word.negate()));
+        assert_eq!(word.negate().negate(), word);
+        assert_eq!(word.union(word.negate()), CharClass::any());
+        assert_eq!(word.intersection(word.negate()), CharClass::empty());
+        assert_eq!(word.difference(lower), CharClass::of(b"-_"));
+        assert_eq!(CharClass::range(b'z', b'a'), CharClass::empty());
+        assert!(CharClass::empty().is_empty() && !word.is_empty());
+
+        // rendering: short runs are listed, large classes negated, special bytes escaped
+        assert_eq!(CharClass::of(b"ab").to_string(), "[ab]");
+        assert_eq!(CharClass::of(b"abc\n").to_string(), "[a-c\\n]");
+        assert_eq!(CharClass::of(b"]^").to_string(), "[\\]\\^]");
+        assert_eq!(CharClass::of(b"\"\\").negate().to_string(), "[^\"\\\\]");
+        assert_eq!(CharClass::empty().to_string(), "[]");
+        assert_eq!(CharClass::any().to_string(), "[^]");
+
+        // as a parser
+        let p = class(word);
+        assert_eq!(p.describe(), "Class([a-z_-])");
+        assert_eq!(p.parse(0, b"_x"), Success(1, b'_'));
+        assert_eq!(p.parse(0, b"X"), Fail(ParseError::expected(0, Expected::Set(word))));
+        assert_eq!(ParseError::expected(0, Expected::Set(word)).to_string(), "expected [a-z_-] at offset 0");
+        let not_word: Parser<u8> = word.negate().into();
+        assert_eq!(not_word.parse(0, b"X"), Success(1, b'X'));
+        assert_eq!(not_word.parse(0, b"\xff"), Success(1, 0xff));
+        let error = ParseError::expected(0, Expected::Set(word.negate()));
+        assert_eq!(not_word.parse(0, b"a"), Fail(error.clone()));
+        assert_eq!(error.to_string(), "expected [^a-z_-] at offset 0");
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(digit().parse(0, b"7"), Success(1, b'7'));
+        assert!(digit().parse(0, b"a").is_fail());
+        assert_eq!(alpha().parse(0, b"Q"), Success(1, b'Q'));
+        assert!(alpha().parse(0, b"_").is_fail());
+        assert_eq!(alphanumeric().parse(0, b"z9"), Success(1, b'z'));
+        assert_eq!(alphanumeric().parse(1, b"z9"), Success(2, b'9'));
+
+        // both cases of hex digits
+        let p = star(hex_digit());
+        assert_eq!(p.parse(0, b"09afAFg"), Success(6, b"09afAF".to_vec()));
+
+        // non-ascii bytes are simply rejected
+        for p in [digit(), alpha(), alphanumeric(), hex_digit()] {
+            assert!(p.parse(0, "é".as_bytes()).is_fail());
+            assert!(p.parse(0, b"\xff").is_fail());
+            assert!(p.parse(0, b"").is_fail());
+        }
+    }
+
+    #[test]
+    fn runs() {
+        assert_eq!(digit1().parse(0, b"123abc"), Success(3, b"123".to_vec()));
+        assert_eq!(alpha1().parse(0, b"abc123"), Success(3, b"abc".to_vec()));
+        assert!(alpha1().parse(3, b"abc").is_fail());
+
+        // at least one byte is required
+        assert!(digit1().parse(0, b"").is_fail());
+        assert!(digit1().parse(0, b"x1").is_fail());
+
+        let number = process(|digits| String::from_utf8(digits).unwrap(), digit1());
+        let p = concat(vec![number.clone(), process(|_| String::new(), byte(b'.')), number]);
+        let result = p.parse(0, b"3.14");
+        assert_eq!(result, Success(4, vec!["3".to_string(), String::new(), "14".to_string()]));
+    }
+
+    #[test]
+    fn predicate_runs() {
+        let lower = take_while(|c| c.is_ascii_lowercase());
+        assert_eq!(lower.parse(0, b"abcD"), Success(3, b"abc".to_vec()));
+        assert_eq!(lower.parse(1, b"abc"), Success(3, b"bc".to_vec()));
+        // at the end of input and with nothing matching
+        assert_eq!(lower.parse(3, b"abc"), Success(3, vec![]));
+        assert_eq!(lower.parse(0, b"ABC"), Success(0, vec![]));
+
+        let lower = take_while1(|c| c.is_ascii_lowercase());
+        assert_eq!(lower.parse(0, b"abcD"), Success(3, b"abc".to_vec()));
+        assert!(lower.parse(3, b"abc").is_fail());
+        assert!(lower.parse(0, b"ABC").is_fail());
+
+        // a long run is matched in one step
+        let source = vec![b'a'; 10_000];
+        assert!(matches!(lower.parse(0, &source), Success(10_000, _)));
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(space0().parse(0, b" \t x"), Success(3, ()));
+        assert_eq!(space0().parse(0, b"x"), Success(0, ()));
+        assert_eq!(space0().parse(0, b""), Success(0, ()));
+        assert_eq!(space1().parse(0, b"  x"), Success(2, ()));
+        assert!(space1().parse(0, b"").is_fail());
+        assert!(space1().parse(0, b"x").is_fail());
+
+        // spaces stop at line breaks, multispaces don't
+        assert_eq!(space0().parse(0, b" \n"), Success(1, ()));
+        assert_eq!(multispace0().parse(0, b" \t\r\n x"), Success(5, ()));
+        assert_eq!(multispace1().parse(0, b" \t\r\n "), Success(5, ()));
+        assert!(multispace1().parse(0, b"x").is_fail());
+
+        // key = value
+        let p = concat(vec![
+            alpha1(),
+            process(|_| vec![], concat(vec![space0(), process(|_| (), byte(b'=')), space0()])),
+            digit1()
+        ]);
+        assert_eq!(p.parse(0, b"x\t= 12"), Success(6, vec![b"x".to_vec(), vec![], b"12".to_vec()]));
+        assert_eq!(p.parse(0, b"x=12"), Success(4, vec![b"x".to_vec(), vec![], b"12".to_vec()]));
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(line_ending().parse(0, b"\nx"), Success(1, ()));
+        assert_eq!(line_ending().parse(0, b"\r\nx"), Success(2, ()));
+        assert!(line_ending().parse(0, b"\rx").is_fail());
+        assert!(line_ending().parse(0, b"\r").is_fail());
+        assert!(line_ending().parse(0, b"").is_fail());
+
+        assert_eq!(not_line_ending().parse(0, b"abc\r\ndef"), Success(3, b"abc".to_vec()));
+        assert_eq!(not_line_ending().parse(4, b"abc\ndef"), Success(7, b"def".to_vec()));
+        assert_eq!(not_line_ending().parse(0, b"\n"), Success(0, vec![]));
+        assert!(not_line_ending().parse(0, b"ab\rc").is_fail());
+
+        // the last line doesn't need a trailing newline
+        let p = star(line());
+        let expected = vec![b"a".to_vec(), vec![], b"b c".to_vec()];
+        assert_eq!(p.parse(0, b"a\r\n\nb c"), Success(7, expected.clone()));
+        assert_eq!(p.parse(0, b"a\r\n\nb c\n"), Success(8, expected));
+        assert_eq!(p.parse(0, b""), Success(0, vec![]));
+    }
+
+    #[test]
+    fn end_of_input() {
+        assert_eq!(eof().parse(3, b"abc"), Success(3, ()));
+        assert!(eof().parse(1, b"abc").is_fail());
+        assert_eq!(eof().parse(0, b""), Success(0, ()));
+
+        // a whole document, and nothing after it
+        let document = process(|_| (), digit1());
+        let p = concat(vec![document, eof()]);
+        assert_eq!(p.parse(0, b"123"), Success(3, vec![(), ()]));
+        assert!(p.parse(0, b"123x").is_fail());
+    }
+
+    #[test]
+    fn fixed_length() {
+        assert_eq!(take(3).parse(0, b"abc"), Success(3, b"abc".to_vec()));
+        assert_eq!(take(2).parse(1, b"abc"), Success(3, b"bc".to_vec()));
+        assert!(take(4).parse(0, b"abc").is_fail());
+        assert!(take(1).parse(3, b"abc").is_fail());
+        assert_eq!(take(0).parse(3, b"abc"), Success(3, vec![]));
+
+        let p = concat(vec![take(2), take(3)]);
+        assert_eq!(p.parse(0, b"\x01\x02abc"), Success(5, vec![vec![1, 2], b"abc".to_vec()]));
+        assert!(p.parse(0, b"\x01\x02ab").is_fail());
+    }
+
+    #[test]
+    fn until_delimiter() {
+        let p = take_until(b"*/");
+        assert_eq!(p.parse(2, b"/* abc */"), Success(7, b" abc ".to_vec()));
+        // the delimiter is left for the next parser
+        let p = concat(vec![take_until(b"*/"), tag(b"*/")]);
+        assert_eq!(p.parse(2, b"/* abc */"), Success(9, vec![b" abc ".to_vec(), b"*/".to_vec()]));
+        let p = take_until_and_consume(b"*/");
+        assert_eq!(p.parse(2, b"/* abc */ x"), Success(9, b" abc ".to_vec()));
+
+        // delimiter right at the current position
+        assert_eq!(take_until(b";").parse(0, b";"), Success(0, vec![]));
+        assert_eq!(take_until_and_consume(b";").parse(0, b";"), Success(1, vec![]));
+
+        // delimiter missing, or longer than the remaining input
+        assert!(take_until(b";").parse(0, b"abc").is_fail());
+        assert!(take_until(b"abcd").parse(0, b"abc").is_fail());
+        assert!(take_until(b";").parse(0, b"").is_fail());
+
+        // overlapping bytes: the first occurrence wins
+        assert_eq!(take_until(b"aa").parse(0, b"baaa"), Success(1, b"b".to_vec()));
+        assert_eq!(take_until_and_consume(b"aa").parse(0, b"aaa"), Success(2, vec![]));
+    }
+
+    #[test]
+    fn remainder() {
+        assert_eq!(rest().parse(2, b"abcd"), Success(4, b"cd".to_vec()));
+        assert_eq!(rest().parse(4, b"abcd"), Success(4, vec![]));
+        assert_eq!(rest().parse(0, b""), Success(0, vec![]));
+        assert_eq!(rest_len().parse(1, b"abcd"), Success(4, 3));
+        assert_eq!(rest_len().parse(4, b"abcd"), Success(4, 0));
+
+        // header, then payload
+        let p = concat(vec![take_until_and_consume(b"\n\n"), rest()]);
+        let expected = vec![b"header".to_vec(), b"payload".to_vec()];
+        assert_eq!(p.parse(0, b"header\n\npayload"), Success(15, expected));
+    }
+
+    #[test]
+    fn functions() {
+        // nmea sentence: the checksum is the xor of the bytes between '$' and '*'
+        let checksum = from_fn(|position, source: &[u8]| {
+            let digits = match source.get(position..position + 3) {
+                Some([b'*', digits @ ..]) => digits,
+                _ => return Fail(ParseError::expected(position, Expected::Byte(b'*')))
+            };
+            let expected = source[1..position].iter().fold(0, |sum, c| sum ^ c);
+            match std::str::from_utf8(digits).ok().and_then(|digits| u8::from_str_radix(digits, 16).ok()) {
+                Some(sum) if sum == expected => Success(position + 3, digits.to_vec()),
+                Some(_) => Fail(ParseError::new(position + 1, "checksum mismatch")),
+                None => Fail(ParseError::expected(position + 1, Expected::Class("hex digit")))
+            }
+        });
+        assert_eq!(checksum.describe(), "FromFn");
+        let sentence = concat(vec![tag(b"$"), take_while1(|c| c != b'*'), checksum.clone()]);
+        let expected = vec![b"$".to_vec(), b"GPGLL,4916.45,N,12311.12,W,225444,A".to_vec(), b"31".to_vec()];
+        assert_eq!(sentence.parse_all(b"$GPGLL,4916.45,N,12311.12,W,225444,A*31"), Success(39, expected));
+        assert_eq!(
+            sentence.parse(0, b"$GPGLL,4916.45,N,12311.12,W,225444,A*32"),
+            Fail(ParseError::new(37, "checksum mismatch"))
+        );
+        assert_eq!(sentence.parse(0, b"$GPGLL*3"), Fail(ParseError::expected(6, Expected::Byte(b'*'))));
+        assert_eq!(sentence.parse(0, b"$A*zz"), Fail(ParseError::expected(3, Expected::Class("hex digit"))));
+
+        // the copies share the function
+        let copy = checksum.clone();
+        assert_eq!(copy.parse(2, b"$A*41"), Success(5, b"41".to_vec()));
+        assert_eq!(oneof(vec![copy, tag(b"*")]).parse(2, b"$A*00"), Success(3, b"*".to_vec()));
+    }
+
+    #[test]
+    fn literals() {
+        // a string, a byte, a range and a prebuilt parser in one alternation
+        let token = crate::alt!["let", b';', b'0'..=b'9', tag_no_case(b"fn")];
+        assert_eq!(token.parse(0, b"let x"), Success(3, b"let".to_vec()));
+        assert_eq!(token.parse(0, b";"), Success(1, b";".to_vec()));
+        assert_eq!(token.parse(0, b"7"), Success(1, b"7".to_vec()));
+        assert_eq!(token.parse(0, b"FN"), Success(2, b"FN".to_vec()));
+        assert_eq!(token.parse(0, b"x"), Fail(ParseError {
+            expected: vec![Expected::Tag(b"let"), Expected::Byte(b';'), Expected::Tag(b"fn")],
+            ..ParseError::unexpected(0, b"x")
+        }));
+
+        // single bytes keep their type among byte parsers
+        let sign: Parser<u8> = crate::alt![b'+', b'-', digit()];
+        assert_eq!(sign.parse(0, b"-1"), Success(1, b'-'));
+        assert_eq!(sign.parse(0, b"1"), Success(1, b'1'));
+
+        let assignment = crate::cat!["let ", alpha1(), b" = ", b'0'..b':', b";"];
+        let expected = vec![b"let ".to_vec(), b"x".to_vec(), b" = ".to_vec(), b"5".to_vec(), b";".to_vec()];
+        assert_eq!(assignment.parse_all(b"let x = 5;"), Success(10, expected));
+
+        let p: Parser<Vec<u8>> = "if".into();
+        assert_eq!(p.describe(), r#"Tag("if")"#);
+        let p: Parser<u8> = b'a'.into();
+        assert_eq!(p.describe(), "Byte('a')");
+    }
+}
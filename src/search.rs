@@ -0,0 +1,86 @@
+use std::ops::Range;
+use crate::{Parse, Parser};
+use crate::Result::*;
+use crate::error::ParseFailure;
+
+// the parser as a pattern: find the regions of the input it matches, and rewrite them
+// scanning: the parser is tried at every position, from the start of the input to its end (included),
+// the matches are leftmost and don't overlap
+// - after a match, the scan continues from its end
+// - where the parser fails (or stops with a fatal error), the scan moves on by one byte
+// - after a zero-width match, the scan moves on by one byte too (or it would match at the same position forever)
+
+// the range and the value of every match
+pub fn find_all<T: 'static, E: ParseFailure>(parser: &Parser<T, E>, input: &[u8]) -> Vec<(Range<usize>, T)> {
+    let mut matches = Vec::new();
+    let mut position = 0;
+    while position <= input.len() {
+        match parser.parse(position, input) {
+            Success(end, data) => {
+                matches.push((position..end, data));
+                position = if end == position { position + 1 } else { end };
+            }
+            Fail(_) | Error(_) => position += 1
+        }
+    }
+    matches
+}
+
+// a copy of the input, with every match replaced by f(value, matched bytes)
+// (a zero-width match inserts its replacement)
+pub fn replace_all<T: 'static, E: ParseFailure>(
+    parser: &Parser<T, E>,
+    input: &[u8],
+    f: impl Fn(&T, &[u8]) -> Vec<u8>
+) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut copied = 0;
+    for (range, data) in find_all(parser, input) {
+        output.extend_from_slice(&input[copied..range.start]);
+        output.extend(f(&data, &input[range.clone()]));
+        copied = range.end;
+    }
+    output.extend_from_slice(&input[copied..]);
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::{combinator, primitive, text};
+
+    // ${name}
+    fn variable() -> Parser<String> {
+        combinator::delimited(primitive::tag(b"${"), text::identifier(), primitive::byte(b'}'))
+    }
+
+    #[test]
+    fn template() {
+        let values = HashMap::from([("name", "world"), ("greeting", "hello")]);
+        let expand = |name: &String, matched: &[u8]| match values.get(name.as_str()) {
+            Some(value) => value.as_bytes().to_vec(),
+            // unknown variables are left as they are
+            None => matched.to_vec()
+        };
+        let output = replace_all(&variable(), b"${greeting}, ${name}! (${unknown}, ${} and $name)", expand);
+        assert_eq!(output, b"hello, world! (${unknown}, ${} and $name)");
+        // adjacent matches
+        assert_eq!(replace_all(&variable(), b"${name}${name}", expand), b"worldworld");
+        // no match
+        assert_eq!(replace_all(&variable(), b"no variables", expand), b"no variables");
+        assert_eq!(replace_all(&variable(), b"", expand), b"");
+        assert_eq!(find_all(&variable(), b"$${a} ${b}"), vec![(1..5, "a".to_string()), (6..10, "b".to_string())]);
+    }
+
+    #[test]
+    fn zero_width() {
+        // the scan doesn't hang on empty matches, and inserts at every position where they happen
+        let spaces = primitive::space0();
+        assert_eq!(replace_all(&spaces, b"a  b", |_, _| b"_".to_vec()), b"_a__b_");
+        let before_digit = combinator::peek(primitive::digit());
+        assert_eq!(replace_all(&before_digit, b"a1b22", |_, _| b"#".to_vec()), b"a#1b#2#2");
+        assert_eq!(find_all(&before_digit, b"12").len(), 2);
+    }
+}
@@ -0,0 +1,322 @@
+use std::ops::Deref;
+use std::sync::Arc;
+use crate::{Parse, Parser, Result};
+use crate::Result::*;
+use crate::error::{ErrorKind, Expected, ParseError, ParseFailure};
+
+// stateful parsers: parse() also gets a &mut S owned by the application
+// (a symbol table, an indentation stack, an "inside an interpolation" flag...)
+// the combinators pass the state down to their parsers, with_state() reads and changes it,
+// and stateless() turns any Parser into a stateful one (that ignores the state)
+//
+// backtracking: the changes made by a failed branch are undone
+// the combinators that backtrack (oneof(), optional() and star()) take a snapshot of the state before trying
+// a parser (S: Clone), and put it back when the parser fails
+// so the state should be cheap to clone (small, or shared with Rc/Arc)
+// (after a failed parse, or a fatal error, the state of the application is left as the failure found it)
+
+pub trait StatefulParse<T, S, E = ParseError> {
+    fn create(&self) -> StatefulParser<T, S, E>;
+    fn parse(&self, position: usize, source: &[u8], state: &mut S) -> Result<T, E>;
+
+    // parse the whole source (fails if anything is left after the parser)
+    fn parse_all(&self, source: &[u8], state: &mut S) -> Result<T, E> where E: ParseFailure {
+        match self.parse(0, source, state) {
+            Success(position, data) if position == source.len() => Success(position, data),
+            Success(position, _) => Fail(E::expected(position, Expected::EndOfInput)),
+            Fail(error) => Fail(error),
+            Error(error) => Error(error)
+        }
+    }
+}
+
+pub type StatefulParser<T, S, E = ParseError> = Box<dyn StatefulParse<T, S, E> + Send + Sync>;
+
+impl<T, S, E: ParseFailure> StatefulParse<T, S, E> for StatefulParser<T, S, E> {
+    fn create(&self) -> StatefulParser<T, S, E> {
+        self.deref().create()
+    }
+
+    fn parse(&self, position: usize, source: &[u8], state: &mut S) -> Result<T, E> {
+        self.deref().parse(position, source, state)
+    }
+}
+
+impl<T, S, E: ParseFailure> Clone for StatefulParser<T, S, E> {
+    fn clone(&self) -> Self {
+        self.deref().create()
+    }
+}
+
+
+// a parser that doesn't use the state
+struct StatelessParser<T, E> {
+    parser: Parser<T, E>
+}
+
+impl<T: 'static, S, E: ParseFailure> StatefulParse<T, S, E> for StatelessParser<T, E> {
+    fn create(&self) -> StatefulParser<T, S, E> {
+        Box::new(StatelessParser { parser: self.parser.clone() })
+    }
+
+    fn parse(&self, position: usize, source: &[u8], _state: &mut S) -> Result<T, E> {
+        self.parser.parse(position, source)
+    }
+}
+
+pub fn stateless<T: 'static, S, E: ParseFailure>(parser: Parser<T, E>) -> StatefulParser<T, S, E> {
+    StatelessParser { parser }.create()
+}
+
+// read and change the state with the value of a successful parse
+// the parser fails (at its start position) when the function returns None
+// (changes made before returning None are undone by the enclosing backtracking combinator, see above)
+type StateFn<T, U, S> = dyn Fn(T, &mut S) -> Option<U> + Send + Sync;
+
+struct WithStateParser<T, U, S, E> {
+    parser: StatefulParser<T, S, E>,
+    f: Arc<StateFn<T, U, S>>
+}
+
+impl<T: 'static, U: 'static, S: 'static, E: ParseFailure> StatefulParse<U, S, E> for WithStateParser<T, U, S, E> {
+    fn create(&self) -> StatefulParser<U, S, E> {
+        Box::new(WithStateParser { parser: self.parser.clone(), f: self.f.clone() })
+    }
+
+    fn parse(&self, position: usize, source: &[u8], state: &mut S) -> Result<U, E> {
+        match self.parser.parse(position, source, state) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(end, data) => match (self.f)(data, state) {
+                Some(data) => Success(end, data),
+                None => Fail(E::new(position, "value rejected by with_state()").with_kind(ErrorKind::PredicateFailed))
+            }
+        }
+    }
+}
+
+pub fn with_state<T: 'static, U: 'static, S: 'static, E: ParseFailure>(
+    f: impl Fn(T, &mut S) -> Option<U> + Send + Sync + 'static,
+    parser: StatefulParser<T, S, E>
+) -> StatefulParser<U, S, E> {
+    WithStateParser { parser, f: Arc::new(f) }.create()
+}
+
+// apply a function to the result of a successful parsing
+struct MapParser<T, U, S, E> {
+    parser: StatefulParser<T, S, E>,
+    f: Arc<dyn Fn(T) -> U + Send + Sync>
+}
+
+impl<T: 'static, U: 'static, S: 'static, E: ParseFailure> StatefulParse<U, S, E> for MapParser<T, U, S, E> {
+    fn create(&self) -> StatefulParser<U, S, E> {
+        Box::new(MapParser { parser: self.parser.clone(), f: self.f.clone() })
+    }
+
+    fn parse(&self, position: usize, source: &[u8], state: &mut S) -> Result<U, E> {
+        self.parser.parse(position, source, state).map(|data| (self.f)(data))
+    }
+}
+
+pub fn process<T: 'static, U: 'static, S: 'static, E: ParseFailure>(
+    f: impl Fn(T) -> U + Send + Sync + 'static,
+    parser: StatefulParser<T, S, E>
+) -> StatefulParser<U, S, E> {
+    MapParser { parser, f: Arc::new(f) }.create()
+}
+
+// two parsers in sequence
+struct PairParser<A, B, S, E> {
+    first: StatefulParser<A, S, E>,
+    second: StatefulParser<B, S, E>
+}
+
+impl<A: 'static, B: 'static, S: 'static, E: ParseFailure> StatefulParse<(A, B), S, E> for PairParser<A, B, S, E> {
+    fn create(&self) -> StatefulParser<(A, B), S, E> {
+        Box::new(PairParser { first: self.first.clone(), second: self.second.clone() })
+    }
+
+    fn parse(&self, position: usize, source: &[u8], state: &mut S) -> Result<(A, B), E> {
+        match self.first.parse(position, source, state) {
+            Fail(error) => Fail(error),
+            Error(error) => Error(error),
+            Success(position, a) => self.second.parse(position, source, state).map(|b| (a, b))
+        }
+    }
+}
+
+pub fn pair<A: 'static, B: 'static, S: 'static, E: ParseFailure>(
+    first: StatefulParser<A, S, E>,
+    second: StatefulParser<B, S, E>
+) -> StatefulParser<(A, B), S, E> {
+    PairParser { first, second }.create()
+}
+
+// the first alternative that succeeds, from a snapshot of the state
+// (the failure that went the furthest, if all of them fail)
+struct OrParser<T, S, E> {
+    parsers: Vec<StatefulParser<T, S, E>>
+}
+
+impl<T: 'static, S: Clone + 'static, E: ParseFailure> StatefulParse<T, S, E> for OrParser<T, S, E> {
+    fn create(&self) -> StatefulParser<T, S, E> {
+        Box::new(OrParser { parsers: self.parsers.clone() })
+    }
+
+    fn parse(&self, position: usize, source: &[u8], state: &mut S) -> Result<T, E> {
+        let mut error: Option<E> = None;
+        for p in &self.parsers {
+            let snapshot = state.clone();
+            match p.parse(position, source, state) {
+                Error(e) => return Error(e),
+                Fail(e) => {
+                    *state = snapshot;
+                    error = Some(match error {
+                        None => e,
+                        Some(error) => error.merge(e)
+                    });
+                }
+                Success(pos, data) => return Success(pos, data)
+            }
+        }
+        Fail(error.unwrap_or_else(|| E::new(position, "no alternative to choose from")))
+    }
+}
+
+pub fn oneof<T: 'static, S: Clone + 'static, E: ParseFailure>(
+    parsers: Vec<StatefulParser<T, S, E>>
+) -> StatefulParser<T, S, E> {
+    OrParser { parsers }.create()
+}
+
+// None (and the state as it was) when the parser fails
+struct OptionalParser<T, S, E> {
+    parser: StatefulParser<T, S, E>
+}
+
+impl<T: 'static, S: Clone + 'static, E: ParseFailure> StatefulParse<Option<T>, S, E> for OptionalParser<T, S, E> {
+    fn create(&self) -> StatefulParser<Option<T>, S, E> {
+        Box::new(OptionalParser { parser: self.parser.clone() })
+    }
+
+    fn parse(&self, position: usize, source: &[u8], state: &mut S) -> Result<Option<T>, E> {
+        let snapshot = state.clone();
+        match self.parser.parse(position, source, state) {
+            Error(error) => Error(error),
+            Fail(_) => {
+                *state = snapshot;
+                Success(position, None)
+            }
+            Success(position, data) => Success(position, Some(data))
+        }
+    }
+}
+
+pub fn optional<T: 'static, S: Clone + 'static, E: ParseFailure>(
+    parser: StatefulParser<T, S, E>
+) -> StatefulParser<Option<T>, S, E> {
+    OptionalParser { parser }.create()
+}
+
+// repeat the parser until it fails (the changes of the failed repetition are undone)
+struct StarParser<T, S, E> {
+    parser: StatefulParser<T, S, E>
+}
+
+impl<T: 'static, S: Clone + 'static, E: ParseFailure> StatefulParse<Vec<T>, S, E> for StarParser<T, S, E> {
+    fn create(&self) -> StatefulParser<Vec<T>, S, E> {
+        Box::new(StarParser { parser: self.parser.clone() })
+    }
+
+    fn parse(&self, position: usize, source: &[u8], state: &mut S) -> Result<Vec<T>, E> {
+        let mut cursor = position;
+        let mut results = Vec::new();
+        loop {
+            let snapshot = state.clone();
+            match self.parser.parse(cursor, source, state) {
+                Error(error) => return Error(error),
+                Fail(_) => {
+                    *state = snapshot;
+                    break
+                }
+                Success(position, data) => {
+                    results.push(data);
+                    // (an empty match would loop forever)
+                    if position == cursor {
+                        break
+                    }
+                    cursor = position;
+                }
+            }
+        }
+        Success(cursor, results)
+    }
+}
+
+pub fn star<T: 'static, S: Clone + 'static, E: ParseFailure>(
+    parser: StatefulParser<T, S, E>
+) -> StatefulParser<Vec<T>, S, E> {
+    StarParser { parser }.create()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive, text};
+
+    #[test]
+    fn declared_names() {
+        // "let x;" declares x, "use x;" only accepts a declared name
+        type Names = Vec<String>;
+        let name = || stateless::<String, Names, ParseError>(text::identifier());
+        let declaration = || with_state(
+            |(_, name): (Vec<u8>, String), names: &mut Names| {
+                names.push(name.clone());
+                Some(format!("let {}", name))
+            },
+            pair(stateless(primitive::tag(b"let ")), name())
+        );
+        // declares the name before failing without the type
+        let typed = process(|(declaration, _)| declaration, pair(declaration(), stateless(primitive::tag(b":int"))));
+        let usage = with_state(
+            |(_, name): (Vec<u8>, String), names: &mut Names| names.contains(&name).then(|| format!("use {}", name)),
+            pair(stateless(primitive::tag(b"use ")), name())
+        );
+        assert_eq!(usage.parse(0, b"use x", &mut vec!["x".to_string()]), Success(5, "use x".to_string()));
+        let error = ParseError::new(0, "value rejected by with_state()").with_kind(ErrorKind::PredicateFailed);
+        assert_eq!(usage.parse(0, b"use x", &mut Names::new()), Fail(error));
+        let statement = process(
+            |(statement, _)| statement,
+            pair(oneof(vec![typed, declaration(), usage]), stateless(primitive::byte(b';')))
+        );
+        let program = star(statement);
+
+        // the failed typed declaration doesn't declare x twice
+        let mut names = Names::new();
+        assert_eq!(program.parse_all(b"let x;use x;", &mut names), Success(12, vec![
+            "let x".to_string(),
+            "use x".to_string()
+        ]));
+        assert_eq!(names, vec!["x"]);
+
+        let mut names = Names::new();
+        let source = b"let a;let b:int;use a;use b;use c;";
+        assert_eq!(program.parse(0, source, &mut names), Success(28, vec![
+            "let a".to_string(),
+            "let b".to_string(),
+            "use a".to_string(),
+            "use b".to_string()
+        ]));
+        assert_eq!(names, vec!["a", "b"]);
+
+        // both declarations of y failed: y is not declared afterwards
+        let mut names = Names::new();
+        assert_eq!(program.parse(0, b"let y:str;use y;", &mut names), Success(0, vec![]));
+        assert_eq!(names, Names::new());
+
+        // the state isn't needed by stateless parsers
+        let p = optional(stateless::<u8, (), ParseError>(primitive::byte(b'a')));
+        assert_eq!(p.parse(0, b"b", &mut ()), Success(0, None));
+    }
+}
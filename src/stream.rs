@@ -0,0 +1,273 @@
+use std::fmt;
+use std::io::{self, Read};
+use crate::{Parse, Parser};
+use crate::Result::*;
+use crate::error::ParseFailure;
+
+// parsing from a std::io::Read, one record at a time (a line, an entry of a log...), without loading the input
+// ReaderSource keeps a buffer of the input: before parsing a record, it reads until the buffer holds more than
+// capacity bytes (or the rest of the input), so the parser sees the same bytes as if it had the whole input,
+// as long as a record and whatever the parser looks at after it fit in capacity bytes
+// - a parser that reaches the end of a full buffer needs more input than it can get:
+//   the record is too long (StreamError::TooLong), and capacity should be raised
+// - lookbehind: the bytes of the records already parsed are dropped,
+//   so backtracking only goes back to the start of the current record
+// - the positions of a parse error are relative to the start of the record (see StreamError::Parse)
+//
+// for record in ReaderSource::new(File::open(path)?).records(&line) { ... }
+
+pub const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum StreamError<E> {
+    Io(io::Error),
+    // offset: where the record starts in the input (the positions of the error start from there)
+    Parse { offset: u64, error: E },
+    // the record starting at offset doesn't fit in the buffer
+    TooLong { offset: u64 }
+}
+
+impl<E: fmt::Display> fmt::Display for StreamError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Io(error) => write!(f, "read error: {}", error),
+            StreamError::Parse { offset, error } => write!(f, "{} (in the record at offset {})", error, offset),
+            StreamError::TooLong { offset } => write!(f, "record at offset {} is longer than the buffer", offset)
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for StreamError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::Io(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl<E> From<io::Error> for StreamError<E> {
+    fn from(error: io::Error) -> Self {
+        StreamError::Io(error)
+    }
+}
+
+pub struct ReaderSource<R> {
+    reader: R,
+    // the unparsed input is buffer[start..]
+    buffer: Vec<u8>,
+    start: usize,
+    // offset of buffer[start] in the input
+    offset: u64,
+    capacity: usize,
+    eof: bool
+}
+
+impl<R: Read> ReaderSource<R> {
+    pub fn new(reader: R) -> ReaderSource<R> {
+        ReaderSource::with_capacity(reader, DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(reader: R, capacity: usize) -> ReaderSource<R> {
+        assert!(capacity > 0, "the buffer of a ReaderSource can't be empty");
+        let buffer = Vec::with_capacity(2 * capacity);
+        ReaderSource { reader, buffer, start: 0, offset: 0, capacity, eof: false }
+    }
+
+    // offset in the input of the next record
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    // read until more than capacity bytes are buffered, or the end of the input
+    // (a parser that stops at the end of the buffer may need more: one byte past the capacity tells it apart
+    // from a record of exactly capacity bytes)
+    fn fill(&mut self) -> io::Result<()> {
+        if self.eof || self.buffer.len() - self.start > self.capacity {
+            return Ok(())
+        }
+        // (the parsed records are dropped once in a while, not after each one)
+        self.buffer.drain(..self.start);
+        self.start = 0;
+        // read up to twice the capacity, so that the next records don't need a refill
+        // (the buffer never grows beyond that)
+        let mut len = self.buffer.len();
+        self.buffer.resize(2 * self.capacity, 0);
+        let result = loop {
+            if len == self.buffer.len() {
+                break Ok(())
+            }
+            match self.reader.read(&mut self.buffer[len..]) {
+                Ok(0) => {
+                    self.eof = true;
+                    break Ok(())
+                }
+                Ok(n) => len += n,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => break Err(error)
+            }
+        };
+        self.buffer.truncate(len);
+        result
+    }
+
+    // the next record, or None at the end of the input
+    // after an error, the source stays at the start of the record that failed
+    pub fn parse_next<T: 'static, E: ParseFailure>(
+        &mut self,
+        parser: &Parser<T, E>
+    ) -> Option<std::result::Result<T, StreamError<E>>> {
+        if let Err(error) = self.fill() {
+            return Some(Err(StreamError::Io(error)))
+        }
+        let available = &self.buffer[self.start..];
+        if available.is_empty() {
+            return None
+        }
+        let offset = self.offset;
+        // the parser reached the end of a buffer that is not the end of the input
+        let truncated = |position: usize| !self.eof && position >= available.len();
+        Some(match parser.parse(0, available) {
+            Success(end, _) if truncated(end) => Err(StreamError::TooLong { offset }),
+            Success(0, _) => Err(StreamError::Parse { offset, error: E::new(0, "empty record") }),
+            Success(end, data) => {
+                self.start += end;
+                self.offset += end as u64;
+                Ok(data)
+            }
+            Fail(error) | Error(error) if truncated(error.position()) => Err(StreamError::TooLong { offset }),
+            Fail(error) | Error(error) => Err(StreamError::Parse { offset, error })
+        })
+    }
+
+    // the records, one at a time (the iteration stops after the first error)
+    pub fn records<T: 'static, E: ParseFailure>(self, parser: &Parser<T, E>) -> Records<'_, R, T, E> {
+        Records { source: self, parser, done: false }
+    }
+}
+
+pub struct Records<'a, R, T, E> {
+    source: ReaderSource<R>,
+    parser: &'a Parser<T, E>,
+    done: bool
+}
+
+impl<R: Read, T: 'static, E: ParseFailure> Iterator for Records<'_, R, T, E> {
+    type Item = std::result::Result<T, StreamError<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+        let record = self.source.parse_next(self.parser);
+        self.done = !matches!(record, Some(Ok(_)));
+        record
+    }
+}
+
+// every record of the input
+pub fn parse_reader<T: 'static, E: ParseFailure>(
+    parser: &Parser<T, E>,
+    reader: impl Read
+) -> std::result::Result<Vec<T>, StreamError<E>> {
+    ReaderSource::new(reader).records(parser).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{combinator, number, primitive, text};
+    use crate::error::{Expected, ParseError};
+
+    // a reader that returns at most size bytes at a time
+    struct Chunks<'a> {
+        data: &'a [u8],
+        size: usize
+    }
+
+    impl Read for Chunks<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let n = self.size.min(buffer.len()).min(self.data.len());
+            buffer[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    // name=value\n
+    fn entry() -> Parser<(String, u64)> {
+        combinator::terminated(
+            combinator::separated_pair(text::identifier(), primitive::byte(b'='), number::uint()),
+            primitive::byte(b'\n')
+        )
+    }
+
+    #[test]
+    fn chunks() {
+        let data = b"a=1\nbb=22\nccc=333\n";
+        let expected = vec![("a".to_string(), 1), ("bb".to_string(), 22), ("ccc".to_string(), 333)];
+        // records split across chunks, and across refills of the buffer
+        let entry = entry();
+        for size in [1, 2, 3, 100] {
+            for capacity in [12, 64] {
+                let records = ReaderSource::with_capacity(Chunks { data, size }, capacity).records(&entry);
+                assert_eq!(records.map(|record| record.unwrap()).collect::<Vec<_>>(), expected);
+            }
+        }
+        assert_eq!(parse_reader(&entry, &data[..]).unwrap(), expected);
+        assert_eq!(parse_reader(&entry, io::empty()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn larger_than_the_buffer() {
+        let data: Vec<u8> = (0..10_000).flat_map(|n| format!("key{}={}\n", n, n * 7).into_bytes()).collect();
+        let mut source = ReaderSource::with_capacity(Chunks { data: &data, size: 7 }, 32);
+        let mut sum = 0;
+        while let Some(record) = source.parse_next(&entry()) {
+            sum += record.unwrap().1;
+        }
+        assert_eq!(sum, 7 * (0..10_000).sum::<u64>());
+        assert_eq!(source.offset(), data.len() as u64);
+        assert_eq!(source.buffer.capacity(), 2 * 32);
+    }
+
+    #[test]
+    fn full_buffer() {
+        // records of exactly capacity bytes, whatever their place in the buffer
+        let line = combinator::terminated(primitive::alpha1(), primitive::byte(b'\n'));
+        let data = b"abc\ndef\nghi\n";
+        for size in [1, 3, 4, 100] {
+            let records = ReaderSource::with_capacity(Chunks { data, size }, 4).records(&line);
+            let records: Vec<Vec<u8>> = records.map(|record| record.unwrap()).collect();
+            assert_eq!(records, vec![b"abc".to_vec(), b"def".to_vec(), b"ghi".to_vec()]);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        // the position of the error is in the record, the offset of the record is in the input
+        let entry = entry();
+        let mut records = ReaderSource::new(&b"a=1\nb=x\nc=3\n"[..]).records(&entry);
+        assert_eq!(records.next().unwrap().unwrap(), ("a".to_string(), 1));
+        match records.next() {
+            Some(Err(StreamError::Parse { offset: 4, error })) => {
+                assert_eq!(error, ParseError::expected(2, Expected::Class("digit")))
+            }
+            record => panic!("unexpected record {:?}", record)
+        }
+        assert!(records.next().is_none());
+
+        // a record that doesn't fit in the buffer
+        let data = b"a=1\nlong_name=12345678901234\n";
+        let mut source = ReaderSource::with_capacity(Chunks { data, size: 3 }, 8);
+        assert!(matches!(source.parse_next(&entry), Some(Ok(_))));
+        assert!(matches!(source.parse_next(&entry), Some(Err(StreamError::TooLong { offset: 4 }))));
+        let error = source.parse_next(&entry).unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "record at offset 4 is longer than the buffer");
+
+        // the last record, cut by the end of the input
+        let error = parse_reader(&entry, &b"a=1\nb=2"[..]).unwrap_err();
+        assert_eq!(error.to_string(), "expected '\\n' at offset 3 (in the record at offset 4)");
+    }
+}
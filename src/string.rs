@@ -0,0 +1,224 @@
+use crate::{combinator, primitive, Parse, Parser, Result};
+use crate::Result::*;
+use crate::error::{ErrorKind, ParseError, ParseFailure};
+
+// parsers over a &str: every position they return is at a char boundary
+// they are byte parsers inside, wrapped so that only the parsers that stop at char boundaries can be built:
+// the primitives of this module read whole chars, the combinators of this module keep the boundaries,
+// and checked() wraps any other byte parser, failing where it would stop inside a char
+// (the values are chars, Strings and &str slices of the source, never bytes to decode)
+//
+// let word = take_while1(char::is_alphabetic);
+// word.parse_all("café") is Ok("café".to_string()), and word.recognize("日本語 text") is Ok("日本語")
+
+pub struct StrParser<T, E = ParseError> {
+    parser: Parser<T, E>
+}
+
+impl<T: 'static, E: ParseFailure> StrParser<T, E> {
+    // position has to be at a char boundary, as for slicing a str (past the end, the parser fails)
+    pub fn parse(&self, position: usize, source: &str) -> Result<T, E> {
+        if position > source.len() {
+            return Fail(E::new(position, "unexpected end of input").with_kind(ErrorKind::UnexpectedEof))
+        }
+        assert!(source.is_char_boundary(position), "position {} is inside a char", position);
+        self.parser.parse(position, source.as_bytes())
+    }
+
+    // the whole source has to be consumed
+    pub fn parse_all(&self, source: &str) -> std::result::Result<T, E> {
+        self.parser.run_all(source.as_bytes())
+    }
+
+    // the part of the source matched from its start
+    pub fn recognize<'a>(&self, source: &'a str) -> std::result::Result<&'a str, E> {
+        match self.parser.parse(0, source.as_bytes()) {
+            Success(end, _) => Ok(&source[..end]),
+            Fail(error) | Error(error) => Err(error)
+        }
+    }
+
+    // the byte parser, for a grammar over bytes
+    pub fn parser(&self) -> Parser<T, E> {
+        self.parser.clone()
+    }
+
+    pub fn map<U: 'static>(self, f: impl Fn(T) -> U + Send + Sync + 'static) -> StrParser<U, E> {
+        StrParser { parser: combinator::process(f, self.parser) }
+    }
+}
+
+impl<T, E: ParseFailure> Clone for StrParser<T, E> {
+    fn clone(&self) -> Self {
+        StrParser { parser: self.parser.clone() }
+    }
+}
+
+// a byte parser, made to fail when it doesn't stop at a char boundary
+struct CheckedParser<T, E> {
+    parser: Parser<T, E>
+}
+
+impl<T: 'static, E: ParseFailure> Parse<T, E> for CheckedParser<T, E> {
+    fn create(&self) -> Parser<T, E> {
+        Box::new(CheckedParser { parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        self.parser.describe_with(depth)
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            // (a continuation byte can't start a char)
+            Success(end, _) if source.get(end).is_some_and(|&b| b & 0xc0 == 0x80) => {
+                Fail(E::new(end, "inside a char").with_kind(ErrorKind::at(end, source)))
+            }
+            result => result
+        }
+    }
+}
+
+pub fn checked<T: 'static, E: ParseFailure>(parser: Parser<T, E>) -> StrParser<T, E> {
+    StrParser { parser: CheckedParser { parser }.create() }
+}
+
+// primitives
+pub fn any_char() -> StrParser<char> {
+    StrParser { parser: primitive::utf8_char() }
+}
+
+pub fn char_where(f: fn(char) -> bool) -> StrParser<char> {
+    StrParser { parser: primitive::utf8_satisfy(f) }
+}
+
+pub fn str_tag(expected: &'static str) -> StrParser<&'static str> {
+    StrParser { parser: combinator::value(expected, primitive::tag_str(expected)) }
+}
+
+// the longest run of chars accepted by f (possibly empty)
+pub fn take_while(f: fn(char) -> bool) -> StrParser<String> {
+    let chars = combinator::star(primitive::utf8_satisfy(f));
+    StrParser { parser: combinator::process(|chars: Vec<char>| chars.into_iter().collect(), chars) }
+}
+
+pub fn take_while1(f: fn(char) -> bool) -> StrParser<String> {
+    let chars = combinator::many1(primitive::utf8_satisfy(f));
+    StrParser { parser: combinator::process(|chars: Vec<char>| chars.into_iter().collect(), chars) }
+}
+
+// combinators (the same as for bytes)
+pub fn pair<A: 'static, B: 'static, E: ParseFailure>(
+    first: StrParser<A, E>,
+    second: StrParser<B, E>
+) -> StrParser<(A, B), E> {
+    StrParser { parser: combinator::pair(first.parser, second.parser) }
+}
+
+pub fn preceded<A: 'static, B: 'static, E: ParseFailure>(
+    prefix: StrParser<A, E>,
+    value: StrParser<B, E>
+) -> StrParser<B, E> {
+    StrParser { parser: combinator::preceded(prefix.parser, value.parser) }
+}
+
+pub fn terminated<A: 'static, B: 'static, E: ParseFailure>(
+    value: StrParser<A, E>,
+    suffix: StrParser<B, E>
+) -> StrParser<A, E> {
+    StrParser { parser: combinator::terminated(value.parser, suffix.parser) }
+}
+
+pub fn delimited<A: 'static, B: 'static, C: 'static, E: ParseFailure>(
+    open: StrParser<A, E>,
+    value: StrParser<B, E>,
+    close: StrParser<C, E>
+) -> StrParser<B, E> {
+    StrParser { parser: combinator::delimited(open.parser, value.parser, close.parser) }
+}
+
+pub fn oneof<T: 'static, E: ParseFailure>(parsers: Vec<StrParser<T, E>>) -> StrParser<T, E> {
+    StrParser { parser: combinator::oneof(parsers.into_iter().map(|p| p.parser).collect()) }
+}
+
+pub fn optional<T: 'static, E: ParseFailure>(parser: StrParser<T, E>) -> StrParser<Option<T>, E> {
+    StrParser { parser: combinator::optional(parser.parser) }
+}
+
+pub fn star<T: 'static, E: ParseFailure>(parser: StrParser<T, E>) -> StrParser<Vec<T>, E> {
+    StrParser { parser: combinator::star(parser.parser) }
+}
+
+pub fn many1<T: 'static, E: ParseFailure>(parser: StrParser<T, E>) -> StrParser<Vec<T>, E> {
+    StrParser { parser: combinator::many1(parser.parser) }
+}
+
+pub fn sep_by<T: 'static, S: 'static, E: ParseFailure>(
+    item: StrParser<T, E>,
+    separator: StrParser<S, E>
+) -> StrParser<Vec<T>, E> {
+    StrParser { parser: combinator::sep_by(item.parser, separator.parser) }
+}
+
+// the text matched by the parser
+pub fn recognize<T: 'static, E: ParseFailure>(parser: StrParser<T, E>) -> StrParser<String, E> {
+    // (the matched bytes are a slice of a str between char boundaries)
+    let text = |bytes: Vec<u8>| String::from_utf8(bytes).expect("a str parser stopped inside a char");
+    StrParser { parser: combinator::process(text, combinator::recognize(parser.parser)) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_text() {
+        let word = take_while1(char::is_alphabetic);
+        assert_eq!(word.parse_all("café"), Ok("café".to_string()));
+        assert_eq!(word.recognize("日本語 text"), Ok("日本語"));
+        assert_eq!(word.parse(0, "naïve!"), Success(6, "naïve".to_string()));
+
+        // "key: value" pairs, with keys in any script
+        let space = take_while(|c| c == ' ');
+        let value = recognize(many1(char_where(|c| !c.is_whitespace() && c != ',')));
+        let entry = pair(terminated(word.clone(), pair(str_tag(":"), space.clone())), value);
+        let entries = sep_by(entry, pair(str_tag(","), space));
+        assert_eq!(entries.parse_all("名前: 東京, ville: Orléans"), Ok(vec![
+            ("名前".to_string(), "東京".to_string()),
+            ("ville".to_string(), "Orléans".to_string())
+        ]));
+
+        // a predicate on chars, not bytes
+        let cjk = char_where(|c| ('\u{4e00}'..='\u{9fff}').contains(&c));
+        assert_eq!(star(cjk).parse(0, "中文abc"), Success(6, vec!['中', '文']));
+        let greeting = oneof(vec![str_tag("こんにちは"), str_tag("bonjour")]);
+        assert_eq!(greeting.map(|s| s.chars().count()).parse_all("こんにちは"), Ok(5));
+        assert_eq!(any_char().parse(3, "日本"), Success(6, '本'));
+    }
+
+    #[test]
+    fn char_boundaries() {
+        // a byte parser that would stop inside of é
+        let first_byte = checked(primitive::take(1));
+        assert_eq!(first_byte.parse(0, "e"), Success(1, b"e".to_vec()));
+        let error = ParseError::new(1, "inside a char").with_kind(ErrorKind::UnexpectedByte { found: 0xa9 });
+        assert_eq!(first_byte.parse(0, "é"), Fail(error.clone()));
+        assert_eq!(first_byte.parse_all("é"), Err(error));
+        // the boundaries of the other parsers
+        let two = checked(primitive::take(2));
+        assert_eq!(two.recognize("éa"), Ok("é"));
+        assert!(two.parse(0, "aé").is_fail());
+        assert!(char_where(|c| c == 'é').parser().parse(0, b"\xc3").is_fail());
+        // past the end of the source, as for the byte parsers
+        let error = ParseError::new(3, "unexpected end of input").with_kind(ErrorKind::UnexpectedEof);
+        assert_eq!(any_char().parse(3, "é"), Fail(error));
+        assert_eq!(star(any_char()).parse(2, "é"), Success(2, vec![]));
+    }
+
+    #[test]
+    #[should_panic(expected = "position 1 is inside a char")]
+    fn position_inside_a_char() {
+        any_char().parse(1, "é");
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::Debug;
+use crate::Parse;
+use crate::Result::*;
+use crate::error::ParseFailure;
+
+// assertions for the tests of a grammar (with the testing feature, as a dev-dependency)
+// assert_parses!(parser, input, value): the parser parses the whole input, into the value
+// assert_parses!(parser, input, value, stop = n): the parser stops at position n (the rest is left), with the value
+// assert_fails!(parser, input): the parser fails (Fail, or a fatal Error), and the failure is returned
+// assert_fails!(parser, input, at = n): the failure is at position n
+// the input is anything that is AsRef<[u8]> ("...", b"...", a Vec<u8>)
+// on a failed assertion, the panic message shows the parser, the input, the position reached,
+// and the value or the failure that was produced
+
+#[macro_export]
+macro_rules! assert_parses {
+    ($parser:expr, $input:expr, $expected:expr $(,)?) => {
+        $crate::test_util::check_parses(&$parser, ::std::convert::AsRef::<[u8]>::as_ref(&$input), $expected, None)
+    };
+    ($parser:expr, $input:expr, $expected:expr, stop = $stop:expr $(,)?) => {
+        $crate::test_util::check_parses(
+            &$parser,
+            ::std::convert::AsRef::<[u8]>::as_ref(&$input),
+            $expected,
+            Some($stop)
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! assert_fails {
+    ($parser:expr, $input:expr $(,)?) => {
+        $crate::test_util::check_fails(&$parser, ::std::convert::AsRef::<[u8]>::as_ref(&$input), None)
+    };
+    ($parser:expr, $input:expr, at = $position:expr $(,)?) => {
+        $crate::test_util::check_fails(&$parser, ::std::convert::AsRef::<[u8]>::as_ref(&$input), Some($position))
+    };
+}
+
+// the implementations of the macros (#[track_caller]: the panic points at the line of the test)
+#[track_caller]
+pub fn check_parses<T: PartialEq + Debug, E: ParseFailure + Debug>(
+    parser: &(impl Parse<T, E> + ?Sized),
+    input: &[u8],
+    expected: T,
+    stop: Option<usize>
+) {
+    let header = header(parser.describe(), input);
+    match parser.parse(0, input) {
+        Fail(error) => {
+            panic!("{}\n  failed at {} of {}: {:?}", header, error.position(), input.len(), error)
+        }
+        Error(error) => {
+            panic!("{}\n  fatal error at {} of {}: {:?}", header, error.position(), input.len(), error)
+        }
+        Success(position, value) => {
+            let end = stop.unwrap_or(input.len());
+            if position != end {
+                panic!(
+                    "{}\n  stopped at {} of {}, expected {} (left: b\"{}\")\n  value: {:?}",
+                    header,
+                    position,
+                    input.len(),
+                    end,
+                    input[position..].escape_ascii(),
+                    value
+                );
+            }
+            if value != expected {
+                panic!(
+                    "{}\n  reached {} of {}\n  value:    {:?}\n  expected: {:?}",
+                    header,
+                    position,
+                    input.len(),
+                    value,
+                    expected
+                );
+            }
+        }
+    }
+}
+
+#[track_caller]
+pub fn check_fails<T: Debug, E: ParseFailure + Debug>(
+    parser: &(impl Parse<T, E> + ?Sized),
+    input: &[u8],
+    at: Option<usize>
+) -> E {
+    let header = header(parser.describe(), input);
+    let error = match parser.parse(0, input) {
+        Success(position, value) => {
+            let len = input.len();
+            panic!("{}\n  succeeded instead of failing, reached {} of {}\n  value: {:?}", header, position, len, value)
+        }
+        Fail(error) | Error(error) => error
+    };
+    if let Some(at) = at {
+        if error.position() != at {
+            let position = error.position();
+            panic!("{}\n  failed at {}, expected a failure at {}\n  failure: {:?}", header, position, at, error);
+        }
+    }
+    error
+}
+
+fn header(description: String, input: &[u8]) -> String {
+    format!("assertion failed for {}\n  input: b\"{}\"", description, input.escape_ascii())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{combinator, number, primitive, Parser};
+    use crate::error::{Expected, ParseError};
+
+    fn panic_message(f: impl FnOnce()) -> String {
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).expect_err("no panic");
+        payload.downcast_ref::<String>().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn assertions() {
+        let numbers = || combinator::sep_by(number::uint(), primitive::byte(b','));
+        assert_parses!(numbers(), b"1,2", vec![1, 2]);
+        assert_parses!(numbers(), "1,2;3", vec![1, 2], stop = 3);
+        assert_parses!(number::uint(), b"42".to_vec(), 42);
+        let error = assert_fails!(number::uint(), "x", at = 0);
+        assert_eq!(error, ParseError::expected(0, Expected::Class("digit")));
+        let error = assert_fails!(combinator::cut(primitive::byte(b'a')), b"b");
+        assert_eq!(error.position, 0);
+    }
+
+    #[test]
+    fn messages() {
+        let message = panic_message(|| assert_parses!(number::uint(), b"12x", 12));
+        assert_eq!(
+            message,
+            "assertion failed for Uint\n  input: b\"12x\"\n  stopped at 2 of 3, expected 3 (left: b\"x\")\n  value: 12"
+        );
+        let message = panic_message(|| assert_parses!(number::uint(), b"12", 13));
+        assert!(message.ends_with("reached 2 of 2\n  value:    12\n  expected: 13"), "{}", message);
+        let message = panic_message(|| assert_parses!(number::uint(), b"\n", 1));
+        assert!(message.contains("input: b\"\\n\"\n  failed at 0 of 1: "), "{}", message);
+        let message = panic_message(|| {
+            assert_fails!(primitive::byte(b'a'), b"a");
+        });
+        assert!(message.ends_with("succeeded instead of failing, reached 1 of 1\n  value: 97"), "{}", message);
+        let p: Parser<u8> = primitive::byte(b'a');
+        let message = panic_message(|| {
+            assert_fails!(p, b"xb", at = 1);
+        });
+        assert!(message.contains("failed at 0, expected a failure at 1"), "{}", message);
+    }
+}
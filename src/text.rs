@@ -0,0 +1,780 @@
+use std::marker::PhantomData;
+use crate::{describe_node, Parse, Parser, Result};
+use crate::combinator::{oneof, process};
+use crate::Result::*;
+use crate::error::{Expected, ParseError, ParseFailure};
+use crate::primitive::run_length;
+#[cfg(feature = "unicode")]
+use crate::primitive::decode_utf8;
+
+// quoted string with backslash escapes
+// the default is a double-quoted string with the \\ \" \n \t \r \0 and \u{XXXX} escapes,
+// the builder methods change the quote and the escape table:
+// QuotedStringParser::new().quote(b'\'').escape(b'\'', '\'').create()
+// (new() builds a parser failing with a ParseError, QuotedStringParser::<E>::default() one failing with E)
+pub struct QuotedStringParser<E = ParseError> {
+    quote: u8,
+    // None for raw strings
+    escape_char: Option<u8>,
+    escapes: Vec<(u8, char)>,
+    unicode: bool,
+    error: PhantomData<fn() -> E>
+}
+
+impl QuotedStringParser {
+    pub fn new() -> Self {
+        QuotedStringParser::default()
+    }
+}
+
+impl<E: ParseFailure> Default for QuotedStringParser<E> {
+    fn default() -> Self {
+        QuotedStringParser {
+            quote: b'"',
+            escape_char: Some(b'\\'),
+            escapes: vec![
+                (b'\\', '\\'),
+                (b'"', '"'),
+                (b'n', '\n'),
+                (b't', '\t'),
+                (b'r', '\r'),
+                (b'0', '\0')
+            ],
+            unicode: true,
+            error: PhantomData
+        }
+    }
+}
+
+impl<E: ParseFailure> QuotedStringParser<E> {
+    pub fn quote(mut self, quote: u8) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    // add (or replace) the escape sequence \c
+    pub fn escape(mut self, c: u8, decoded: char) -> Self {
+        self.escapes.retain(|&(e, _)| e != c);
+        self.escapes.push((c, decoded));
+        self
+    }
+
+    // backslashes are kept as they are
+    pub fn no_escapes(mut self) -> Self {
+        self.escape_char = None;
+        self
+    }
+
+    pub fn unicode(mut self, enabled: bool) -> Self {
+        self.unicode = enabled;
+        self
+    }
+}
+
+impl<E: ParseFailure> Parse<String, E> for QuotedStringParser<E> {
+    fn create(&self) -> Parser<String, E> {
+        Box::new(QuotedStringParser {
+            quote: self.quote,
+            escape_char: self.escape_char,
+            escapes: self.escapes.clone(),
+            unicode: self.unicode,
+            error: PhantomData
+        })
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<String, E> {
+        if source.get(position) != Some(&self.quote) {
+            return Fail(E::expected(position, Expected::Byte(self.quote)))
+        }
+        let mut cursor = position + 1;
+        let mut decoded = Vec::new();
+        loop {
+            match source.get(cursor) {
+                // unterminated string
+                None => return Fail(E::expected(cursor, Expected::Byte(self.quote))),
+                Some(&c) if c == self.quote => break,
+                Some(&c) if Some(c) == self.escape_char => {
+                    let escaped = match source.get(cursor + 1) {
+                        Some(b'u') if self.unicode => {
+                            match (UnicodeEscapeParser { braced: true, json: false }).parse(cursor, source) {
+                                Success(end, c) => Some((end, c)),
+                                Error(error) => return Error(error),
+                                Fail(_) => None
+                            }
+                        }
+                        Some(&e) => self.escapes.iter()
+                            .find(|&&(c, _)| c == e)
+                            .map(|&(_, c)| (cursor + 2, c)),
+                        None => None
+                    };
+                    match escaped {
+                        Some((end, c)) => {
+                            decoded.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                            cursor = end;
+                        }
+                        // unknown or invalid escape sequence
+                        None => return Fail(E::new(cursor, "invalid escape sequence"))
+                    }
+                }
+                Some(&c) => {
+                    decoded.push(c);
+                    cursor += 1;
+                }
+            }
+        }
+        match String::from_utf8(decoded) {
+            Ok(s) => Success(cursor + 1, s),
+            Err(_) => Fail(E::new(position, "invalid utf-8 in string"))
+        }
+    }
+}
+
+pub fn quoted_string() -> Parser<String> {
+    generic::quoted_string()
+}
+
+// unicode escape sequence: \u{XXXXXX} (1 to 6 hex digits) and/or \uXXXX (exactly 4 hex digits)
+// in the 4-digit (json) form, a high surrogate must be followed by an escaped low surrogate,
+// and the pair is combined into a single char
+// lone surrogates and code points above 0x10FFFF are rejected
+struct UnicodeEscapeParser {
+    braced: bool,
+    json: bool
+}
+
+impl UnicodeEscapeParser {
+    fn hex(source: &[u8], start: usize, len: usize) -> Option<u32> {
+        let digits = source.get(start..start + len)?;
+        if !digits.iter().all(u8::is_ascii_hexdigit) {
+            return None
+        }
+        u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+    }
+
+    // \u{...}, starting at the '{'
+    fn braced(position: usize, source: &[u8]) -> Option<(usize, char)> {
+        if source.get(position) != Some(&b'{') {
+            return None
+        }
+        let start = position + 1;
+        let len = run_length(|c| c.is_ascii_hexdigit(), start, source);
+        if len == 0 || len > 6 || source.get(start + len) != Some(&b'}') {
+            return None
+        }
+        let c = char::from_u32(UnicodeEscapeParser::hex(source, start, len)?)?;
+        Some((start + len + 1, c))
+    }
+
+    // \uXXXX or \uXXXX\uXXXX, starting after the first 'u'
+    fn json(position: usize, source: &[u8]) -> Option<(usize, char)> {
+        let high = UnicodeEscapeParser::hex(source, position, 4)?;
+        if !(0xd800..0xdc00).contains(&high) {
+            // not a surrogate pair (char::from_u32 rejects lone low surrogates)
+            return Some((position + 4, char::from_u32(high)?))
+        }
+        if source.get(position + 4..position + 6) != Some(b"\\u") {
+            return None
+        }
+        let low = UnicodeEscapeParser::hex(source, position + 6, 4)?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return None
+        }
+        let c = char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))?;
+        Some((position + 10, c))
+    }
+}
+
+impl<E: ParseFailure> Parse<char, E> for UnicodeEscapeParser {
+    fn create(&self) -> Parser<char, E> {
+        Box::new(UnicodeEscapeParser { braced: self.braced, json: self.json })
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<char, E> {
+        if source.get(position..position + 2) != Some(b"\\u") {
+            return Fail(E::expected(position, Expected::Tag(b"\\u")))
+        }
+        let start = position + 2;
+        let decoded = match source.get(start) {
+            Some(b'{') if self.braced => UnicodeEscapeParser::braced(start, source),
+            Some(_) if self.json => UnicodeEscapeParser::json(start, source),
+            _ => None
+        };
+        match decoded {
+            Some((end, c)) => Success(end, c),
+            None => Fail(E::new(position, "invalid unicode escape"))
+        }
+    }
+}
+
+// both \u{1F600} and \uD83D\uDE00
+pub fn unicode_escape() -> Parser<char> {
+    generic::unicode_escape()
+}
+
+// \u{1F600} only
+pub fn braced_unicode_escape() -> Parser<char> {
+    generic::braced_unicode_escape()
+}
+
+// \uD83D\uDE00 only
+pub fn json_unicode_escape() -> Parser<char> {
+    generic::json_unicode_escape()
+}
+
+// identifier: one byte accepted by first, then any number of bytes accepted by rest
+struct IdentifierParser {
+    first: fn(u8) -> bool,
+    rest: fn(u8) -> bool
+}
+
+impl<E: ParseFailure> Parse<String, E> for IdentifierParser {
+    fn create(&self) -> Parser<String, E> {
+        Box::new(IdentifierParser { first: self.first, rest: self.rest })
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<String, E> {
+        match source.get(position) {
+            Some(&c) if (self.first)(c) => {
+                let end = position + 1 + run_length(self.rest, position + 1, source);
+                match String::from_utf8(source[position..end].to_vec()) {
+                    Ok(s) => Success(end, s),
+                    Err(_) => Fail(E::new(position, "invalid utf-8 in identifier"))
+                }
+            }
+            _ => Fail(E::expected(position, Expected::Class("identifier")))
+        }
+    }
+}
+
+pub(crate) fn is_identifier_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_'
+}
+
+pub(crate) fn is_identifier_continue(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+// ascii identifier: [A-Za-z_][A-Za-z0-9_]*
+pub fn identifier() -> Parser<String> {
+    generic::identifier()
+}
+
+pub fn identifier_with(first: fn(u8) -> bool, rest: fn(u8) -> bool) -> Parser<String> {
+    generic::identifier_with(first, rest)
+}
+
+// unicode identifier (UAX #31): XID_Start or '_', then any number of XID_Continue
+// positions are still in bytes, the utf-8 decoding stops at the first invalid sequence
+#[cfg(feature = "unicode")]
+struct XidIdentifierParser {}
+
+#[cfg(feature = "unicode")]
+impl<E: ParseFailure> Parse<String, E> for XidIdentifierParser {
+    fn create(&self) -> Parser<String, E> {
+        Box::new(XidIdentifierParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<String, E> {
+        let mut identifier = String::new();
+        let mut cursor = position;
+        match decode_utf8(cursor, source) {
+            Some((c, width)) if c == '_' || unicode_ident::is_xid_start(c) => {
+                identifier.push(c);
+                cursor += width;
+            }
+            _ => return Fail(E::expected(position, Expected::Class("identifier")))
+        }
+        while let Some((c, width)) = decode_utf8(cursor, source) {
+            if !unicode_ident::is_xid_continue(c) {
+                break
+            }
+            identifier.push(c);
+            cursor += width;
+        }
+        Success(cursor, identifier)
+    }
+}
+
+#[cfg(feature = "unicode")]
+pub fn xid_identifier() -> Parser<String> {
+    generic::xid_identifier()
+}
+
+// a literal that must not be directly followed by a byte accepted by boundary
+// ("if" matches in "if(" and "if x", but not in "ifx")
+struct KeywordParser {
+    keyword: &'static [u8],
+    boundary: fn(u8) -> bool
+}
+
+impl<E: ParseFailure> Parse<(), E> for KeywordParser {
+    fn create(&self) -> Parser<(), E> {
+        Box::new(KeywordParser { keyword: self.keyword, boundary: self.boundary })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        format!("Keyword(\"{}\")", self.keyword.escape_ascii())
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<(), E> {
+        let end = position + self.keyword.len();
+        if source.get(position..end) != Some(self.keyword) {
+            return Fail(E::expected(position, Expected::Tag(self.keyword)))
+        }
+        match source.get(end) {
+            Some(&c) if (self.boundary)(c) => Fail(E::custom(position, format!(
+                "expected \"{}\" as a whole word",
+                self.keyword.escape_ascii()
+            ))),
+            _ => Success(end, ())
+        }
+    }
+}
+
+// keyword followed by a byte that can't continue an identifier
+pub fn keyword(kw: &'static str) -> Parser<()> {
+    generic::keyword(kw)
+}
+
+pub fn keyword_with(kw: &'static str, boundary: fn(u8) -> bool) -> Parser<()> {
+    generic::keyword_with(kw, boundary)
+}
+
+// "true" or "false", as whole words
+pub fn boolean() -> Parser<bool> {
+    generic::boolean()
+}
+
+// boolean() with the config file spellings: "yes"/"no" and "on"/"off"
+pub fn config_boolean() -> Parser<bool> {
+    generic::config_boolean()
+}
+
+// on failure, suggest the closest alternative to the word found in the input
+// ("did you mean `false`?" for "flase")
+// the word must be close enough: about one edit every three letters of the alternative
+struct SuggestingParser<T, E> {
+    alternatives: Vec<&'static str>,
+    parser: Parser<T, E>
+}
+
+impl<T: 'static, E: ParseFailure> Parse<T, E> for SuggestingParser<T, E> {
+    fn create(&self) -> Parser<T, E> {
+        Box::new(SuggestingParser { alternatives: self.alternatives.clone(), parser: self.parser.clone() })
+    }
+
+    fn describe_with(&self, depth: usize) -> String {
+        describe_node("Suggesting", depth, |depth| {
+            vec![format!("{:?}", self.alternatives), self.parser.describe_with(depth)]
+        })
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<T, E> {
+        match self.parser.parse(position, source) {
+            Fail(error) => match self.suggestion(position, source) {
+                Some(alternative) => Fail(error.with_hint(format!("did you mean `{}`?", alternative))),
+                None => Fail(error)
+            },
+            result => result
+        }
+    }
+}
+
+impl<T, E> SuggestingParser<T, E> {
+    fn suggestion(&self, position: usize, source: &[u8]) -> Option<&'static str> {
+        let word = &source[position.min(source.len())..][..run_length(is_identifier_continue, position, source)];
+        if word.is_empty() {
+            return None
+        }
+        self.alternatives.iter()
+            .map(|alternative| (edit_distance(word, alternative.as_bytes()), *alternative))
+            .filter(|&(distance, alternative)| distance > 0 && distance <= (alternative.len() + 1) / 3)
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, alternative)| alternative)
+    }
+}
+
+pub fn suggesting<T: 'static, E: ParseFailure>(alternatives: Vec<&'static str>, p: Parser<T, E>) -> Parser<T, E> {
+    SuggestingParser { alternatives, parser: p }.create()
+}
+
+// levenshtein distance: the number of byte insertions, deletions and substitutions from a to b
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    // distances from a[..i] to every prefix of b, one row per i
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, &ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// comments
+// the parsed value is the body of the comment, without its delimiters
+
+// from the prefix to the end of the line (the line ending is not consumed)
+struct LineCommentParser {
+    prefix: &'static [u8]
+}
+
+impl<E: ParseFailure> Parse<Vec<u8>, E> for LineCommentParser {
+    fn create(&self) -> Parser<Vec<u8>, E> {
+        Box::new(LineCommentParser { prefix: self.prefix })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        format!("LineComment(\"{}\")", self.prefix.escape_ascii())
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<u8>, E> {
+        let start = position + self.prefix.len();
+        if source.get(position..start) != Some(self.prefix) {
+            return Fail(E::expected(position, Expected::Tag(self.prefix)))
+        }
+        let len = source[start..].iter().position(|&c| c == b'\n').unwrap_or(source.len() - start);
+        // keep the '\r' of a "\r\n" line ending out of the body
+        let body = source[start..start + len].strip_suffix(b"\r").unwrap_or(&source[start..start + len]);
+        Success(start + body.len(), body.to_vec())
+    }
+}
+
+pub fn line_comment(prefix: &'static [u8]) -> Parser<Vec<u8>> {
+    generic::line_comment(prefix)
+}
+
+// delimited comment, optionally allowing nested comments (/* /* */ */)
+// fails if the comment is not closed
+struct BlockCommentParser {
+    open: &'static [u8],
+    close: &'static [u8],
+    nested: bool
+}
+
+impl<E: ParseFailure> Parse<Vec<u8>, E> for BlockCommentParser {
+    fn create(&self) -> Parser<Vec<u8>, E> {
+        Box::new(BlockCommentParser { open: self.open, close: self.close, nested: self.nested })
+    }
+
+    fn describe_with(&self, _depth: usize) -> String {
+        let name = if self.nested { "NestedBlockComment" } else { "BlockComment" };
+        format!("{}(\"{}\", \"{}\")", name, self.open.escape_ascii(), self.close.escape_ascii())
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> Result<Vec<u8>, E> {
+        let start = position + self.open.len();
+        if source.get(position..start) != Some(self.open) {
+            return Fail(E::expected(position, Expected::Tag(self.open)))
+        }
+        let mut depth = 1;
+        let mut cursor = start;
+        while cursor < source.len() {
+            let rest = &source[cursor..];
+            if rest.starts_with(self.close) {
+                depth -= 1;
+                if depth == 0 {
+                    return Success(cursor + self.close.len(), source[start..cursor].to_vec())
+                }
+                cursor += self.close.len();
+            } else if self.nested && rest.starts_with(self.open) {
+                depth += 1;
+                cursor += self.open.len();
+            } else {
+                cursor += 1;
+            }
+        }
+        // unterminated comment
+        Fail(E::expected(source.len(), Expected::Tag(self.close)))
+    }
+}
+
+pub fn block_comment(open: &'static [u8], close: &'static [u8]) -> Parser<Vec<u8>> {
+    generic::block_comment(open, close)
+}
+
+pub fn nested_block_comment(open: &'static [u8], close: &'static [u8]) -> Parser<Vec<u8>> {
+    generic::nested_block_comment(open, close)
+}
+
+// the same parsers, for any error type (the ones above fail with a ParseError)
+// let name: Parser<String, Diagnostic> = text::generic::identifier();
+pub mod generic {
+    use super::*;
+
+    pub fn quoted_string<E: ParseFailure>() -> Parser<String, E> {
+        QuotedStringParser::default().create()
+    }
+
+    pub fn unicode_escape<E: ParseFailure>() -> Parser<char, E> {
+        UnicodeEscapeParser { braced: true, json: true }.create()
+    }
+
+    pub fn braced_unicode_escape<E: ParseFailure>() -> Parser<char, E> {
+        UnicodeEscapeParser { braced: true, json: false }.create()
+    }
+
+    pub fn json_unicode_escape<E: ParseFailure>() -> Parser<char, E> {
+        UnicodeEscapeParser { braced: false, json: true }.create()
+    }
+
+    pub fn identifier<E: ParseFailure>() -> Parser<String, E> {
+        identifier_with(is_identifier_start, is_identifier_continue)
+    }
+
+    pub fn identifier_with<E: ParseFailure>(first: fn(u8) -> bool, rest: fn(u8) -> bool) -> Parser<String, E> {
+        IdentifierParser { first, rest }.create()
+    }
+
+    #[cfg(feature = "unicode")]
+    pub fn xid_identifier<E: ParseFailure>() -> Parser<String, E> {
+        XidIdentifierParser {}.create()
+    }
+
+    pub fn keyword<E: ParseFailure>(kw: &'static str) -> Parser<(), E> {
+        keyword_with(kw, is_identifier_continue)
+    }
+
+    pub fn keyword_with<E: ParseFailure>(kw: &'static str, boundary: fn(u8) -> bool) -> Parser<(), E> {
+        KeywordParser { keyword: kw.as_bytes(), boundary }.create()
+    }
+
+    pub fn boolean<E: ParseFailure>() -> Parser<bool, E> {
+        oneof(vec![
+            process(|_| true, keyword("true")),
+            process(|_| false, keyword("false"))
+        ])
+    }
+
+    pub fn config_boolean<E: ParseFailure>() -> Parser<bool, E> {
+        oneof(vec![
+            boolean(),
+            process(|_| true, keyword("yes")),
+            process(|_| false, keyword("no")),
+            process(|_| true, keyword("on")),
+            process(|_| false, keyword("off"))
+        ])
+    }
+
+    pub fn line_comment<E: ParseFailure>(prefix: &'static [u8]) -> Parser<Vec<u8>, E> {
+        LineCommentParser { prefix }.create()
+    }
+
+    pub fn block_comment<E: ParseFailure>(open: &'static [u8], close: &'static [u8]) -> Parser<Vec<u8>, E> {
+        BlockCommentParser { open, close, nested: false }.create()
+    }
+
+    pub fn nested_block_comment<E: ParseFailure>(open: &'static [u8], close: &'static [u8]) -> Parser<Vec<u8>, E> {
+        BlockCommentParser { open, close, nested: true }.create()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinator::star;
+    use crate::error::ParseError;
+
+    #[test]
+    fn strings() {
+        let p = quoted_string();
+        assert_eq!(p.parse(0, br#""""#), Success(2, String::new()));
+        assert_eq!(p.parse(0, br#""abc" rest"#), Success(5, "abc".to_string()));
+        assert_eq!(p.parse(0, br#""say \"hi\"""#), Success(12, "say \"hi\"".to_string()));
+        assert_eq!(p.parse(0, br#""a\\b\n\t\r\0""#), Success(14, "a\\b\n\t\r\0".to_string()));
+        assert_eq!(p.parse(0, r#""é\u{e9}\u{1F600}""#.as_bytes()), Success(19, "éé😀".to_string()));
+
+        // unterminated, backslash at the end of the input
+        assert!(p.parse(0, br#""abc"#).is_fail());
+        assert!(p.parse(0, br#""abc\"#).is_fail());
+        // unknown and invalid escapes
+        assert!(p.parse(0, br#""\q""#).is_fail());
+        assert!(p.parse(0, br#""\u{D800}""#).is_fail());
+        assert!(p.parse(0, br#""\u{110000}""#).is_fail());
+        assert!(p.parse(0, br#""\u{}""#).is_fail());
+        // invalid utf-8
+        assert!(p.parse(0, b"\"\xff\"").is_fail());
+        // not a string
+        assert!(p.parse(0, b"abc").is_fail());
+
+        // adjacent strings
+        let p = star(quoted_string());
+        assert_eq!(p.parse(0, br#""a""b""#), Success(6, vec!["a".to_string(), "b".to_string()]));
+    }
+
+    #[test]
+    fn single_quoted() {
+        let p = QuotedStringParser::new().quote(b'\'').escape(b'\'', '\'').create();
+        assert_eq!(p.parse(0, br#"'it\'s "ok"'"#), Success(12, "it's \"ok\"".to_string()));
+        assert!(p.parse(0, br#""abc""#).is_fail());
+
+        // raw strings: backslashes are kept as they are
+        let p = QuotedStringParser::new().no_escapes().create();
+        assert_eq!(p.parse(0, br#""a\n""#), Success(5, "a\\n".to_string()));
+    }
+
+    #[test]
+    fn unicode_escapes() {
+        let p = unicode_escape();
+        assert_eq!(p.parse(0, br"\u{0}"), Success(5, '\0'));
+        assert_eq!(p.parse(0, br"\u{1F600}"), Success(9, '😀'));
+        assert_eq!(p.parse(0, br"\u00e9x"), Success(6, 'é'));
+        assert_eq!(p.parse(0, br"\uD83D\uDE00"), Success(12, '😀'));
+
+        // lone surrogates, out of range, malformed
+        assert!(p.parse(0, br"\uD83D").is_fail());
+        assert!(p.parse(0, br"\uD83Dx").is_fail());
+        assert!(p.parse(0, br"\uD83D\u0041").is_fail());
+        assert!(p.parse(0, br"\uDE00").is_fail());
+        assert!(p.parse(0, br"\u{D800}").is_fail());
+        assert!(p.parse(0, br"\u{110000}").is_fail());
+        assert!(p.parse(0, br"\u{1234567}").is_fail());
+        assert!(p.parse(0, br"\u{}").is_fail());
+        assert!(p.parse(0, br"\u{41").is_fail());
+        assert!(p.parse(0, br"\u12").is_fail());
+        assert!(p.parse(0, br"\u+123").is_fail());
+
+        // each syntax can be used on its own
+        assert_eq!(braced_unicode_escape().parse(0, br"\u{41}"), Success(6, 'A'));
+        assert!(braced_unicode_escape().parse(0, br"\u0041").is_fail());
+        assert_eq!(json_unicode_escape().parse(0, br"\u0041"), Success(6, 'A'));
+        assert!(json_unicode_escape().parse(0, br"\u{41}").is_fail());
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(identifier().parse(0, b"_foo1 = 2"), Success(5, "_foo1".to_string()));
+        assert_eq!(identifier().parse(0, b"x"), Success(1, "x".to_string()));
+        assert!(identifier().parse(0, b"1abc").is_fail());
+        assert!(identifier().parse(0, b"").is_fail());
+        // at the very end of the input
+        assert_eq!(identifier().parse(4, b"let abc"), Success(7, "abc".to_string()));
+
+        // lisp-style names
+        let p = identifier_with(|c| c.is_ascii_lowercase(), |c| c.is_ascii_lowercase() || c == b'-');
+        assert_eq!(p.parse(0, b"set-car! x"), Success(7, "set-car".to_string()));
+        assert!(p.parse(0, b"-x").is_fail());
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn unicode_identifiers() {
+        let p = xid_identifier();
+        assert_eq!(p.parse(0, b"_foo1 "), Success(5, "_foo1".to_string()));
+        assert_eq!(p.parse(0, "αβγ = 1".as_bytes()), Success(6, "αβγ".to_string()));
+        assert_eq!(p.parse(0, "変数2+".as_bytes()), Success(7, "変数2".to_string()));
+        // combining marks can continue an identifier, but not start one
+        assert_eq!(p.parse(0, "e\u{301}t\u{e9}".as_bytes()), Success(6, "e\u{301}t\u{e9}".to_string()));
+        assert!(p.parse(0, "\u{301}e".as_bytes()).is_fail());
+
+        // nothing is consumed on failure
+        assert!(p.parse(0, b"1abc").is_fail());
+        assert!(p.parse(0, "→x".as_bytes()).is_fail());
+        assert!(p.parse(0, b"").is_fail());
+
+        // invalid utf-8 ends the identifier
+        assert_eq!(p.parse(0, b"ab\xffcd"), Success(2, "ab".to_string()));
+        assert!(p.parse(0, b"\xffab").is_fail());
+    }
+
+    #[test]
+    fn keywords() {
+        assert_eq!(keyword("if").parse(0, b"if(x)"), Success(2, ()));
+        assert_eq!(keyword("if").parse(0, b"if x"), Success(2, ()));
+        assert!(keyword("if").parse(0, b"ifx").is_fail());
+        assert!(keyword("if").parse(0, b"if_").is_fail());
+        assert_eq!(keyword("if").parse(0, b"if"), Success(2, ()));
+        assert!(keyword("if").parse(0, b"i").is_fail());
+
+        // a failed keyword doesn't consume anything, so the identifier can match
+        let p = oneof(vec![process(|_| None, keyword("if")), process(Some, identifier())]);
+        assert_eq!(p.parse(0, b"if "), Success(2, None));
+        assert_eq!(p.parse(0, b"ifelse "), Success(6, Some("ifelse".to_string())));
+
+        // custom boundary: only letters continue a word
+        let p = keyword_with("x", |c| c.is_ascii_alphabetic());
+        assert_eq!(p.parse(0, b"x1"), Success(1, ()));
+        assert!(p.parse(0, b"xy").is_fail());
+    }
+
+    #[test]
+    fn booleans() {
+        assert_eq!(boolean().parse(0, b"true"), Success(4, true));
+        assert_eq!(boolean().parse(0, b"false)"), Success(5, false));
+        assert!(boolean().parse(0, b"yes").is_fail());
+        // word boundary
+        assert!(boolean().parse(0, b"truex").is_fail());
+
+        let p = oneof(vec![process(|b: bool| b.to_string(), boolean()), identifier()]);
+        assert_eq!(p.parse(0, b"true"), Success(4, "true".to_string()));
+        assert_eq!(p.parse(0, b"true_value"), Success(10, "true_value".to_string()));
+
+        assert_eq!(config_boolean().parse(0, b"on"), Success(2, true));
+        assert_eq!(config_boolean().parse(0, b"off"), Success(3, false));
+        assert_eq!(config_boolean().parse(0, b"no"), Success(2, false));
+        assert_eq!(config_boolean().parse(0, b"yes"), Success(3, true));
+        assert_eq!(config_boolean().parse(0, b"false"), Success(5, false));
+        assert!(config_boolean().parse(0, b"nope").is_fail());
+    }
+
+    #[test]
+    fn suggestions() {
+        assert_eq!(edit_distance(b"flase", b"false"), 2);
+        assert_eq!(edit_distance(b"", b"abc"), 3);
+        assert_eq!(edit_distance(b"kitten", b"sitting"), 3);
+
+        let p = suggesting(vec!["true", "false", "null"], oneof(vec![
+            process(|_| "true", keyword("true")),
+            process(|_| "false", keyword("false")),
+            process(|_| "null", keyword("null"))
+        ]));
+        assert_eq!(p.parse(1, b"[flase]"), Fail(ParseError {
+            expected: vec![Expected::Tag(b"true"), Expected::Tag(b"false"), Expected::Tag(b"null")],
+            ..ParseError::new(1, "")
+        }.with_hint("did you mean `false`?".to_string())));
+        let Fail(error) = p.parse(0, b"nul") else { panic!("no failure") };
+        assert_eq!(error.hint(), Some("did you mean `null`?"));
+        assert_eq!(
+            error.to_string(),
+            r#"expected one of: "true", "false", "null" (did you mean `null`?) at offset 0"#
+        );
+
+        // too far from every alternative, or nothing like a word
+        let Fail(error) = p.parse(0, b"nothing") else { panic!("no failure") };
+        assert_eq!(error.hint(), None);
+        let Fail(error) = p.parse(0, b"+1") else { panic!("no failure") };
+        assert_eq!(error.hint(), None);
+        assert_eq!(p.parse(0, b"true"), Success(4, "true"));
+    }
+
+    #[test]
+    fn comments() {
+        let p = line_comment(b"//");
+        assert_eq!(p.parse(0, b"// note\nx"), Success(7, b" note".to_vec()));
+        assert_eq!(p.parse(0, b"// note\r\nx"), Success(7, b" note".to_vec()));
+        // at the end of the input, without a newline
+        assert_eq!(p.parse(2, b"x // note"), Success(9, b" note".to_vec()));
+        assert_eq!(p.parse(0, b"//"), Success(2, vec![]));
+        assert!(p.parse(0, b"/ note").is_fail());
+
+        let p = block_comment(b"/*", b"*/");
+        assert_eq!(p.parse(0, b"/* a */b"), Success(7, b" a ".to_vec()));
+        assert_eq!(p.parse(0, b"/**/"), Success(4, vec![]));
+        assert!(p.parse(0, b"/* a").is_fail());
+        assert!(p.parse(0, b"/*/").is_fail());
+        // without nesting, the first */ closes the comment
+        assert_eq!(p.parse(0, b"/* /* */ */"), Success(8, b" /* ".to_vec()));
+
+        let p = nested_block_comment(b"/*", b"*/");
+        assert_eq!(p.parse(0, b"/* /* */ */"), Success(11, b" /* */ ".to_vec()));
+        assert!(p.parse(0, b"/* /* */").is_fail());
+
+        let comments = star(oneof(vec![line_comment(b"#"), block_comment(b"(*", b"*)")]));
+        let result = comments.parse(0, b"(* a *)# b");
+        assert_eq!(result, Success(10, vec![b" a ".to_vec(), b" b".to_vec()]));
+    }
+}
@@ -0,0 +1,270 @@
+use std::ops::Range;
+use std::sync::Arc;
+use crate::{Parse, Parser, Result};
+use crate::Result::*;
+use crate::error::{ErrorKind, Expected, ParseError, ParseFailure};
+use crate::items::ItemParser;
+
+// two-stage parsing: a lexer cuts the source into tokens, then a grammar parses the tokens
+// tokenize() runs a byte parser (the lexer) over the source, and keeps the span of each token in the source
+// the token grammar is made of the usual combinators (pair(), oneof(), star()... over the tokens, see items),
+// with token(), token_if() and token_map() to read a token
+//
+// positions: a success is at the index of the next token, but a failure is at an offset in the source text
+// (the start of the span of the token that was rejected, or the end of the last token at the end of input),
+// so that errors point into the source, and compare (merge()) the same way as indices would
+// (use end() and parse_tokens() of this module rather than items::end() and parse_all(), which fail at indices)
+//
+// let tokens = tokenize(&lexer, &multispace0(), source)?;
+// let program = parse_tokens(&statements, &tokens)?;
+
+// a token and its span in the source
+pub type Spanned<T> = (T, Range<usize>);
+
+pub type TokenParser<T, U, E = ParseError> = ItemParser<Spanned<T>, U, E>;
+
+// run the lexer until the end of the source, skipping the junk (whitespace, comments...) before each token
+// fails where no token can be read (a token has to consume something)
+pub fn tokenize<T: 'static, J: 'static, E: ParseFailure>(
+    lexer: &Parser<T, E>,
+    skip: &Parser<J, E>,
+    source: &[u8]
+) -> std::result::Result<Vec<Spanned<T>>, E> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    loop {
+        match skip.parse(position, source) {
+            Success(end, _) => position = end,
+            Fail(_) => {}
+            Error(error) => return Err(error)
+        }
+        if position >= source.len() {
+            return Ok(tokens)
+        }
+        match lexer.parse(position, source) {
+            Success(end, _) if end == position => {
+                return Err(E::new(position, "empty token").with_kind(ErrorKind::at(position, source)))
+            }
+            Success(end, token) => {
+                tokens.push((token, position..end));
+                position = end;
+            }
+            Fail(error) | Error(error) => return Err(error)
+        }
+    }
+}
+
+// where a token parser fails: the start of the token, or the end of the last one
+fn offset<T>(position: usize, tokens: &[Spanned<T>]) -> usize {
+    match tokens.get(position) {
+        Some((_, span)) => span.start,
+        None => tokens.last().map_or(0, |(_, span)| span.end)
+    }
+}
+
+// one token, turned into a value by f (see items::select())
+type TokenFn<T, U> = dyn Fn(&Spanned<T>) -> Option<U> + Send + Sync;
+
+struct TokenSelectParser<T, U> {
+    name: Option<&'static str>,
+    f: Arc<TokenFn<T, U>>
+}
+
+impl<T: 'static, U: 'static, E: ParseFailure> Parse<U, E, Spanned<T>> for TokenSelectParser<T, U> {
+    fn create(&self) -> TokenParser<T, U, E> {
+        Box::new(TokenSelectParser { name: self.name, f: self.f.clone() })
+    }
+
+    fn parse(&self, position: usize, source: &[Spanned<T>]) -> Result<U, E> {
+        if let Some(data) = source.get(position).and_then(|token| (self.f)(token)) {
+            return Success(position + 1, data)
+        }
+        let offset = offset(position, source);
+        match (self.name, source.get(position)) {
+            (Some(name), _) => Fail(E::expected(offset, Expected::Class(name))),
+            (None, Some(_)) => Fail(E::new(offset, "unexpected token")),
+            (None, None) => Fail(E::new(offset, "unexpected end of input").with_kind(ErrorKind::UnexpectedEof))
+        }
+    }
+}
+
+// a token equal to kind, with its span
+pub fn token<T: PartialEq + Clone + Send + Sync + 'static, E: ParseFailure>(
+    kind: T
+) -> TokenParser<T, Spanned<T>, E> {
+    TokenSelectParser { name: None, f: Arc::new(move |token: &Spanned<T>| (token.0 == kind).then(|| token.clone())) }
+        .create()
+}
+
+// a token accepted by the predicate, with its span (fails expecting the name)
+pub fn token_if<T: Clone + 'static, E: ParseFailure>(
+    name: &'static str,
+    predicate: impl Fn(&T) -> bool + Send + Sync + 'static
+) -> TokenParser<T, Spanned<T>, E> {
+    let f = move |token: &Spanned<T>| predicate(&token.0).then(|| token.clone());
+    TokenSelectParser { name: Some(name), f: Arc::new(f) }.create()
+}
+
+// the value of a token (the payload of an identifier or a number token...), where f returns one
+pub fn token_map<T: 'static, U: 'static, E: ParseFailure>(
+    name: &'static str,
+    f: impl Fn(&T) -> Option<U> + Send + Sync + 'static
+) -> TokenParser<T, U, E> {
+    TokenSelectParser { name: Some(name), f: Arc::new(move |token: &Spanned<T>| f(&token.0)) }.create()
+}
+
+// succeeds after the last token only
+struct EndParser {}
+
+impl<T: 'static, E: ParseFailure> Parse<(), E, Spanned<T>> for EndParser {
+    fn create(&self) -> TokenParser<T, (), E> {
+        Box::new(EndParser {})
+    }
+
+    fn parse(&self, position: usize, source: &[Spanned<T>]) -> Result<(), E> {
+        if position >= source.len() {
+            return Success(position, ())
+        }
+        Fail(E::expected(offset(position, source), Expected::EndOfInput))
+    }
+}
+
+pub fn end<T: 'static, E: ParseFailure>() -> TokenParser<T, (), E> {
+    EndParser {}.create()
+}
+
+// parse all the tokens
+pub fn parse_tokens<T: 'static, U: 'static, E: ParseFailure>(
+    parser: &TokenParser<T, U, E>,
+    tokens: &[Spanned<T>]
+) -> std::result::Result<U, E> {
+    match parser.parse(0, tokens) {
+        Success(position, data) if position >= tokens.len() => Ok(data),
+        Success(position, _) => Err(E::expected(offset(position, tokens), Expected::EndOfInput)),
+        Fail(error) | Error(error) => Err(error)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{combinator, number, primitive, text};
+    use crate::combinator::{oneof, pair, preceded, process, star, terminated};
+
+    #[derive(Clone, Eq, PartialEq, Debug)]
+    enum Token {
+        Let,
+        Print,
+        Name(String),
+        Number(u64),
+        Equal,
+        Plus,
+        Semicolon
+    }
+
+    // keywords are names until the lexer says otherwise: no backtracking over "let" in "letter"
+    fn lexer() -> Parser<Token> {
+        combinator::oneof(vec![
+            combinator::process(|name: String| match name.as_str() {
+                "let" => Token::Let,
+                "print" => Token::Print,
+                _ => Token::Name(name)
+            }, text::identifier()),
+            combinator::process(Token::Number, number::uint()),
+            combinator::value(Token::Equal, primitive::byte(b'=')),
+            combinator::value(Token::Plus, primitive::byte(b'+')),
+            combinator::value(Token::Semicolon, primitive::byte(b';'))
+        ])
+    }
+
+    #[derive(Eq, PartialEq, Debug)]
+    enum Statement {
+        Let(String, Vec<Operand>),
+        Print(Vec<Operand>)
+    }
+
+    #[derive(Eq, PartialEq, Debug)]
+    enum Operand {
+        Number(u64),
+        Name(String)
+    }
+
+    // statement = ("let" name "=" sum | "print" sum) ";", sum = operand ("+" operand)*
+    fn statement() -> TokenParser<Token, Statement> {
+        let name = || token_map("name", |token: &Token| match token {
+            Token::Name(name) => Some(name.clone()),
+            _ => None
+        });
+        let operand = || oneof(vec![
+            process(Operand::Name, name()),
+            token_map("number", |token: &Token| match token {
+                Token::Number(n) => Some(Operand::Number(*n)),
+                _ => None
+            })
+        ]);
+        let sum = || process(|(first, mut rest): (Operand, Vec<Operand>)| {
+            rest.insert(0, first);
+            rest
+        }, pair(operand(), star(preceded(token(Token::Plus), operand()))));
+        let assignment = process(
+            |(name, sum)| Statement::Let(name, sum),
+            pair(preceded(token(Token::Let), name()), preceded(token(Token::Equal), sum()))
+        );
+        let print = process(Statement::Print, preceded(token(Token::Print), sum()));
+        terminated(oneof(vec![assignment, print]), token(Token::Semicolon))
+    }
+
+    fn program() -> TokenParser<Token, Vec<Statement>> {
+        terminated(star(statement()), end())
+    }
+
+    #[test]
+    fn statements() {
+        let source = b"let letter = 1 + x;\nprint letter;";
+        let tokens = tokenize(&lexer(), &primitive::multispace0(), source).unwrap();
+        assert_eq!(tokens.len(), 10);
+        assert_eq!(tokens[1], (Token::Name("letter".to_string()), 4..10));
+        assert_eq!(tokens[9], (Token::Semicolon, 32..33));
+        assert_eq!(parse_tokens(&program(), &tokens), Ok(vec![
+            Statement::Let("letter".to_string(), vec![Operand::Number(1), Operand::Name("x".to_string())]),
+            Statement::Print(vec![Operand::Name("letter".to_string())])
+        ]));
+        assert_eq!(tokenize(&lexer(), &primitive::multispace0(), b"  "), Ok(vec![]));
+        // the spans of the token parsers
+        let name = token_if("name", |token| matches!(token, Token::Name(_)));
+        let spanned: TokenParser<Token, _> = pair(token(Token::Let), name);
+        let Success(2, ((_, let_span), (_, name_span))) = spanned.parse(0, &tokens) else { panic!("no match") };
+        assert_eq!((let_span, name_span), (0..3, 4..10));
+    }
+
+    #[test]
+    fn lexer_errors() {
+        // the offset of the byte that no token starts with
+        let error = tokenize(&lexer(), &primitive::multispace0(), b"let x = 1 @ 2;").unwrap_err();
+        assert_eq!(error.position, 10);
+        let error = tokenize(&primitive::multispace0(), &primitive::multispace0(), b"x").unwrap_err();
+        assert_eq!(error, ParseError::new(0, "empty token").with_kind(ErrorKind::UnexpectedByte { found: b'x' }));
+    }
+
+    #[test]
+    fn parser_errors() {
+        let parse = |source: &[u8]| {
+            let tokens = tokenize(&lexer(), &primitive::multispace0(), source).unwrap();
+            parse_tokens(&statement(), &tokens)
+        };
+        // "=" is at offset 6, where a name was expected
+        assert_eq!(parse(b"let\n  = 2;"), Err(ParseError::expected(6, Expected::Class("name"))));
+        // the operands of print are names or numbers
+        let error = parse(b"print  let;").unwrap_err();
+        assert_eq!(error.to_string(), "expected one of: name, number at offset 7");
+        // (an operand that fails after a + ends the sum before the +)
+        assert_eq!(parse(b"print 1 + let;"), Err(ParseError::new(8, "unexpected token")));
+        // the missing ; is after the last token
+        let error = parse(b"print 1 + 2  ").unwrap_err();
+        assert_eq!(error, ParseError::new(11, "unexpected end of input").with_kind(ErrorKind::UnexpectedEof));
+        // a whole program stops before the statement that fails
+        let tokens = tokenize(&lexer(), &primitive::multispace0(), b"print 1;\nprint;").unwrap();
+        assert_eq!(parse_tokens(&program(), &tokens), Err(ParseError::expected(9, Expected::EndOfInput)));
+    }
+}
@@ -0,0 +1,66 @@
+// #[derive(Parse)] (the derive crate is a dev-dependency, the parser crate re-exports it with its derive feature)
+
+use parser::prelude::*;
+use parser::Result::{Fail, Success};
+use parser::text;
+use parser_derive::Parse;
+
+#[derive(Parse, PartialEq, Debug)]
+#[parse(sep = ";")]
+struct Point {
+    #[parse(tag = "x=")]
+    x: i64,
+    #[parse(tag = "y=")]
+    y: i64
+}
+
+#[derive(Parse, PartialEq, Debug)]
+#[parse(skip_ws)]
+enum Shape {
+    #[parse(tag = "circle")]
+    Circle(u32),
+    #[parse(tag = "rect", sep = ",")]
+    Rect {
+        width: u32,
+        height: u32
+    },
+    #[parse(tag = "empty")]
+    Empty
+}
+
+fn word() -> Parser<String> {
+    text::identifier()
+}
+
+#[derive(Parse, PartialEq, Debug)]
+#[parse(tag = "(", sep = " ")]
+struct Labeled(#[parse(with = "word")] String, Point);
+
+#[test]
+fn structs() {
+    assert_eq!(parse_value::<Point>(b"x=1;y=2"), Ok(Point { x: 1, y: 2 }));
+    assert_eq!(parse_value::<Point>(b"x=-1;y=+2"), Ok(Point { x: -1, y: 2 }));
+    assert_eq!(Point::parser().parse(0, b"x=1,y=2"), Fail(ParseError::expected(3, Expected::Tag(b";"))));
+    assert_eq!(Point::parser().parse(0, b"x=1;y=2;"), Success(7, Point { x: 1, y: 2 }));
+    // no whitespace without skip_ws
+    assert!(parse_value::<Point>(b"x=1; y=2").is_err());
+
+    // a parser given by a function, and a field of a derived type
+    let labeled = Labeled("a".to_string(), Point { x: 3, y: 4 });
+    assert_eq!(parse_value::<Labeled>(b"(a x=3;y=4"), Ok(labeled));
+    assert_eq!("x=5;y=6".parse::<Parsed<Point>>(), Ok(Parsed(Point { x: 5, y: 6 })));
+}
+
+#[test]
+fn enums() {
+    assert_eq!(parse_value::<Shape>(b"circle 5"), Ok(Shape::Circle(5)));
+    assert_eq!(parse_value::<Shape>(b"  rect 2 , 3"), Ok(Shape::Rect { width: 2, height: 3 }));
+    assert_eq!(parse_value::<Shape>(b"empty"), Ok(Shape::Empty));
+    let error = parse_value::<Shape>(b"square 1").unwrap_err();
+    assert_eq!(error.expected, vec![Expected::Tag(b"circle"), Expected::Tag(b"rect"), Expected::Tag(b"empty")]);
+
+    // in a grammar, like any other parser
+    let shapes = sep_by(Shape::parser(), byte(b';'));
+    let expected = vec![Shape::Empty, Shape::Circle(1)];
+    assert_eq!(shapes.parse_str_all("empty; circle 1"), Ok(expected));
+}
@@ -0,0 +1,70 @@
+// the crate as a dependency: only the public api is used here
+
+use parser::prelude::*;
+use parser::Result::{Fail, Success};
+use parser::{grammar, number, seq, text};
+
+// a parser written outside of the crate
+struct Letters {}
+
+impl Parse<String> for Letters {
+    fn create(&self) -> Parser<String> {
+        Box::new(Letters {})
+    }
+
+    fn parse(&self, position: usize, source: &[u8]) -> parser::Result<String> {
+        let len = source[position..].iter().take_while(|c| c.is_ascii_alphabetic()).count();
+        if len == 0 {
+            return Fail(ParseError::expected(position, Expected::Class("letter")))
+        }
+        Success(position + len, String::from_utf8_lossy(&source[position..position + len]).into_owned())
+    }
+}
+
+#[test]
+fn combinators() {
+    let key = Letters {}.create();
+    let entry = separated_pair(key, byte(b'='), number::uint());
+    let entries = sep_by(entry, lexeme(byte(b',')));
+    let parsed = entries.run_all(b"a=1, bc=2").unwrap();
+    assert_eq!(parsed, vec![("a".to_string(), 1), ("bc".to_string(), 2)]);
+
+    let error = entries.run_all(b"a=1, =2").unwrap_err();
+    assert_eq!(error.position, 3);
+    assert!(error.all_kinds().contains(&ErrorKind::ExpectedEnd));
+
+    // methods, operators and macros
+    let word = (tag(b"on") | tag(b"off")).map(|word| word == b"on");
+    assert_eq!(word.parse(0, b"off"), Success(3, false));
+    let quoted = seq!(byte(b'<'), text::identifier(), byte(b'>'));
+    assert_eq!(quoted.run(b"<x>").map(|(_, (_, name, _))| name), Ok("x".to_string()));
+
+    // entry points from the start of the input
+    assert_eq!(number::uint().parse_str("12 apples"), Success(2, 12));
+    assert_eq!(number::uint().parse_str_all("12"), Ok(12));
+}
+
+#[test]
+fn grammars() {
+    grammar! {
+        list: Vec<u64> = "[" ~ items? ~ "]" => |(_, items, _)| items.unwrap_or_default();
+        items: Vec<u64> = {sep_by1(number::uint(), byte(b','))};
+    }
+    assert_eq!(list().run_all(b"[1,2]"), Ok(vec![1, 2]));
+    assert_eq!(list().run_all(b"[]"), Ok(vec![]));
+    let error = list().run_all(b"[1,").unwrap_err();
+    assert_eq!(error.to_string(), r#"expected "]" at offset 2"#);
+}
+
+// the macros don't rely on the prelude of the calling crate
+#[no_implicit_prelude]
+mod without_prelude {
+    ::parser::grammar! {
+        sign: ::std::vec::Vec<u8> = "+" | b"-";
+    }
+
+    #[test]
+    fn grammar_without_prelude() {
+        ::std::assert!(::parser::Parse::parse(&sign(), 0, b"-").is_success());
+    }
+}